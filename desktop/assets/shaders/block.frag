@@ -1,8 +1,17 @@
 #version 330 core
 out vec4 FragColor;
-in vec2 UV;
+in vec2 TileUV;
+flat in uint TexId;
+in float Brightness;
 uniform sampler2D myTextureSampler;
+uniform float alpha_cutoff; // 0 for blended faces, cutout faces discard fragments below it
 void main()
 {
-    FragColor = texture( myTextureSampler, UV );
+    const float single_block_u = 1./64.; // Texture consists of 64 blocks placed in a row along x axis.
+    // Wrapping with fract breaks derivatives at tile borders, so mipmap level is chosen from the unwrapped coordinates
+    vec2 unwrapped = vec2((float(TexId) + TileUV.x) * single_block_u, TileUV.y);
+    vec2 uv = vec2((float(TexId) + fract(TileUV.x)) * single_block_u, fract(TileUV.y));
+    vec4 color = textureGrad( myTextureSampler, uv, dFdx(unwrapped), dFdy(unwrapped) );
+    if (color.a < alpha_cutoff) discard;
+    FragColor = vec4(color.rgb * Brightness, color.a);
 }
\ No newline at end of file
@@ -1,7 +1,10 @@
 #version 330 core
 layout (location = 11) in uvec4 coords;
-layout (location = 13) in uint tex_id;
-out vec2 UV;
+layout (location = 12) in uvec4 shading; // sky light, block light (both in range 0-15), ambient occlusion of 4 corners (2 bits each), quad size-1 along both tangent axes (4 bits each)
+layout (location = 13) in uint tex_id; // texture id, the two highest bits hold the shape (0 full block, 1 lower half, 2 upper half)
+out vec2 TileUV; // in units of texture tiles, so that merged quads repeat the texture
+flat out uint TexId;
+out float Brightness;
 uniform vec3 chunk_location;
 
 layout (std140) uniform Matrices
@@ -63,7 +66,26 @@ void main()
     uint orientation = coords.w;
     vec3 block_position = vec3(float(x),float(y),float(z));
     vec3 vertex_pos = vertices[orientation*uint(6) + uint(gl_VertexID)];
-    gl_Position = MVP * vec4(vertex_pos+block_position+chunk_location, 1.0);
+    // Tangent axes of the face are x,z for top and bottom faces, y,z for left and right faces, x,y for front and back faces
+    vec2 tangent_pos = orientation < uint(2) ? vertex_pos.xz : (orientation < uint(4) ? vertex_pos.yz : vertex_pos.xy);
+    uint shape = tex_id >> uint(30);
     vec2 uv = texture_uv[orientation*uint(6) + uint(gl_VertexID)];
-    UV = vec2(uv.x + float(tex_id)*single_block_u,uv.y);
+    if (shape != uint(0)) {
+        // slabs are squeezed into their half of the block. Texture v of side faces follows the height, so they show the matching half of it
+        vertex_pos.y = vertex_pos.y * 0.5 + (shape == uint(2) ? 0.5 : 0.0);
+        if (orientation >= uint(2)) uv.y = vertex_pos.y;
+    }
+    vec2 quad_size = vec2(float((shading.w & uint(15)) + uint(1)), float((shading.w >> uint(4)) + uint(1)));
+    vec3 scale = orientation < uint(2) ? vec3(quad_size.x, 1, quad_size.y) : (orientation < uint(4) ? vec3(1, quad_size.x, quad_size.y) : vec3(quad_size.x, quad_size.y, 1));
+    gl_Position = MVP * vec4(vertex_pos*scale+block_position+chunk_location, 1.0);
+    // Texture u follows the first tangent axis, except for left and right faces where it follows the second one
+    vec2 uv_tiles = orientation == uint(2) || orientation == uint(3) ? quad_size.yx : quad_size;
+    TileUV = vec2(uv.x / single_block_u, uv.y) * uv_tiles;
+    TexId = tex_id & uint(0x3FFFFFFF);
+    // Every level of light is 20% dimmer than the one above. Faces never get completely black.
+    float light_level = float(max(shading.x, shading.y));
+    // Corner index is given by the vertex position along the tangent axes of the face
+    uint corner = uint(tangent_pos.x) + uint(2) * uint(tangent_pos.y);
+    uint ambient_occlusion = (shading.z >> (uint(2) * corner)) & uint(3);
+    Brightness = max(pow(0.8, 15.0 - light_level), 0.05) * (0.4 + 0.2 * float(ambient_occlusion));
 }
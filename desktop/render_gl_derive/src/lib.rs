@@ -95,9 +95,9 @@ fn generate_impl(ast: &syn::DeriveInput) -> proc_macro2::TokenStream {
     quote!{
         impl VertexAttribPointers for #ident #generics #where_clause {
             #[allow(unused_variables)]
-            fn vertex_attrib_pointers(gl: &::gl::Gl){
-                let offset:usize = 0;
+            fn vertex_attrib_pointers_from(gl: &::gl::Gl, first: usize){
                 let stride = ::std::mem::size_of::<Self>(); // byte offset between consecutive attributes
+                let offset:usize = first * stride;
                 #(#fields_vertex_attrib_pointer)*
             }
         }
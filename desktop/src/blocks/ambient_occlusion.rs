@@ -0,0 +1,39 @@
+use crate::blocks::world_blocks::WorldBlocks;
+use crate::blocks::face_orientation::FaceOrientation;
+
+/**Tangent axes (u,v) spanning a face of the given orientation. The corner of a face lying at
+tangent coordinates (cu,cv) has index cu + 2*cv. This must match the corner numbering in block.vert*/
+pub fn tangent_axes(ort: FaceOrientation) -> ((i32, i32, i32), (i32, i32, i32)) {
+    match ort {
+        FaceOrientation::YPlus | FaceOrientation::YMinus => ((1, 0, 0), (0, 0, 1)),
+        FaceOrientation::XPlus | FaceOrientation::XMinus => ((0, 1, 0), (0, 0, 1)),
+        FaceOrientation::ZPlus | FaceOrientation::ZMinus => ((1, 0, 0), (0, 1, 0)),
+    }
+}
+
+/**Ambient occlusion of the four corners of a face, packed as 2 bits per corner (corner i takes bits 2i and 2i+1).
+Each corner is darkened by the opaque blocks among its two side neighbours and its diagonal neighbour
+lying in front of the face. Value 3 means no occlusion and 0 means the corner is fully enclosed.*/
+pub fn face_ambient_occlusion(blocks: &WorldBlocks, x: i32, y: i32, z: i32, ort: FaceOrientation) -> u8 {
+    let (nx, ny, nz) = ort.offset();
+    let (qx, qy, qz) = (x + nx, y + ny, z + nz);
+    let ((ux, uy, uz), (vx, vy, vz)) = tangent_axes(ort);
+    let occludes = |du: i32, dv: i32| blocks.try_get_block(qx + ux * du + vx * dv, qy + uy * du + vy * dv, qz + uz * du + vz * dv)
+        .map(|b| !b.is_transparent())
+        .unwrap_or(false);
+    let mut ao = 0;
+    for cv in 0..2 {
+        for cu in 0..2 {
+            let (du, dv) = (cu * 2 - 1, cv * 2 - 1);
+            let side_u = occludes(du, 0);
+            let side_v = occludes(0, dv);
+            let corner_ao = if side_u && side_v {
+                0
+            } else {
+                3 - side_u as u8 - side_v as u8 - occludes(du, dv) as u8
+            };
+            ao |= corner_ao << (2 * (cu + 2 * cv));
+        }
+    }
+    ao
+}
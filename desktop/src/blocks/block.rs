@@ -1,9 +1,14 @@
 use std::fmt::{Display, Formatter};
-use crate::blocks::block_properties::{BLOCKS, STONE};
+use crate::blocks::block_properties::BlockProp;
+use crate::blocks::block_registry::registry;
+use crate::blocks::block_state::{BlockState, Axis, Orientation};
 use crate::render_gl::data::VertexAttribPointers;
 use crate::blocks::face_orientation::FaceOrientation;
+use crate::blocks::face::FaceShape;
 use crate::render_gl::data::VertexAttrib;
 
+/**Lower 16 bits hold the id of the block in the registry and upper 16 bits hold its BlockState.
+Blocks are equal only if both their ids and states are equal. Use is_same_kind to compare only ids*/
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 #[repr(C, packed)]
 #[derive(VertexAttribPointers)]
@@ -20,6 +25,9 @@ impl Display for Block {
     }
 }
 
+const ID_MASK: u32 = 0xFFFF;
+const STATE_SHIFT: u32 = 16;
+
 impl Block {
     pub const fn air() -> Self {
         Self::new(0)
@@ -27,24 +35,95 @@ impl Block {
     pub const fn new(idx: u32) -> Self {
         Self { idx }
     }
-    pub fn weight(&self) -> u32 {
-        (self.idx - 10).max(0)
+    pub fn idx(&self) -> u32 {
+        self.idx & ID_MASK
+    }
+    pub fn state(&self) -> BlockState {
+        BlockState::from_bits((self.idx >> STATE_SHIFT) as u16)
+    }
+    pub fn with_state(&self, state: BlockState) -> Self {
+        Self { idx: self.idx() | ((state.bits() as u32) << STATE_SHIFT) }
+    }
+    pub fn is_same_kind(&self, other: Block) -> bool {
+        self.idx() == other.idx()
+    }
+    /**The same kind of block in the state it should have when placed against the given face
+    of another block by a player looking along (look_x, look_z)*/
+    pub fn placed(&self, against: FaceOrientation, look_x: f32, look_z: f32) -> Self {
+        self.with_state(BlockState::placed(self.props().orientation(), against, look_x, look_z))
+    }
+    /**The same block turned by the given number of quarter turns around the Y axis*/
+    pub fn rotated_y(&self, turns: usize) -> Self {
+        self.with_state(self.state().rotated_y(self.props().orientation(), turns))
+    }
+    /**The same block reflected across a plane perpendicular to the given axis*/
+    pub fn mirrored(&self, axis: Axis) -> Self {
+        self.with_state(self.state().mirrored(self.props().orientation(), axis))
+    }
+    /**Properties of this block as defined in the global block registry*/
+    pub fn props(&self) -> &'static BlockProp {
+        registry().get(*self)
+    }
+    pub fn has_gravity(&self) -> bool {
+        self.props().has_gravity()
+    }
+    pub fn hardness(&self) -> f32 {
+        self.props().hardness()
+    }
+    pub fn blast_resistance(&self) -> f32 {
+        self.props().blast_resistance()
     }
     pub fn is_solid(&self) -> bool {
-        self.idx > 0
+        self.props().is_solid()
+    }
+    /**Box occupied by the block relative to its minimal corner, or None if things can pass through it.
+    Slabs occupy only the half given by their state*/
+    pub fn collision_box(&self) -> Option<(glm::Vec3, glm::Vec3)> {
+        if !self.is_solid() {
+            None
+        } else if self.props().orientation() == Orientation::Half {
+            let bottom = if self.state().is_top_half() { 0.5 } else { 0. };
+            Some((glm::vec3(0., bottom, 0.), glm::vec3(1., bottom + 0.5, 1.)))
+        } else {
+            Some((glm::vec3(0., 0., 0.), glm::vec3(1., 1., 1.)))
+        }
     }
     pub fn is_transparent(&self) -> bool {
-        self.idx < STONE.idx
+        self.props().is_transparent()
+    }
+    /**Faces of blended blocks are drawn after all others, sorted from back to front.
+    Cutout blocks are transparent, but drawn together with opaque ones*/
+    pub fn is_blended(&self) -> bool {
+        self.is_transparent() && !self.props().is_cutout()
     }
     pub fn is_air(&self) -> bool {
-        self.idx == 0
+        self.idx() == 0
     }
     pub fn texture_id(&self, ort: FaceOrientation) -> u32 {
-        BLOCKS[self.idx as usize].get_texture_id(ort)
+        self.props().get_texture_id(self.state(), ort)
     }
     pub fn name(&self) -> &'static str {
-        BLOCKS[self.idx as usize].name()
+        self.props().name()
+    }
+    pub fn light_emission(&self) -> u8 {
+        self.props().light_emission()
+    }
+    pub fn light_filter(&self) -> u8 {
+        self.props().light_filter()
+    }
+    /**Slabs fill only half of their block, so they never hide faces of their neighbours and none of their own faces are hidden*/
+    pub fn is_full_cube(&self) -> bool {
+        self.props().orientation() != Orientation::Half
+    }
+    pub fn face_shape(&self) -> FaceShape {
+        if self.is_full_cube() {
+            FaceShape::Full
+        } else if self.state().is_top_half() {
+            FaceShape::UpperHalf
+        } else {
+            FaceShape::LowerHalf
+        }
     }
-    pub fn show_neighboring_faces(&self) -> bool { self.is_transparent() }
+    pub fn show_neighboring_faces(&self) -> bool { self.is_transparent() || !self.is_full_cube() }
     pub fn show_my_faces(&self) -> bool { !self.is_air() }
 }
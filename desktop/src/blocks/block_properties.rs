@@ -1,25 +1,69 @@
 use crate::blocks::{FaceOrientation, Block};
+use crate::blocks::block_registry::registry;
+use crate::blocks::block_state::{BlockState, Orientation};
 
+/**Properties of a single kind of block as defined in assets/blocks.txt*/
+#[derive(Clone, Debug)]
 pub struct BlockProp{
-    name:&'static str,
-    texture_ids:[u32;6],
+    pub(crate) name:String,
+    pub(crate) texture_ids:[u32;6],
+    /**Faces of neighbouring blocks are visible through transparent blocks*/
+    pub(crate) transparent:bool,
+    /**Entities collide with solid blocks*/
+    pub(crate) solid:bool,
+    /**Level of block light emitted by this block*/
+    pub(crate) light_emission:u8,
+    /**How much light is lost when passing through this block. Opaque blocks stop it completely*/
+    pub(crate) light_filter:u8,
+    /**Seconds needed to break the block. Negative hardness means the block is unbreakable*/
+    pub(crate) hardness:f32,
+    /**How much an explosion weakens when passing through this block. Negative resistance means the block can't be blown up*/
+    pub(crate) blast_resistance:f32,
+    /**Blocks with gravity fall down when there is nothing below them*/
+    pub(crate) gravity:bool,
+    /**Transparent blocks that are alpha tested instead of blended, so their faces don't need sorting*/
+    pub(crate) cutout:bool,
+    /**Which part of the block state rotates the textures and is set on placement*/
+    pub(crate) orientation:Orientation,
 }
 
 impl BlockProp{
-    const fn regular(name:&'static str, texture_id:u32)->Self{
-        Self{name,texture_ids:[texture_id;6]}
+    pub fn new(name:String)->Self{
+        Self{name,texture_ids:[0;6],transparent:false,solid:true,light_emission:0,light_filter:15,hardness:1.,blast_resistance:1.,gravity:false,cutout:false,orientation:Orientation::Fixed}
     }
-    const fn top_sides_bottom(name:&'static str, texture_id_top:u32,texture_id_side:u32,texture_id_bottom:u32)->Self{
-        Self{name,texture_ids:[texture_id_top,texture_id_bottom,texture_id_side,texture_id_side,texture_id_side,texture_id_side]}
+    /**Textures of oriented blocks are rotated according to their state*/
+    pub fn get_texture_id(&self, state:BlockState, ort:FaceOrientation)->u32{
+        self.texture_ids[state.unrotated_face(self.orientation, ort) as usize]
     }
-    const fn top_sides_bottom_front(name:&'static str, texture_id_top:u32,texture_id_side:u32,texture_id_bottom:u32,texture_id_front:u32)->Self{
-        Self{name,texture_ids:[texture_id_top,texture_id_bottom,texture_id_side,texture_id_side,texture_id_side,texture_id_front]}
+    pub fn name(&self)->&str{
+        &self.name
     }
-    pub fn get_texture_id(&self, ort:FaceOrientation)->u32{
-        self.texture_ids[ort as usize]
+    pub fn is_transparent(&self)->bool{
+        self.transparent
     }
-    pub fn name(&self)->&'static str{
-        self.name
+    pub fn is_solid(&self)->bool{
+        self.solid
+    }
+    pub fn light_emission(&self)->u8{
+        self.light_emission
+    }
+    pub fn light_filter(&self)->u8{
+        self.light_filter
+    }
+    pub fn hardness(&self)->f32{
+        self.hardness
+    }
+    pub fn blast_resistance(&self)->f32{
+        self.blast_resistance
+    }
+    pub fn has_gravity(&self)->bool{
+        self.gravity
+    }
+    pub fn is_cutout(&self)->bool{
+        self.cutout
+    }
+    pub fn orientation(&self)->Orientation{
+        self.orientation
     }
 }
 pub const AIR:Block = Block::new(0);
@@ -38,44 +82,49 @@ pub const BRICK:Block = Block::new(12);
 pub const TNT:Block = Block::new(13);
 pub const COBBLESTONE:Block = Block::new(14);
 pub const BEDROCK:Block = Block::new(15);
-pub const GRAVEL:Block = Block::new(14);
-
-pub const BLOCKS:[BlockProp;34] = [
-    BlockProp::regular("air", /*Some dummy value*/256),
-    BlockProp::regular("glass", 28),
-    BlockProp::regular("ice", 55),
-    BlockProp::regular("spawner", 53),
-    BlockProp::regular("water", 31),
-    BlockProp::top_sides_bottom("leaves", 51,52, 51),
-    // blocks above are transparent. Blocks below are not
-    BlockProp::regular("stone", 1),
-    BlockProp::regular("dirt", 2),
-    BlockProp::top_sides_bottom("grass", 0, 3,2),
-    BlockProp::regular("plank", 4),
-    BlockProp::top_sides_bottom_front("crafting", 59, 62,4, 63),
-    BlockProp::top_sides_bottom("slab", 6,5,6),
-    BlockProp::regular("brick", 7),
-    BlockProp::top_sides_bottom("tnt", 9,8,10),
-    BlockProp::regular("cobblestone", 11),
-    BlockProp::regular("bedrock", 12),
-    BlockProp::regular("sand", 13),
-    BlockProp::regular("gravel", 14),
-    BlockProp::top_sides_bottom("wood", 16,15,16),
-    BlockProp::regular("iron", 17),
-    BlockProp::regular("gold", 18),
-    BlockProp::regular("diamond", 19),
-    BlockProp::regular("emerald", 20),
-    BlockProp::regular("gold ore", 21),
-    BlockProp::regular("iron ore", 22),
-    BlockProp::regular("coal ore", 23),
-    BlockProp::regular("bookshelf", 24),
-    BlockProp::regular("moss stone", 25),
-    BlockProp::regular("obsidian", 26),
-    BlockProp::regular("sponge", 27),
-    BlockProp::regular("diamond ore", 29),
-    BlockProp::regular("redstone ore", 30),
-    BlockProp::regular("lava", 36),
-    BlockProp::regular("snow", 54),
+pub const SAND:Block = Block::new(16);
+pub const GRAVEL:Block = Block::new(17);
+pub const WOOD:Block = Block::new(18);
+pub const GOLD_ORE:Block = Block::new(23);
+pub const IRON_ORE:Block = Block::new(24);
+pub const COAL_ORE:Block = Block::new(25);
+pub const OBSIDIAN:Block = Block::new(28);
+pub const DIAMOND_ORE:Block = Block::new(30);
+pub const REDSTONE_ORE:Block = Block::new(31);
+pub const LAVA:Block = Block::new(32);
+pub const SAPLING:Block = Block::new(34);
 
-
-];
\ No newline at end of file
+/**Blocks that the code refers to directly. Their ids in assets/blocks.txt must match the constants above*/
+pub const BUILTIN:[(Block,&str);27] = [
+    (AIR, "air"),
+    (GLASS, "glass"),
+    (ICE, "ice"),
+    (SPAWNER, "spawner"),
+    (WATER, "water"),
+    (LEAVES, "leaves"),
+    (STONE, "stone"),
+    (DIRT, "dirt"),
+    (GRASS, "grass"),
+    (PLANK, "plank"),
+    (CRAFTING, "crafting"),
+    (SLAB, "slab"),
+    (BRICK, "brick"),
+    (TNT, "tnt"),
+    (COBBLESTONE, "cobblestone"),
+    (BEDROCK, "bedrock"),
+    (SAND, "sand"),
+    (GRAVEL, "gravel"),
+    (WOOD, "wood"),
+    (GOLD_ORE, "gold_ore"),
+    (IRON_ORE, "iron_ore"),
+    (COAL_ORE, "coal_ore"),
+    (OBSIDIAN, "obsidian"),
+    (DIAMOND_ORE, "diamond_ore"),
+    (REDSTONE_ORE, "redstone_ore"),
+    (LAVA, "lava"),
+    (SAPLING, "sapling"),
+];
+/**Also accepts names that use spaces instead of underscores (as in older saves)*/
+pub fn find_by_name(name: &str) -> Option<Block> {
+    registry().find_by_name(&name.replace(' ', "_"))
+}
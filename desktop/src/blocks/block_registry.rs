@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use crate::blocks::Block;
+use crate::blocks::block_properties::{BlockProp, BUILTIN};
+use crate::blocks::light::MAX_LIGHT;
+use crate::blocks::block_state::Orientation;
+use crate::resources::{self, Resources};
+
+pub const BLOCKS_FILE: &str = "blocks.txt";
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to load block definitions")]
+    Io(#[cause] resources::Error),
+    #[fail(display = "Block definitions, line {}: {}", line, message)]
+    Parse { line: usize, message: String },
+    #[fail(display = "Block {:?} is defined more than once", name)]
+    DuplicateName { name: String },
+    #[fail(display = "Built-in block {:?} must have id {} but has {:?}", name, id, found)]
+    MismatchedBuiltin { name: String, id: u32, found: Option<u32> },
+    #[fail(display = "Too many blocks ({}), ids must fit in 16 bits", count)]
+    TooManyBlocks { count: usize },
+    #[fail(display = "Block registry is already initialised")]
+    AlreadyInitialised,
+}
+
+impl From<std::io::Error> for Error {
+    fn from(other: std::io::Error) -> Self {
+        Error::Io(resources::Error::from(other))
+    }
+}
+
+/**All kinds of blocks known to the game. Id of a block is its position in the registry*/
+pub struct BlockRegistry {
+    blocks: Vec<BlockProp>,
+    by_name: HashMap<String, Block>,
+}
+
+static REGISTRY: OnceLock<BlockRegistry> = OnceLock::new();
+
+/**Global registry used by all Block methods. Panics if init was not called yet*/
+pub fn registry() -> &'static BlockRegistry {
+    REGISTRY.get().expect("Block registry is not initialised. Call block_registry::init first")
+}
+
+/**Makes the registry globally available. Can only be done once*/
+pub fn init(registry: BlockRegistry) -> Result<(), Error> {
+    REGISTRY.set(registry).map_err(|_| Error::AlreadyInitialised)
+}
+
+/**Loads blocks.txt from resources and initialises the global registry with it*/
+pub fn init_from_res(res: &Resources) -> Result<(), Error> {
+    init(BlockRegistry::from_res(res)?)
+}
+
+impl BlockRegistry {
+    pub fn from_res(res: &Resources) -> Result<Self, Error> {
+        Self::parse(&res.load(BLOCKS_FILE)?)
+    }
+    /**Parses definitions in the format described at the top of assets/blocks.txt
+    and checks that built-in blocks have the ids the code expects*/
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut blocks: Vec<BlockProp> = Vec::new();
+        let mut explicit_filter = false;
+        let mut explicit_resistance = false;
+        for (line_idx, line) in text.lines().enumerate() {
+            let line_no = line_idx + 1;
+            let err = |message: String| Error::Parse { line: line_no, message };
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = match line.find('=') {
+                Some(eq) => (line[..eq].trim(), line[eq + 1..].trim()),
+                None => return Err(err(format!("expected key=value but got {:?}", line))),
+            };
+            if key == "name" {
+                if value.is_empty() || value.contains(char::is_whitespace) {
+                    return Err(err(format!("invalid block name {:?}", value)));
+                }
+                Self::finish(blocks.last_mut(), explicit_filter, explicit_resistance);
+                explicit_filter = false;
+                explicit_resistance = false;
+                blocks.push(BlockProp::new(value.to_string()));
+                continue;
+            }
+            let block = blocks.last_mut().ok_or_else(|| err(format!("{} appears before the first name", key)))?;
+            match key {
+                "textures" => {
+                    let ids = value.split_whitespace()
+                        .map(|id| id.parse::<u32>().map_err(|_| err(format!("invalid texture id {:?}", id))))
+                        .collect::<Result<Vec<u32>, Error>>()?;
+                    // order of FaceOrientation is YPlus, YMinus, XPlus, XMinus, ZPlus, ZMinus
+                    block.texture_ids = match ids.as_slice() {
+                        &[all] => [all; 6],
+                        &[top, side, bottom] => [top, bottom, side, side, side, side],
+                        &[top, side, bottom, front] => [top, bottom, side, side, side, front],
+                        &[yp, ym, xp, xm, zp, zm] => [yp, ym, xp, xm, zp, zm],
+                        _ => return Err(err(format!("expected 1, 3, 4 or 6 texture ids but got {}", ids.len()))),
+                    };
+                }
+                "transparent" => block.transparent = parse_bool(value).ok_or_else(|| err(format!("invalid boolean {:?}", value)))?,
+                "solid" => block.solid = parse_bool(value).ok_or_else(|| err(format!("invalid boolean {:?}", value)))?,
+                "gravity" => block.gravity = parse_bool(value).ok_or_else(|| err(format!("invalid boolean {:?}", value)))?,
+                "cutout" => block.cutout = parse_bool(value).ok_or_else(|| err(format!("invalid boolean {:?}", value)))?,
+                "light" => block.light_emission = parse_light(value).ok_or_else(|| err(format!("invalid light level {:?}", value)))?,
+                "light_filter" => {
+                    block.light_filter = parse_light(value).ok_or_else(|| err(format!("invalid light level {:?}", value)))?;
+                    explicit_filter = true;
+                }
+                "orientation" => block.orientation = match value {
+                    "fixed" => Orientation::Fixed,
+                    "facing" => Orientation::Facing,
+                    "axis" => Orientation::Axis,
+                    "half" => Orientation::Half,
+                    _ => return Err(err(format!("unknown orientation {:?}", value))),
+                },
+                "hardness" => block.hardness = value.parse().map_err(|_| err(format!("invalid hardness {:?}", value)))?,
+                "blast_resistance" => {
+                    block.blast_resistance = value.parse().map_err(|_| err(format!("invalid blast resistance {:?}", value)))?;
+                    explicit_resistance = true;
+                }
+                _ => return Err(err(format!("unknown key {:?}", key))),
+            }
+        }
+        Self::finish(blocks.last_mut(), explicit_filter, explicit_resistance);
+        Self::from_props(blocks)
+    }
+    /**Transparent blocks let light through and blocks resist explosions as much as they resist players, unless told otherwise*/
+    fn finish(block: Option<&mut BlockProp>, explicit_filter: bool, explicit_resistance: bool) {
+        if let Some(block) = block {
+            if block.transparent && !explicit_filter {
+                block.light_filter = 0;
+            }
+            if !explicit_resistance {
+                block.blast_resistance = block.hardness;
+            }
+        }
+    }
+    pub fn from_props(blocks: Vec<BlockProp>) -> Result<Self, Error> {
+        if blocks.len() > u16::MAX as usize + 1 {
+            return Err(Error::TooManyBlocks { count: blocks.len() });
+        }
+        let mut by_name = HashMap::with_capacity(blocks.len());
+        for (idx, block) in blocks.iter().enumerate() {
+            if by_name.insert(block.name.clone(), Block::new(idx as u32)).is_some() {
+                return Err(Error::DuplicateName { name: block.name.clone() });
+            }
+        }
+        for &(block, name) in BUILTIN.iter() {
+            let found = by_name.get(name).map(|b| b.idx());
+            if found != Some(block.idx()) {
+                return Err(Error::MismatchedBuiltin { name: name.to_string(), id: block.idx(), found });
+            }
+        }
+        Ok(Self { blocks, by_name })
+    }
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+    pub fn get(&self, block: Block) -> &BlockProp {
+        &self.blocks[block.idx() as usize]
+    }
+    pub fn find_by_name(&self, name: &str) -> Option<Block> {
+        self.by_name.get(name).cloned()
+    }
+    pub fn iter(&self) -> impl Iterator<Item=(Block, &BlockProp)> {
+        self.blocks.iter().enumerate().map(|(idx, prop)| (Block::new(idx as u32), prop))
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_light(value: &str) -> Option<u8> {
+    value.parse::<u8>().ok().filter(|&l| l <= MAX_LIGHT)
+}
+
+/**Initialises the global registry with assets/blocks.txt, unless some other test already did*/
+#[cfg(test)]
+pub(crate) fn init_for_tests() {
+    REGISTRY.get_or_init(|| BlockRegistry::parse(include_str!("../../assets/blocks.txt")).unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASSETS: &str = include_str!("../../assets/blocks.txt");
+
+    fn parse_error_line(text: &str) -> usize {
+        match BlockRegistry::parse(text) {
+            Err(Error::Parse { line, .. }) => line,
+            Err(e) => panic!("expected a parse error but got {}", e),
+            Ok(_) => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn malformed_lines_are_reported_with_line_number() {
+        assert_eq!(parse_error_line("name=air\n# comment\nsolid"), 3);
+        assert_eq!(parse_error_line("solid=false\nname=air"), 1);
+        assert_eq!(parse_error_line("name=air\nsolid=maybe"), 2);
+        assert_eq!(parse_error_line("name=air\n\nlight=16"), 3);
+        assert_eq!(parse_error_line("name=air\ntextures=1 2"), 2);
+        assert_eq!(parse_error_line("name=air\ncolour=red"), 2);
+        assert_eq!(parse_error_line("name=two words"), 1);
+    }
+
+    #[test]
+    fn duplicate_names_are_rejected() {
+        match BlockRegistry::parse("name=air\nname=stone\nname=air") {
+            Err(Error::DuplicateName { name }) => assert_eq!(name, "air"),
+            _ => panic!("expected a duplicate name error"),
+        }
+    }
+
+    #[test]
+    fn builtin_blocks_must_keep_their_ids() {
+        match BlockRegistry::parse("name=glass\nname=air") {
+            Err(Error::MismatchedBuiltin { name, id, found }) => {
+                assert_eq!(name, "air");
+                assert_eq!(id, 0);
+                assert_eq!(found, Some(1));
+            }
+            _ => panic!("expected a mismatched built-in error"),
+        }
+    }
+
+    #[test]
+    fn defaults_follow_hardness_and_transparency() {
+        let text = format!("{}\nname=soft\nhardness=2.5\ntransparent=true\n\
+                            name=tough\nhardness=0.5\nblast_resistance=10\nlight_filter=3\n", ASSETS);
+        let registry = BlockRegistry::parse(&text).unwrap();
+        let soft = registry.get(registry.find_by_name("soft").unwrap());
+        assert_eq!(soft.blast_resistance, 2.5);
+        assert_eq!(soft.light_filter, 0);
+        let tough = registry.get(registry.find_by_name("tough").unwrap());
+        assert_eq!(tough.hardness, 0.5);
+        assert_eq!(tough.blast_resistance, 10.);
+        assert_eq!(tough.light_filter, 3);
+        assert_eq!(registry.len(), ASSETS.lines().filter(|l| l.starts_with("name=")).count() + 2);
+    }
+}
@@ -0,0 +1,261 @@
+use crate::blocks::face_orientation::FaceOrientation;
+
+/**Sides in the order in which they follow each other when a block is turned by a quarter around the Y axis.
+Blocks that are not rotated have their front facing ZMinus*/
+const HORIZONTAL: [FaceOrientation; 4] = [FaceOrientation::ZMinus, FaceOrientation::XPlus, FaceOrientation::ZPlus, FaceOrientation::XMinus];
+
+const FACING_SHIFT: u16 = 0;
+const FACING_MASK: u16 = 0b11;
+const AXIS_SHIFT: u16 = 2;
+const AXIS_MASK: u16 = 0b11;
+const TOP_HALF_SHIFT: u16 = 4;
+const TOP_HALF_MASK: u16 = 0b1;
+const FLUID_LEVEL_SHIFT: u16 = 5;
+const FLUID_LEVEL_MASK: u16 = 0b1111;
+const GROWTH_SHIFT: u16 = 9;
+const GROWTH_MASK: u16 = 0b111;
+
+/**Which part of the block state decides how a block is oriented when placed and how its textures are laid out*/
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Orientation {
+    /**Textures never change (most blocks)*/
+    Fixed,
+    /**Front of the block (the ZMinus texture) turns towards the player, like a crafting table*/
+    Facing,
+    /**Top and bottom textures lie along one of the axes, like logs*/
+    Axis,
+    /**The block occupies either the lower or the upper half, like slabs*/
+    Half,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Axis {
+    Y = 0,
+    X = 1,
+    Z = 2,
+}
+
+impl Axis {
+    pub fn of(ort: FaceOrientation) -> Self {
+        match ort {
+            FaceOrientation::YPlus | FaceOrientation::YMinus => Axis::Y,
+            FaceOrientation::XPlus | FaceOrientation::XMinus => Axis::X,
+            FaceOrientation::ZPlus | FaceOrientation::ZMinus => Axis::Z,
+        }
+    }
+}
+
+/**Additional data stored alongside the block id. Every field occupies its own bits, so that all of them
+fit into 16 bits: facing (2), axis (2), slab half (1), fluid level (4) and growth stage (3).
+The default state (all zeros) is the state of freshly generated blocks.*/
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct BlockState(u16);
+
+impl BlockState {
+    pub const fn from_bits(bits: u16) -> Self {
+        Self(bits)
+    }
+    pub const fn bits(&self) -> u16 {
+        self.0
+    }
+    fn get(&self, shift: u16, mask: u16) -> u16 {
+        (self.0 >> shift) & mask
+    }
+    fn with(self, shift: u16, mask: u16, value: u16) -> Self {
+        assert!(value <= mask, "State value {} does not fit in mask {:b}", value, mask);
+        Self((self.0 & !(mask << shift)) | (value << shift))
+    }
+    /**Side towards which the front of the block faces*/
+    pub fn facing(&self) -> FaceOrientation {
+        HORIZONTAL[self.get(FACING_SHIFT, FACING_MASK) as usize]
+    }
+    /**Only sides can be faced*/
+    pub fn with_facing(self, facing: FaceOrientation) -> Self {
+        let turns = HORIZONTAL.iter().position(|&o| o == facing).expect("Blocks can only face sides");
+        self.with(FACING_SHIFT, FACING_MASK, turns as u16)
+    }
+    pub fn axis(&self) -> Axis {
+        match self.get(AXIS_SHIFT, AXIS_MASK) {
+            1 => Axis::X,
+            2 => Axis::Z,
+            _ => Axis::Y,
+        }
+    }
+    pub fn with_axis(self, axis: Axis) -> Self {
+        self.with(AXIS_SHIFT, AXIS_MASK, axis as u16)
+    }
+    pub fn is_top_half(&self) -> bool {
+        self.get(TOP_HALF_SHIFT, TOP_HALF_MASK) != 0
+    }
+    pub fn with_top_half(self, top_half: bool) -> Self {
+        self.with(TOP_HALF_SHIFT, TOP_HALF_MASK, top_half as u16)
+    }
+    /**0 stands for a source block, higher levels flow further away from it*/
+    pub fn fluid_level(&self) -> u8 {
+        self.get(FLUID_LEVEL_SHIFT, FLUID_LEVEL_MASK) as u8
+    }
+    pub fn with_fluid_level(self, level: u8) -> Self {
+        self.with(FLUID_LEVEL_SHIFT, FLUID_LEVEL_MASK, level as u16)
+    }
+    pub fn growth(&self) -> u8 {
+        self.get(GROWTH_SHIFT, GROWTH_MASK) as u8
+    }
+    pub fn with_growth(self, growth: u8) -> Self {
+        self.with(GROWTH_SHIFT, GROWTH_MASK, growth as u16)
+    }
+    /**State of the same block turned by the given number of quarter turns around the Y axis,
+    in the same direction in which HORIZONTAL is ordered*/
+    pub fn rotated_y(self, orientation: Orientation, turns: usize) -> Self {
+        match orientation {
+            Orientation::Facing => {
+                let facing = (self.get(FACING_SHIFT, FACING_MASK) as usize + turns) % HORIZONTAL.len();
+                self.with(FACING_SHIFT, FACING_MASK, facing as u16)
+            }
+            Orientation::Axis if turns % 2 == 1 => match self.axis() {
+                Axis::X => self.with_axis(Axis::Z),
+                Axis::Z => self.with_axis(Axis::X),
+                Axis::Y => self,
+            },
+            _ => self,
+        }
+    }
+    /**State of the same block reflected across a plane perpendicular to the given axis*/
+    pub fn mirrored(self, orientation: Orientation, axis: Axis) -> Self {
+        match orientation {
+            Orientation::Facing => {
+                let facing = self.facing();
+                if Axis::of(facing) == axis {
+                    self.with_facing(facing.opposite())
+                } else {
+                    self
+                }
+            }
+            Orientation::Half if axis == Axis::Y => self.with_top_half(!self.is_top_half()),
+            _ => self,
+        }
+    }
+    /**Face of an unrotated block whose texture should be shown on the given face of a block in this state*/
+    pub fn unrotated_face(&self, orientation: Orientation, ort: FaceOrientation) -> FaceOrientation {
+        match orientation {
+            Orientation::Facing => match HORIZONTAL.iter().position(|&o| o == ort) {
+                Some(side) => {
+                    let turns = self.get(FACING_SHIFT, FACING_MASK) as usize;
+                    HORIZONTAL[(side + HORIZONTAL.len() - turns) % HORIZONTAL.len()]
+                }
+                None => ort,
+            },
+            Orientation::Axis => match (self.axis(), ort) {
+                (Axis::X, FaceOrientation::XPlus) | (Axis::Z, FaceOrientation::ZPlus) => FaceOrientation::YPlus,
+                (Axis::X, FaceOrientation::XMinus) | (Axis::Z, FaceOrientation::ZMinus) => FaceOrientation::YMinus,
+                (Axis::X, FaceOrientation::YPlus) => FaceOrientation::XPlus,
+                (Axis::X, FaceOrientation::YMinus) => FaceOrientation::XMinus,
+                (Axis::Z, FaceOrientation::YPlus) => FaceOrientation::ZPlus,
+                (Axis::Z, FaceOrientation::YMinus) => FaceOrientation::ZMinus,
+                _ => ort,
+            },
+            Orientation::Fixed | Orientation::Half => ort,
+        }
+    }
+    /**State of a block placed against the given face of another block by a player looking along (look_x, look_z)*/
+    pub fn placed(orientation: Orientation, against: FaceOrientation, look_x: f32, look_z: f32) -> Self {
+        let state = Self::default();
+        match orientation {
+            Orientation::Fixed => state,
+            Orientation::Facing => {
+                // front turns back towards the player
+                let facing = if look_x.abs() > look_z.abs() {
+                    if look_x > 0. { FaceOrientation::XMinus } else { FaceOrientation::XPlus }
+                } else {
+                    if look_z > 0. { FaceOrientation::ZMinus } else { FaceOrientation::ZPlus }
+                };
+                state.with_facing(facing)
+            }
+            Orientation::Axis => state.with_axis(Axis::of(against)),
+            Orientation::Half => state.with_top_half(against == FaceOrientation::YMinus),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_round_trip_without_touching_each_other() {
+        let state = BlockState::default()
+            .with_facing(FaceOrientation::XMinus)
+            .with_axis(Axis::Z)
+            .with_top_half(true)
+            .with_fluid_level(15)
+            .with_growth(7);
+        assert_eq!(state.facing(), FaceOrientation::XMinus);
+        assert_eq!(state.axis(), Axis::Z);
+        assert!(state.is_top_half());
+        assert_eq!(state.fluid_level(), 15);
+        assert_eq!(state.growth(), 7);
+        assert_eq!(state.bits(), 0b111_1111_1_10_11);
+        assert_eq!(BlockState::from_bits(state.bits()), state);
+        let cleared = state.with_fluid_level(0).with_top_half(false);
+        assert_eq!(cleared.facing(), FaceOrientation::XMinus);
+        assert_eq!(cleared.axis(), Axis::Z);
+        assert_eq!(cleared.growth(), 7);
+        assert_eq!(cleared.fluid_level(), 0);
+        assert!(!cleared.is_top_half());
+    }
+
+    #[test]
+    #[should_panic]
+    fn values_must_fit_in_their_bits() {
+        BlockState::default().with_growth(8);
+    }
+
+    #[test]
+    fn four_quarter_turns_come_back() {
+        let state = BlockState::default().with_facing(FaceOrientation::ZMinus);
+        assert_eq!(state.rotated_y(Orientation::Facing, 1).facing(), FaceOrientation::XPlus);
+        assert_eq!(state.rotated_y(Orientation::Facing, 2).facing(), FaceOrientation::ZPlus);
+        assert_eq!(state.rotated_y(Orientation::Facing, 3).facing(), FaceOrientation::XMinus);
+        assert_eq!(state.rotated_y(Orientation::Facing, 4), state);
+        let log = BlockState::default().with_axis(Axis::X);
+        assert_eq!(log.rotated_y(Orientation::Axis, 1).axis(), Axis::Z);
+        assert_eq!(log.rotated_y(Orientation::Axis, 2).axis(), Axis::X);
+        assert_eq!(BlockState::default().with_axis(Axis::Y).rotated_y(Orientation::Axis, 1).axis(), Axis::Y);
+        // fixed blocks and slabs are not affected by turning
+        assert_eq!(state.rotated_y(Orientation::Fixed, 1), state);
+        let slab = BlockState::default().with_top_half(true);
+        assert_eq!(slab.rotated_y(Orientation::Half, 1), slab);
+    }
+
+    #[test]
+    fn mirroring_flips_only_along_its_axis() {
+        let state = BlockState::default().with_facing(FaceOrientation::XPlus);
+        assert_eq!(state.mirrored(Orientation::Facing, Axis::X).facing(), FaceOrientation::XMinus);
+        assert_eq!(state.mirrored(Orientation::Facing, Axis::Z), state);
+        assert_eq!(state.mirrored(Orientation::Facing, Axis::X).mirrored(Orientation::Facing, Axis::X), state);
+        let slab = BlockState::default();
+        assert!(slab.mirrored(Orientation::Half, Axis::Y).is_top_half());
+        assert_eq!(slab.mirrored(Orientation::Half, Axis::X), slab);
+    }
+
+    #[test]
+    fn turned_front_shows_front_texture() {
+        for &facing in HORIZONTAL.iter() {
+            let state = BlockState::default().with_facing(facing);
+            assert_eq!(state.unrotated_face(Orientation::Facing, facing), FaceOrientation::ZMinus);
+            assert_eq!(state.unrotated_face(Orientation::Facing, FaceOrientation::YPlus), FaceOrientation::YPlus);
+        }
+        let log = BlockState::default().with_axis(Axis::X);
+        assert_eq!(log.unrotated_face(Orientation::Axis, FaceOrientation::XPlus), FaceOrientation::YPlus);
+        assert_eq!(log.unrotated_face(Orientation::Axis, FaceOrientation::YPlus), FaceOrientation::XPlus);
+    }
+
+    #[test]
+    fn placed_state_follows_player_and_clicked_face() {
+        // looking towards +x, the front turns back towards the player
+        assert_eq!(BlockState::placed(Orientation::Facing, FaceOrientation::YPlus, 1., 0.2).facing(), FaceOrientation::XMinus);
+        assert_eq!(BlockState::placed(Orientation::Facing, FaceOrientation::YPlus, 0.1, -1.).facing(), FaceOrientation::ZPlus);
+        assert_eq!(BlockState::placed(Orientation::Axis, FaceOrientation::ZMinus, 0., 1.).axis(), Axis::Z);
+        assert!(BlockState::placed(Orientation::Half, FaceOrientation::YMinus, 0., 1.).is_top_half());
+        assert!(!BlockState::placed(Orientation::Half, FaceOrientation::YPlus, 0., 1.).is_top_half());
+    }
+}
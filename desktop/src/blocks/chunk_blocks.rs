@@ -0,0 +1,413 @@
+use std::ops::{Deref, DerefMut};
+use crate::blocks::block::Block;
+use crate::blocks::chunk_coords::{CHUNK_WIDTH, CHUNK_DEPTH, CHUNK_HEIGHT, SECTION_HEIGHT, SECTIONS};
+use crate::blocks::light::MAX_LIGHT;
+
+const SECTION_VOLUME: usize = CHUNK_WIDTH * SECTION_HEIGHT * CHUNK_DEPTH;
+/**Returned for blocks of sections that are not allocated*/
+static AIR: Block = Block::air();
+
+/**Number of bits needed to index a palette of the given length*/
+fn bits_for(palette_len: usize) -> usize {
+    (usize::BITS - (palette_len.max(1) - 1).leading_zeros()) as usize
+}
+
+/**Blocks of a single section stored as a palette of distinct blocks and indices into it, each using only as many bits
+as the palette needs. Indices never cross the boundary of a word.*/
+#[derive(Clone)]
+struct PalettedSection {
+    palette: Vec<Block>,
+    /**Bits per index. Zero if the palette holds a single block, in which case there are no words at all*/
+    bits: usize,
+    words: Vec<u64>,
+    /**Number of blocks other than air. Sections without any are dropped*/
+    non_air: usize,
+}
+
+impl PalettedSection {
+    fn filled(block: Block) -> Self {
+        Self { palette: vec![block], bits: 0, words: Vec::new(), non_air: if block.is_air() { 0 } else { SECTION_VOLUME } }
+    }
+    fn idx(x: usize, y: usize, z: usize) -> usize {
+        z * CHUNK_WIDTH + x + y * CHUNK_WIDTH * CHUNK_DEPTH
+    }
+    fn index(&self, i: usize) -> usize {
+        if self.bits == 0 {
+            return 0;
+        }
+        let per_word = 64 / self.bits;
+        let word = self.words[i / per_word];
+        ((word >> ((i % per_word) * self.bits)) & ((1 << self.bits) - 1)) as usize
+    }
+    fn set_index(&mut self, i: usize, value: usize) {
+        let per_word = 64 / self.bits;
+        let shift = (i % per_word) * self.bits;
+        let mask = ((1u64 << self.bits) - 1) << shift;
+        let word = &mut self.words[i / per_word];
+        *word = (*word & !mask) | ((value as u64) << shift);
+    }
+    fn get(&self, i: usize) -> &Block {
+        &self.palette[self.index(i)]
+    }
+    /**Returns false if the block was already there*/
+    fn set(&mut self, i: usize, block: Block) -> bool {
+        let old = self.palette[self.index(i)];
+        if old == block {
+            return false;
+        }
+        if old.is_air() {
+            self.non_air += 1;
+        }
+        if block.is_air() {
+            self.non_air -= 1;
+        }
+        let value = match self.palette.iter().position(|&b| b == block) {
+            Some(value) => value,
+            None => {
+                if self.palette.len() == 1 << self.bits {
+                    self.make_room();
+                }
+                self.palette.push(block);
+                self.palette.len() - 1
+            }
+        };
+        self.set_index(i, value);
+        true
+    }
+    /**Drops blocks of the palette that are no longer used and then widens indices so that one more block fits in*/
+    fn make_room(&mut self) {
+        let indices: Vec<usize> = (0..SECTION_VOLUME).map(|i| self.index(i)).collect();
+        let mut used = vec![false; self.palette.len()];
+        for &index in indices.iter() {
+            used[index] = true;
+        }
+        let mut remap = vec![0; self.palette.len()];
+        let mut palette = Vec::with_capacity(self.palette.len() + 1);
+        for (index, &block) in self.palette.iter().enumerate() {
+            if used[index] {
+                remap[index] = palette.len();
+                palette.push(block);
+            }
+        }
+        self.bits = bits_for(palette.len() + 1);
+        self.palette = palette;
+        let per_word = 64 / self.bits;
+        self.words = vec![0; (SECTION_VOLUME + per_word - 1) / per_word];
+        for (i, &index) in indices.iter().enumerate() {
+            self.set_index(i, remap[index]);
+        }
+    }
+}
+
+/**Light of a single section, packed like in ChunkBlocks. Sections lit the same way throughout
+(open sky above the terrain, darkness deep below it) store just one value*/
+#[derive(Clone)]
+enum SectionLight {
+    Uniform(u8),
+    Varying(Vec<u8>),
+}
+
+impl SectionLight {
+    /**Full sky light and no block light, as in the air above the terrain*/
+    const OPEN_SKY: Self = SectionLight::Uniform(MAX_LIGHT << 4);
+
+    fn get(&self, i: usize) -> u8 {
+        match self {
+            SectionLight::Uniform(light) => *light,
+            SectionLight::Varying(light) => light[i],
+        }
+    }
+    /**Returns false if the light was already there*/
+    fn set(&mut self, i: usize, light: u8) -> bool {
+        match self {
+            SectionLight::Uniform(uniform) if *uniform == light => false,
+            SectionLight::Uniform(uniform) => {
+                let mut varying = vec![*uniform; SECTION_VOLUME];
+                varying[i] = light;
+                *self = SectionLight::Varying(varying);
+                true
+            }
+            SectionLight::Varying(varying) => {
+                let changed = varying[i] != light;
+                varying[i] = light;
+                changed
+            }
+        }
+    }
+    fn compact(&mut self) {
+        if let SectionLight::Varying(varying) = self {
+            let first = varying[0];
+            if varying.iter().all(|&light| light == first) {
+                *self = SectionLight::Uniform(first);
+            }
+        }
+    }
+}
+
+/**Blocks of a single chunk. All coordinates are relative to the chunk origin.
+Blocks are kept in sections of SECTION_HEIGHT layers, which are not allocated at all while they only contain air.*/
+#[derive(Clone)]
+pub struct ChunkBlocks {
+    sections: Vec<Option<PalettedSection>>,
+    /**Sky light in the upper 4 bits and block light in the lower 4 bits, one entry per section. It's never saved,
+    but recomputed after loading. Until then the whole chunk is lit as open sky*/
+    light: Vec<SectionLight>,
+    /**Incremented whenever blocks or light change. Copies made for background work use it to tell if they are out of date*/
+    revision: u64,
+}
+
+impl ChunkBlocks {
+    pub fn new() -> Self {
+        Self { sections: vec![None; SECTIONS], light: vec![SectionLight::OPEN_SKY; SECTIONS], revision: 0 }
+    }
+    fn assert_in_bounds(x: usize, y: usize, z: usize) {
+        assert!(x < CHUNK_WIDTH);
+        assert!(y < CHUNK_HEIGHT);
+        assert!(z < CHUNK_DEPTH);
+    }
+    fn light_at(&self, x: usize, y: usize, z: usize) -> u8 {
+        Self::assert_in_bounds(x, y, z);
+        self.light[y / SECTION_HEIGHT].get(PalettedSection::idx(x, y % SECTION_HEIGHT, z))
+    }
+    fn set_light_at(&mut self, x: usize, y: usize, z: usize, light: u8) {
+        Self::assert_in_bounds(x, y, z);
+        if self.light[y / SECTION_HEIGHT].set(PalettedSection::idx(x, y % SECTION_HEIGHT, z), light) {
+            self.revision += 1;
+        }
+    }
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+    /**True if the section contains nothing but air*/
+    pub fn is_section_empty(&self, section: usize) -> bool {
+        self.sections[section].is_none()
+    }
+    /**Number of distinct blocks in the section (at least one, even when it is empty)*/
+    pub fn section_palette_len(&self, section: usize) -> usize {
+        self.sections[section].as_ref().map(|s| s.palette.len()).unwrap_or(1)
+    }
+    pub fn get_block(&self, x: usize, y: usize, z: usize) -> &Block {
+        Self::assert_in_bounds(x, y, z);
+        match &self.sections[y / SECTION_HEIGHT] {
+            Some(section) => section.get(PalettedSection::idx(x, y % SECTION_HEIGHT, z)),
+            None => &AIR,
+        }
+    }
+    /**The block is written back once the returned guard is dropped*/
+    pub fn get_block_mut(&mut self, x: usize, y: usize, z: usize) -> BlockMut<'_> {
+        let block = *self.get_block(x, y, z);
+        BlockMut { chunk: self, x, y, z, block, original: block }
+    }
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, block: Block) {
+        Self::assert_in_bounds(x, y, z);
+        let section = &mut self.sections[y / SECTION_HEIGHT];
+        if section.is_none() {
+            if block.is_air() {
+                return;
+            }
+            *section = Some(PalettedSection::filled(Block::air()));
+        }
+        let paletted = section.as_mut().unwrap();
+        if paletted.set(PalettedSection::idx(x, y % SECTION_HEIGHT, z), block) {
+            self.revision += 1;
+        }
+        if paletted.non_air == 0 {
+            *section = None;
+        }
+    }
+    pub fn sky_light(&self, x: usize, y: usize, z: usize) -> u8 {
+        self.light_at(x, y, z) >> 4
+    }
+    pub fn block_light(&self, x: usize, y: usize, z: usize) -> u8 {
+        self.light_at(x, y, z) & 0xF
+    }
+    pub fn set_sky_light(&mut self, x: usize, y: usize, z: usize, level: u8) {
+        let l = self.light_at(x, y, z);
+        self.set_light_at(x, y, z, (l & 0xF) | (level << 4));
+    }
+    pub fn set_block_light(&mut self, x: usize, y: usize, z: usize, level: u8) {
+        let l = self.light_at(x, y, z);
+        self.set_light_at(x, y, z, (l & 0xF0) | (level & 0xF));
+    }
+    /**Drops per-block light of sections that ended up lit the same way throughout*/
+    pub fn compact_light(&mut self) {
+        for light in self.light.iter_mut() {
+            light.compact();
+        }
+    }
+    pub fn fill_level(&mut self, from_y: usize, height: usize, block: Block) {
+        let to_y = (from_y + height).min(CHUNK_HEIGHT);
+        let mut y = from_y;
+        while y < to_y {
+            let section_end = (y / SECTION_HEIGHT + 1) * SECTION_HEIGHT;
+            if y % SECTION_HEIGHT == 0 && section_end <= to_y {
+                // whole sections are replaced without going through the palette block by block
+                self.sections[y / SECTION_HEIGHT] = if block.is_air() { None } else { Some(PalettedSection::filled(block)) };
+                self.revision += 1;
+                y = section_end;
+                continue;
+            }
+            for z in 0..CHUNK_DEPTH {
+                for x in 0..CHUNK_WIDTH {
+                    self.set_block(x, y, z, block)
+                }
+            }
+            y += 1;
+        }
+    }
+    /**Fills every column from the bottom of the chunk up to (but excluding) height_at(x,z)*/
+    pub fn fill_heightmap(&mut self, filler_block: Block, height_at: impl Fn(usize, usize) -> usize) {
+        for x in 0..CHUNK_WIDTH {
+            for z in 0..CHUNK_DEPTH {
+                for y in 0..height_at(x, z).min(CHUNK_HEIGHT) {
+                    self.set_block(x, y, z, filler_block)
+                }
+            }
+        }
+    }
+}
+
+/**Mutable access to a single block of a chunk. Blocks are not stored one by one, so the block is only
+written back into the chunk when the guard is dropped (or committed), and only if it changed*/
+pub struct BlockMut<'a> {
+    chunk: &'a mut ChunkBlocks,
+    x: usize,
+    y: usize,
+    z: usize,
+    block: Block,
+    original: Block,
+}
+
+impl<'a> BlockMut<'a> {
+    /**Writes the block back right away and returns it, releasing the borrow of the chunk*/
+    pub fn commit(self) -> Block {
+        self.block
+    }
+}
+
+impl<'a> Deref for BlockMut<'a> {
+    type Target = Block;
+
+    fn deref(&self) -> &Self::Target {
+        &self.block
+    }
+}
+
+impl<'a> DerefMut for BlockMut<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.block
+    }
+}
+
+impl<'a> Drop for BlockMut<'a> {
+    fn drop(&mut self) {
+        if self.block != self.original {
+            self.chunk.set_block(self.x, self.y, self.z, self.block)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bits_for_palette_lengths() {
+        assert_eq!(bits_for(1), 0);
+        assert_eq!(bits_for(2), 1);
+        assert_eq!(bits_for(3), 2);
+        assert_eq!(bits_for(4), 2);
+        assert_eq!(bits_for(5), 3);
+        assert_eq!(bits_for(16), 4);
+        assert_eq!(bits_for(17), 5);
+    }
+
+    #[test]
+    fn section_grows_palette_and_keeps_blocks() {
+        let mut section = PalettedSection::filled(Block::air());
+        let block_at = |i: usize| Block::new((i % 40) as u32 + 1);
+        for i in 0..SECTION_VOLUME {
+            assert!(section.set(i, block_at(i)));
+        }
+        assert_eq!(section.palette.len(), 41);
+        assert_eq!(section.bits, 6);
+        assert_eq!(section.non_air, SECTION_VOLUME);
+        for i in 0..SECTION_VOLUME {
+            assert_eq!(*section.get(i), block_at(i));
+        }
+        assert!(!section.set(0, block_at(0)));
+    }
+
+    #[test]
+    fn make_room_drops_unused_palette_entries() {
+        let mut section = PalettedSection::filled(Block::new(1));
+        section.set(0, Block::new(2));
+        section.set(1, Block::new(3));
+        // palette is full with 4 entries of 2 bits, block 1 and 2 are no longer used after this
+        section.set(2, Block::new(4));
+        for i in 0..SECTION_VOLUME {
+            section.set(i, if i == 1 { Block::new(3) } else { Block::new(4) });
+        }
+        section.set(5, Block::new(5));
+        assert_eq!(section.palette, vec![Block::new(3), Block::new(4), Block::new(5)]);
+        assert_eq!(section.bits, 2);
+        assert_eq!(*section.get(1), Block::new(3));
+        assert_eq!(*section.get(5), Block::new(5));
+        assert_eq!(*section.get(6), Block::new(4));
+    }
+
+    #[test]
+    fn sections_are_dropped_when_emptied() {
+        let mut chunk = ChunkBlocks::new();
+        let y = SECTION_HEIGHT + 3;
+        assert!(chunk.is_section_empty(1));
+        chunk.set_block(2, y, 7, Block::new(6));
+        chunk.set_block(3, y, 7, Block::new(9));
+        assert!(!chunk.is_section_empty(1));
+        assert_eq!(chunk.section_palette_len(1), 3);
+        assert_eq!(*chunk.get_block(3, y, 7), Block::new(9));
+        chunk.set_block(2, y, 7, Block::air());
+        assert!(!chunk.is_section_empty(1));
+        chunk.set_block(3, y, 7, Block::air());
+        assert!(chunk.is_section_empty(1));
+        assert!(chunk.get_block(3, y, 7).is_air());
+    }
+
+    #[test]
+    fn block_mut_writes_back_on_drop() {
+        let mut chunk = ChunkBlocks::new();
+        let revision = chunk.revision();
+        *chunk.get_block_mut(1, 1, 1) = Block::new(7);
+        assert_eq!(*chunk.get_block(1, 1, 1), Block::new(7));
+        assert!(chunk.revision() > revision);
+        let revision = chunk.revision();
+        let _ = *chunk.get_block_mut(1, 1, 1);
+        assert_eq!(chunk.revision(), revision);
+    }
+
+    #[test]
+    fn fill_level_replaces_whole_sections() {
+        let mut chunk = ChunkBlocks::new();
+        chunk.fill_level(0, SECTION_HEIGHT + 1, Block::new(6));
+        assert_eq!(chunk.section_palette_len(0), 1);
+        assert_eq!(*chunk.get_block(0, SECTION_HEIGHT, 0), Block::new(6));
+        assert!(chunk.get_block(0, SECTION_HEIGHT + 1, 0).is_air());
+        chunk.fill_level(0, SECTION_HEIGHT, Block::air());
+        assert!(chunk.is_section_empty(0));
+    }
+
+    #[test]
+    fn light_is_stored_per_block_only_where_it_varies() {
+        let mut chunk = ChunkBlocks::new();
+        assert_eq!(chunk.sky_light(0, 0, 0), MAX_LIGHT);
+        chunk.set_block_light(4, 2, 4, 9);
+        assert_eq!(chunk.block_light(4, 2, 4), 9);
+        assert_eq!(chunk.sky_light(4, 2, 4), MAX_LIGHT);
+        assert_eq!(chunk.block_light(4, 3, 4), 0);
+        assert!(matches!(chunk.light[0], SectionLight::Varying(_)));
+        chunk.set_block_light(4, 2, 4, 0);
+        chunk.compact_light();
+        assert!(matches!(chunk.light[0], SectionLight::Uniform(_)));
+        assert_eq!(chunk.block_light(4, 2, 4), 0);
+    }
+}
@@ -0,0 +1,56 @@
+use crate::blocks::face_orientation::FaceOrientation;
+
+pub const CHUNK_WIDTH: usize = 16;
+pub const CHUNK_DEPTH: usize = 16;
+pub const CHUNK_HEIGHT: usize = 256;
+pub const CHUNK_VOLUME: usize = CHUNK_WIDTH * CHUNK_DEPTH * CHUNK_HEIGHT;
+/**Chunks are split vertically into cubic sections. Sections that hold only air take no memory
+and they are also the units of cave culling*/
+pub const SECTION_HEIGHT: usize = 16;
+pub const SECTIONS: usize = CHUNK_HEIGHT / SECTION_HEIGHT;
+
+/**The world is unbounded along x and z axes. Chunks are identified by signed (chunk_x, chunk_z) coordinates,
+ where chunk (0,0) spans blocks from (0,*,0) to (CHUNK_WIDTH-1,*,CHUNK_DEPTH-1) and chunk (-1,-1) spans blocks from
+ (-CHUNK_WIDTH,*,-CHUNK_DEPTH) to (-1,*,-1).*/
+pub type ChunkPos = (i32, i32);
+
+pub fn block_pos_into_chunk_pos(x: i32, z: i32) -> ChunkPos {
+    (x.div_euclid(CHUNK_WIDTH as i32), z.div_euclid(CHUNK_DEPTH as i32))
+}
+pub fn chunk_pos_into_block_pos(chunk_x: i32, chunk_z: i32) -> (i32, i32) {
+    (chunk_x * CHUNK_WIDTH as i32, chunk_z * CHUNK_DEPTH as i32)
+}
+pub fn absolute_block_to_chunk_block_position(x: i32, y: i32, z: i32) -> (u8, u8, u8) {
+    assert!(is_height_in_bounds(y));
+    (x.rem_euclid(CHUNK_WIDTH as i32) as u8, y as u8, z.rem_euclid(CHUNK_DEPTH as i32) as u8)
+}
+pub fn is_height_in_bounds(y: i32) -> bool {
+    0 <= y && y < CHUNK_HEIGHT as i32
+}
+/**Chebyshev distance between two chunks*/
+pub fn chunk_distance(a: ChunkPos, b: ChunkPos) -> i32 {
+    (a.0 - b.0).abs().max((a.1 - b.1).abs())
+}
+pub fn for_each_neighbour<F: FnMut(i32, i32, i32, FaceOrientation)>(
+    x: i32,
+    y: i32,
+    z: i32,
+    mut f: F,
+) {
+    if y + 1 < CHUNK_HEIGHT as i32 {
+        f(x, y + 1, z, FaceOrientation::YPlus)
+    }
+    if y >= 1 {
+        f(x, y - 1, z, FaceOrientation::YMinus)
+    }
+    f(x + 1, y, z, FaceOrientation::XPlus);
+    f(x - 1, y, z, FaceOrientation::XMinus);
+    f(x, y, z + 1, FaceOrientation::ZPlus);
+    f(x, y, z - 1, FaceOrientation::ZMinus);
+}
+pub fn for_each_neighbouring_chunk<F: FnMut(ChunkPos)>(chunk_x: i32, chunk_z: i32, mut f: F) {
+    f((chunk_x + 1, chunk_z));
+    f((chunk_x - 1, chunk_z));
+    f((chunk_x, chunk_z + 1));
+    f((chunk_x, chunk_z - 1));
+}
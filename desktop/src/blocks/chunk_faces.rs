@@ -3,15 +3,26 @@ use crate::render_gl::instanced_logical_model::InstancedLogicalModel;
 use crate::render_gl::buffer::{BufferDynamicDraw, DynamicBuffer};
 use crate::blocks::block::Block;
 use crate::blocks::face_orientation::FaceOrientation;
-use crate::blocks::world_size::{WorldSize};
+use crate::blocks::chunk_coords::absolute_block_to_chunk_block_position;
+use crate::blocks::greedy_mesher::{vertical_extent, face_centre};
+use crate::blocks::chunk_mesh::ChunkMesh;
+use crate::blocks::face_list::FaceList;
+use crate::blocks::section_visibility::SectionVisibility;
+use crate::blocks::chunk_coords::{SECTIONS, SECTION_HEIGHT};
 
 pub struct ChunkFaces {
-    opaque_faces: Vec<Face>,
-    transparent_faces: Vec<Face>,
-    has_opaque_faces_to_update: bool,
-    has_transparent_faces_to_update: bool,
+    opaque_faces: FaceList,
+    transparent_faces: FaceList,
     opaque_faces_model: InstancedLogicalModel<Face, BufferDynamicDraw>,
     transparent_faces_model: InstancedLogicalModel<Face, BufferDynamicDraw>,
+    /**Lowest and one past the highest layer covered by faces uploaded to the GPU. None if there are no faces*/
+    y_range: Option<(usize, usize)>,
+    /**First instance and number of instances of every section in the uploaded buffers. Faces are uploaded ordered by section*/
+    opaque_ranges: [(usize, usize); SECTIONS],
+    transparent_ranges: [(usize, usize); SECTIONS],
+    visibility: [SectionVisibility; SECTIONS],
+    /**Camera location (relative to the chunk) for which transparent faces were last sorted. None if they need sorting*/
+    sorted_for: Option<glm::Vec3>,
 }
 
 impl ChunkFaces {
@@ -21,33 +32,106 @@ impl ChunkFaces {
     pub fn gl_draw_transparent(&self){
         self.transparent_faces_model.draw_instanced_triangles(0, /*one quad=2 triangles=6 vertices*/6, self.transparent_faces_model.ibo().len());
     }
-    pub fn gl_update_opaque(&mut self) -> bool {
-        let Self {
-            opaque_faces,
-            has_opaque_faces_to_update,
-            opaque_faces_model, ..
-        } = self;
-        if *has_opaque_faces_to_update {
-            *has_opaque_faces_to_update = false;
-            opaque_faces_model.ibo_mut().update(opaque_faces.as_slice());
-            assert_eq!(opaque_faces_model.ibo().len(), opaque_faces.len());
+    pub fn gl_draw_opaque_section(&self, section: usize) {
+        let (first, count) = self.opaque_ranges[section];
+        self.opaque_faces_model.draw_instanced_triangles_range(0, /*one quad=2 triangles=6 vertices*/6, first, count);
+    }
+    pub fn gl_draw_transparent_section(&self, section: usize) {
+        let (first, count) = self.transparent_ranges[section];
+        self.transparent_faces_model.draw_instanced_triangles_range(0, /*one quad=2 triangles=6 vertices*/6, first, count);
+    }
+    /**Faces are uploaded grouped by section, so that each section can be drawn on its own. The sort is stable,
+    hence transparent faces of a section stay sorted back to front*/
+    fn upload(faces: &FaceList, model: &mut InstancedLogicalModel<Face, BufferDynamicDraw>, ranges: &mut [(usize, usize); SECTIONS]) {
+        let mut by_section = faces.as_slice().to_vec();
+        by_section.sort_by_key(|face| face.block_y() / SECTION_HEIGHT);
+        *ranges = [(0, 0); SECTIONS];
+        for (slot, face) in by_section.iter().enumerate() {
+            let range = &mut ranges[face.block_y() / SECTION_HEIGHT];
+            if range.1 == 0 {
+                range.0 = slot;
+            }
+            range.1 += 1;
+        }
+        model.ibo_mut().update(&by_section);
+        assert_eq!(model.ibo().len(), faces.len());
+    }
+    fn gl_update(faces: &mut FaceList, model: &mut InstancedLogicalModel<Face, BufferDynamicDraw>, ranges: &mut [(usize, usize); SECTIONS]) -> bool {
+        if faces.take_faces_to_update() {
+            Self::upload(faces, model, ranges);
             true
         } else { false }
     }
+    pub fn gl_update_opaque(&mut self) -> bool {
+        let Self { opaque_faces, opaque_faces_model, opaque_ranges, .. } = self;
+        let updated = Self::gl_update(opaque_faces, opaque_faces_model, opaque_ranges);
+        if updated {
+            self.update_y_range();
+        }
+        updated
+    }
     pub fn gl_update_transparent(&mut self) -> bool {
-        let Self {
-            transparent_faces,
-            has_transparent_faces_to_update,
-            transparent_faces_model, ..
-        } = self;
-        if *has_transparent_faces_to_update {
-            *has_transparent_faces_to_update = false;
-            transparent_faces_model.ibo_mut().update(transparent_faces.as_slice());
-            assert_eq!(transparent_faces_model.ibo().len(), transparent_faces.len());
+        let Self { transparent_faces, transparent_faces_model, transparent_ranges, .. } = self;
+        let updated = Self::gl_update(transparent_faces, transparent_faces_model, transparent_ranges);
+        if updated {
+            self.update_y_range();
+        }
+        updated
+    }
+    /**Uploads transparent faces that were only sorted since the last upload. Faces stay the same,
+    so neither the sections they belong to nor the bounding box of the chunk change*/
+    pub fn gl_update_transparent_order(&mut self) -> bool {
+        let Self { transparent_faces, transparent_faces_model, transparent_ranges, .. } = self;
+        if transparent_faces.take_reordered() {
+            Self::upload(transparent_faces, transparent_faces_model, transparent_ranges);
             true
         } else { false }
     }
+    fn update_y_range(&mut self) {
+        self.y_range = self.opaque_faces.as_slice().iter().chain(self.transparent_faces.as_slice().iter())
+            .map(|face| (face.block_y(), face.block_y() + vertical_extent(face)))
+            .fold(None, |range, (from, to)| match range {
+                Some((min, max)) => Some((min.min(from), max.max(to))),
+                None => Some((from, to)),
+            });
+    }
+    /**Sorts transparent faces back to front as seen from the camera (given relative to the chunk). Faces are only
+    sorted again if they changed or the camera moved further than threshold from where they were last sorted.
+    Returns true if faces were sorted and need to be uploaded (see gl_update_transparent_order)*/
+    pub(crate) fn sort_transparent(&mut self, camera: &glm::Vec3, threshold: f32) -> bool {
+        let up_to_date = !self.transparent_faces.has_faces_to_update() &&
+            self.sorted_for.map(|sorted_for| glm::distance(&sorted_for, camera) <= threshold).unwrap_or(false);
+        if up_to_date || self.transparent_faces.len() < 2 {
+            return false;
+        }
+        self.transparent_faces.sort_back_to_front(|face| glm::distance2(&face_centre(face), camera));
+        self.sorted_for = Some(camera.clone());
+        true
+    }
+    /**Number of uploaded faces (opaque and transparent) of the section*/
+    pub fn section_len(&self, section: usize) -> usize {
+        self.opaque_ranges[section].1 + self.transparent_ranges[section].1
+    }
+    pub fn section_len_transparent(&self, section: usize) -> usize {
+        self.transparent_ranges[section].1
+    }
+    pub fn visibility(&self, section: usize) -> SectionVisibility {
+        self.visibility[section]
+    }
+    pub(crate) fn set_visibility(&mut self, section: usize, visibility: SectionVisibility) {
+        self.visibility[section] = visibility
+    }
+    /**Used to tighten the bounding box of the chunk for frustum culling*/
+    pub fn y_range(&self) -> Option<(usize, usize)> {
+        self.y_range
+    }
 
+    pub fn opaque(&self) -> &FaceList {
+        &self.opaque_faces
+    }
+    pub fn transparent(&self) -> &FaceList {
+        &self.transparent_faces
+    }
     pub fn opaque_as_slice(&self) -> &[Face] {
         self.opaque_faces.as_slice()
     }
@@ -62,173 +146,127 @@ impl ChunkFaces {
     }
     pub fn new(gl: &gl::Gl) -> Self {
         Self {
-            opaque_faces: Vec::new(),
-            transparent_faces: Vec::new(),
-            has_opaque_faces_to_update: false,
-            has_transparent_faces_to_update: false,
+            opaque_faces: FaceList::new(),
+            transparent_faces: FaceList::new(),
             opaque_faces_model: InstancedLogicalModel::new(DynamicBuffer::with_capacity(16, &gl), &gl),
             transparent_faces_model: InstancedLogicalModel::new(DynamicBuffer::with_capacity(16, &gl), &gl),
+            y_range: None,
+            opaque_ranges: [(0, 0); SECTIONS],
+            transparent_ranges: [(0, 0); SECTIONS],
+            visibility: [SectionVisibility::ALL; SECTIONS],
+            sorted_for: None,
         }
     }
-    pub(crate) fn push_block(&mut self, x: usize, y: usize, z: usize, ort: FaceOrientation, block: Block) {
-        let (x, y, z) = WorldSize::absolute_block_to_chunk_block_position(x,y,z);
-        self.push(x, y, z, ort, block)
-    }
-    fn push(&mut self, x: u8, y: u8, z: u8, ort: FaceOrientation, block: Block) {
-        let face = Face::from_coords_and_ort(x, y, z, ort, block.texture_id(ort));
-        assert!(self.find_opaque_by_coords_and_ort(face.coords_and_ort()).is_none());
-        assert!(self.find_transparent_by_coords_and_ort(face.coords_and_ort()).is_none());
-        if block.is_transparent() {
-            self.transparent_faces.push(face);
-            self.has_transparent_faces_to_update = true;
-        } else {
-            self.opaque_faces.push(face);
-            self.has_opaque_faces_to_update = true;
+    /**Faces of blended blocks are kept separately from those of opaque (and cutout) blocks, because they are drawn after them*/
+    fn list(&self, transparent: bool) -> &FaceList {
+        if transparent { &self.transparent_faces } else { &self.opaque_faces }
+    }
+    fn list_mut(&mut self, transparent: bool) -> &mut FaceList {
+        if transparent { &mut self.transparent_faces } else { &mut self.opaque_faces }
+    }
+    /**Replaces all faces (and visibility of sections) with those of a mesh built from scratch*/
+    pub(crate) fn set_mesh(&mut self, mesh: ChunkMesh) {
+        let ChunkMesh { opaque, transparent, visibility } = mesh;
+        self.opaque_faces.replace_all(opaque);
+        self.transparent_faces.replace_all(transparent);
+        self.visibility = visibility;
+    }
+    /**True if faces changed since they were last uploaded to GPU*/
+    pub fn has_faces_to_update(&self) -> bool {
+        self.opaque_faces.has_faces_to_update() || self.transparent_faces.has_faces_to_update()
+    }
+    /**Shading consists of sky light, block light and ambient occlusion of the face*/
+    pub(crate) fn push_block(&mut self, x: i32, y: i32, z: i32, ort: FaceOrientation, block: Block, shading: (u8, u8, u8)) {
+        let (x, y, z) = absolute_block_to_chunk_block_position(x,y,z);
+        self.push(x, y, z, ort, block, shading)
+    }
+    fn push(&mut self, x: u8, y: u8, z: u8, ort: FaceOrientation, block: Block, shading: (u8, u8, u8)) {
+        let face = Face::of_block(x, y, z, ort, block, shading);
+        assert!(self.list(!block.is_blended()).get(face.coords_and_ort()).is_none());
+        self.list_mut(block.is_blended()).push(face);
+    }
+    /**Sets shading of faces to the value returned by shading_of. Faces for which it returns None are left untouched*/
+    pub(crate) fn refresh_shading(&mut self, shading_of: impl Fn(&Face) -> Option<(u8, u8, u8)>) {
+        let mut refresh = |face: &mut Face| shading_of(face).map(|shading| face.set_shading(shading)).unwrap_or(false);
+        self.opaque_faces.update_all(&mut refresh);
+        self.transparent_faces.update_all(&mut refresh);
+    }
+    /**Sets shading of the face if it exists (in either list)*/
+    pub(crate) fn refresh_face_shading(&mut self, x: i32, y: i32, z: i32, ort: FaceOrientation, shading: (u8, u8, u8)) {
+        let (x, y, z) = absolute_block_to_chunk_block_position(x,y,z);
+        let coords = Face::encode_coords_and_ort(x, y, z, ort);
+        for &transparent in &[false, true] {
+            if let Some(face) = self.list_mut(transparent).get_mut(coords) {
+                face.set_shading(shading);
+            }
         }
     }
     pub fn find_transparent_by_coords_and_ort(&self, coords: u32) -> Option<&Face> {
-        self.transparent_faces.iter().find(|f| f.coords_and_ort() == coords)
+        self.transparent_faces.get(coords)
     }
     pub fn find_opaque_by_coords_and_ort(&self, coords: u32) -> Option<&Face> {
-        self.opaque_faces.iter().find(|f| f.coords_and_ort() == coords)
+        self.opaque_faces.get(coords)
     }
     pub fn position_transparent_by_coords_and_ort(&self, coords: u32) -> Option<usize> {
-        self.transparent_faces.iter().position(|f| f.coords_and_ort() == coords)
+        self.transparent_faces.position(coords)
     }
     pub fn position_opaque_by_coords_and_ort(&self, coords: u32) -> Option<usize> {
-        self.opaque_faces.iter().position(|f| f.coords_and_ort() == coords)
+        self.opaque_faces.position(coords)
     }
-    pub fn find_transparent(&self, x: u8, y: u8, z: u8) -> Option<&Face> {
-        self.transparent_faces.iter().find(|f| f.matches_coords(x, y, z))
+    pub fn has_transparent(&self, x: u8, y: u8, z: u8) -> bool {
+        self.transparent_faces.contains_block(x, y, z)
     }
-    pub fn find_opaque(&self, x: u8, y: u8, z: u8) -> Option<&Face> {
-        self.opaque_faces.iter().find(|f| f.matches_coords(x, y, z))
+    pub fn has_opaque(&self, x: u8, y: u8, z: u8) -> bool {
+        self.opaque_faces.contains_block(x, y, z)
     }
-    pub(crate) fn remove_block_transparent(&mut self, x: usize, y: usize, z: usize) {
-        let (x, y, z) = WorldSize::absolute_block_to_chunk_block_position(x,y,z);
-        self.remove_transparent(x, y, z);
+    pub(crate) fn remove_block_transparent(&mut self, x: i32, y: i32, z: i32) {
+        let (x, y, z) = absolute_block_to_chunk_block_position(x,y,z);
+        self.remove_block(x, y, z, true)
     }
-
-    fn remove_transparent(&mut self, x: u8, y: u8, z: u8) {
-        let mut i = 0;
-        assert!(self.find_opaque(x, y, z).is_none());
-        assert!(self.find_transparent(x, y, z).is_some());
-        while i < self.transparent_faces.len() {
-            if self.transparent_faces[i].matches_coords(x, y, z) {
-                self.remove_transparent_at(i);
-            } else {
-                i += 1;
-            }
-        }
+    pub(crate) fn remove_block_opaque(&mut self, x: i32, y: i32, z: i32) {
+        let (x, y, z) = absolute_block_to_chunk_block_position(x,y,z);
+        self.remove_block(x, y, z, false)
     }
-    pub(crate) fn remove_block_opaque(&mut self, x: usize, y: usize, z: usize) {
-        let (x, y, z) = WorldSize::absolute_block_to_chunk_block_position(x,y,z);
-        self.remove_opaque(x, y, z)
-    }
-    fn remove_opaque(&mut self, x: u8, y: u8, z: u8) {
-        assert!(self.find_opaque(x, y, z).is_some());
-        assert!(self.find_transparent(x, y, z).is_none());
-        let mut i = 0;
-        while i < self.opaque_faces.len() {
-            if self.opaque_faces[i].matches_coords(x, y, z) {
-                self.remove_opaque_at(i);
-            } else {
-                i += 1;
-            }
-        }
+    /**Blocks enclosed by opaque neighbours have no faces, so there might be nothing to remove*/
+    fn remove_block(&mut self, x: u8, y: u8, z: u8, transparent: bool) {
+        assert!(!self.list(!transparent).contains_block(x, y, z));
+        self.list_mut(transparent).remove_block(x, y, z);
     }
-    pub(crate) fn update_block_textures(&mut self, x: usize, y: usize, z: usize, new_block: Block) {
-        let (x, y, z) = WorldSize::absolute_block_to_chunk_block_position(x,y,z);
+    pub(crate) fn update_block_textures(&mut self, x: i32, y: i32, z: i32, new_block: Block) {
+        let (x, y, z) = absolute_block_to_chunk_block_position(x,y,z);
         self.update_textures(x, y, z, new_block)
     }
     /**The transparency of old textures must be the same as that of new ones. If transparency can change, use change_textures instead*/
     fn update_textures(&mut self, x: u8, y: u8, z: u8, new_block: Block) {
         assert!(!new_block.is_air());
-        let faces = if new_block.is_transparent() {
-            assert!(self.find_opaque(x, y, z).is_none(), "Failed to update texture at {},{},{} to new block id {}", x, y, z, new_block);
-            assert!(self.find_transparent(x, y, z).is_some(), "Failed to update texture at {},{},{} to new block id {}", x, y, z, new_block);
-            self.has_transparent_faces_to_update = true;
-            &mut self.transparent_faces
-        } else {
-            assert!(self.find_opaque(x, y, z).is_some(), "Failed to update texture at {},{},{} to new block id {}", x, y, z, new_block);
-            assert!(self.find_transparent(x, y, z).is_none(), "Failed to update texture at {},{},{} to new block id {}", x, y, z, new_block);
-            self.has_opaque_faces_to_update = true;
-            &mut self.opaque_faces
-        };
-
-        for face in faces.iter_mut() {
-            if face.matches_coords(x, y, z) {
-                face.update_texture(new_block);
-            }
-        }
-    }
-    fn borrow_transparent_and_opaque_mut(&mut self) -> (&mut Vec<Face>, &mut Vec<Face>) {
-        let Self { transparent_faces, opaque_faces, .. } = self;
-        (transparent_faces, opaque_faces)
+        let transparent = new_block.is_blended();
+        assert!(!self.list(!transparent).contains_block(x, y, z), "Failed to update texture at {},{},{} to new block id {}", x, y, z, new_block);
+        self.list_mut(transparent).for_each_block_face_mut(x, y, z, |face| face.update_texture(new_block));
     }
-    pub(crate) fn change_block_textures(&mut self, x: usize, y: usize, z: usize, new_block: Block) {
-        let (x, y, z) = WorldSize::absolute_block_to_chunk_block_position(x,y,z);
+    pub(crate) fn change_block_textures(&mut self, x: i32, y: i32, z: i32, new_block: Block) {
+        let (x, y, z) = absolute_block_to_chunk_block_position(x,y,z);
         self.change_textures(x, y, z, new_block)
     }
     /**Changes textures on existing faces and assumes that the transparency is going to be switched. If transparency did not change, use update_textures instead*/
     fn change_textures(&mut self, x: u8, y: u8, z: u8, new_block: Block) {
         assert!(!new_block.is_air());
-        let (from, to) = if new_block.is_transparent() {
-            assert!(self.find_opaque(x, y, z).is_some(), "Failed to update texture at {},{},{} to new block id {}", x, y, z, new_block);
-            assert!(self.find_transparent(x, y, z).is_none(), "Failed to update texture at {},{},{} to new block id {}", x, y, z, new_block);
-            let (trans, opaq) = self.borrow_transparent_and_opaque_mut();
-            (opaq, trans)
-        } else {
-            assert!(self.find_opaque(x, y, z).is_none(), "Failed to update texture at {},{},{} to new block id {}", x, y, z, new_block);
-            assert!(self.find_transparent(x, y, z).is_some(), "Failed to update texture at {},{},{} to new block id {}", x, y, z, new_block);
-            self.borrow_transparent_and_opaque_mut()
-        };
-
-        let mut i = 0;
-        while i < from.len() {
-            if from[i].matches_coords(x, y, z) {
-                to.push(from.swap_remove(i))
-            } else {
-                i += 1;
-            }
+        let transparent = new_block.is_blended();
+        assert!(!self.list(transparent).contains_block(x, y, z), "Failed to update texture at {},{},{} to new block id {}", x, y, z, new_block);
+        for mut face in self.list_mut(!transparent).remove_block(x, y, z) {
+            face.update_texture(new_block);
+            self.list_mut(transparent).push(face);
         }
     }
-    pub(crate) fn remove_opaque_block_face(&mut self, x: usize, y: usize, z: usize, ort: FaceOrientation) {
-        let (x, y, z) = WorldSize::absolute_block_to_chunk_block_position(x,y,z);
-        self.remove_opaque_face(x, y, z, ort)
+    pub(crate) fn remove_opaque_block_face(&mut self, x: i32, y: i32, z: i32, ort: FaceOrientation) {
+        let (x, y, z) = absolute_block_to_chunk_block_position(x,y,z);
+        self.remove_face(x, y, z, ort, false)
     }
-    fn remove_opaque_face(&mut self, x: u8, y: u8, z: u8, ort: FaceOrientation) {
-        let face = Face::encode_coords_and_ort(x, y, z, ort);
-        self.remove_opaque_at(self.position_opaque_by_coords_and_ort(face).unwrap())
+    pub(crate) fn remove_transparent_block_face(&mut self, x: i32, y: i32, z: i32, ort: FaceOrientation) {
+        let (x, y, z) = absolute_block_to_chunk_block_position(x,y,z);
+        self.remove_face(x, y, z, ort, true)
     }
-    pub(crate) fn remove_transparent_block_face(&mut self, x: usize, y: usize, z: usize, ort: FaceOrientation) {
-        let (x, y, z) = WorldSize::absolute_block_to_chunk_block_position(x,y,z);
-        self.remove_transparent_face(x, y, z, ort)
-    }
-    fn remove_transparent_face(&mut self, x: u8, y: u8, z: u8, ort: FaceOrientation) {
+    fn remove_face(&mut self, x: u8, y: u8, z: u8, ort: FaceOrientation, transparent: bool) {
         let face = Face::encode_coords_and_ort(x, y, z, ort);
-        self.remove_transparent_at(self.position_transparent_by_coords_and_ort(face).unwrap())
-    }
-    fn update_texture(&mut self, idx: usize, new_block: Block) {
-        assert!(!new_block.is_air());
-        let face = if new_block.is_transparent() {
-            self.has_transparent_faces_to_update = true;
-            &mut self.transparent_faces[idx]
-        } else {
-            self.has_opaque_faces_to_update = true;
-            &mut self.opaque_faces[idx]
-        };
-        face.update_texture(new_block)
-    }
-    fn remove_transparent_at(&mut self, idx: usize) {
-        self.transparent_faces.swap_remove(idx);
-        self.has_transparent_faces_to_update = true;
-    }
-    fn remove_opaque_at(&mut self, idx: usize) {
-        self.opaque_faces.swap_remove(idx);
-        self.has_opaque_faces_to_update = true;
+        self.list_mut(transparent).remove(face).unwrap();
     }
 }
-
-
-
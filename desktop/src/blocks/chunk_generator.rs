@@ -0,0 +1,7 @@
+use crate::blocks::chunk_blocks::ChunkBlocks;
+
+/**Produces content of chunks that were never saved before. Generation must be deterministic,
+ because chunks can be generated in any order and at any time.*/
+pub trait ChunkGenerator: Send + Sync {
+    fn generate(&self, chunk_x: i32, chunk_z: i32, chunk: &mut ChunkBlocks);
+}
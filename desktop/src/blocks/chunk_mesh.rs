@@ -0,0 +1,61 @@
+use crate::blocks::world_blocks::WorldBlocks;
+use crate::blocks::face::Face;
+use crate::blocks::face_orientation::FaceOrientation;
+use crate::blocks::chunk_coords::{ChunkPos, CHUNK_WIDTH, CHUNK_DEPTH, SECTION_HEIGHT, SECTIONS, for_each_neighbour, chunk_pos_into_block_pos, absolute_block_to_chunk_block_position};
+use crate::blocks::section_visibility::SectionVisibility;
+use crate::blocks::greedy_mesher::{MeshingMode, merge_faces};
+use crate::blocks::ambient_occlusion::face_ambient_occlusion;
+use crate::blocks::light;
+
+/**Sky light, block light and ambient occlusion of a face*/
+pub(crate) fn face_shading(blocks: &WorldBlocks, x: i32, y: i32, z: i32, ort: FaceOrientation) -> (u8, u8, u8) {
+    let (sky_light, block_light) = light::face_light(blocks, x, y, z, ort);
+    (sky_light, block_light, face_ambient_occlusion(blocks, x, y, z, ort))
+}
+
+/**Faces of a chunk built on the CPU (possibly on a worker thread), ready to replace those held by ChunkFaces*/
+pub struct ChunkMesh {
+    pub(crate) opaque: Vec<Face>,
+    pub(crate) transparent: Vec<Face>,
+    pub(crate) visibility: [SectionVisibility; SECTIONS],
+}
+
+impl ChunkMesh {
+    /**Computes all faces of the chunk from scratch. Faces between this chunk and its neighbours
+    are only shown if the neighbour is loaded. Only the chunk and its neighbours need to be present in blocks*/
+    pub fn build(blocks: &WorldBlocks, chunk_pos: ChunkPos, meshing_mode: MeshingMode) -> Self {
+        let mut opaque = Vec::new();
+        let mut transparent = Vec::new();
+        let (from_x, from_z) = chunk_pos_into_block_pos(chunk_pos.0, chunk_pos.1);
+        let chunk_blocks = blocks.get_chunk(chunk_pos).unwrap();
+        // sections that hold only air have no faces of their own
+        let non_empty: Vec<usize> = (0..SECTIONS).filter(|&section| !chunk_blocks.is_section_empty(section)).collect();
+        for x in from_x..from_x + CHUNK_WIDTH as i32 {
+            for z in from_z..from_z + CHUNK_DEPTH as i32 {
+                for y in non_empty.iter().flat_map(|&section| (section * SECTION_HEIGHT) as i32..((section + 1) * SECTION_HEIGHT) as i32) {
+                    let &block = blocks.get_block(x, y, z);
+                    if block.show_my_faces() {
+                        for_each_neighbour(x, y, z, |neighbour_x, neighbour_y, neighbour_z, ort| {
+                            if let Some(neighbour) = blocks.try_get_block(neighbour_x, neighbour_y, neighbour_z) {
+                                if neighbour.show_neighboring_faces() || !block.is_full_cube() {
+                                    let (chunk_x, chunk_y, chunk_z) = absolute_block_to_chunk_block_position(x, y, z);
+                                    let face = Face::of_block(chunk_x, chunk_y, chunk_z, ort, block, face_shading(blocks, x, y, z, ort));
+                                    if block.is_blended() { &mut transparent } else { &mut opaque }.push(face);
+                                }
+                            }
+                        });
+                    }
+                }
+            }
+        }
+        if meshing_mode == MeshingMode::Greedy {
+            opaque = merge_faces(&opaque);
+            transparent = merge_faces(&transparent);
+        }
+        let mut visibility = [SectionVisibility::ALL; SECTIONS];
+        for (section, visibility) in visibility.iter_mut().enumerate() {
+            *visibility = SectionVisibility::compute(chunk_blocks, section);
+        }
+        Self { opaque, transparent, visibility }
+    }
+}
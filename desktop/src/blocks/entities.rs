@@ -3,6 +3,7 @@ use crate::render_gl::uniform_buffer::{UniformBuffer, ShaderStorageBuffer, Varia
 use crate::render_gl::buffer::ShaderStorageArrayBuffer;
 use crate::render_gl::data::VertexAttribPointers;
 use num_traits::FloatConst;
+use crate::blocks::raycast::ray_aabb;
 
 #[derive(Copy, Clone, Debug)]
 #[repr(C, packed)]
@@ -33,6 +34,25 @@ impl BoneInstance {
         self.body_part_and_bone_variant.d1
     }
 
+    /**Position of the joint in world-space*/
+    pub fn position(&self) -> glm::Vec3 {
+        self.position
+    }
+    pub fn rotation(&self) -> glm::Quat {
+        self.rotation
+    }
+    /**Distance along the normalized direction at which the ray enters the box of this bone.
+    The ray is moved into the bone's local space, where the box is axis-aligned*/
+    pub fn ray_intersection(&self, origin: &glm::Vec3, direction: &glm::Vec3) -> Option<f32> {
+        let inverse_rotation = glm::quat_inverse(&self.rotation());
+        let local_origin = glm::quat_rotate_vec3(&inverse_rotation, &(origin - self.position()));
+        let local_direction = glm::quat_rotate_vec3(&inverse_rotation, direction);
+        let body_part = self.body_part();
+        let min = -body_part.joint_position();
+        let max = body_part.size() + min;
+        ray_aabb(&local_origin, &local_direction, &min, &max)
+    }
+
     pub fn update(&mut self,entity_position: &glm::Vec3, entity_rotation:&glm::Quat){
         let body_part = self.body_part();
         self.position=body_part.absolute_rotated_position(entity_position,entity_rotation);
@@ -71,6 +91,27 @@ impl BodyPart {
         }
     }
 
+    /**Size of the box of the bone. Must agree with body_part_size_and_joint_pos in mobs.vert*/
+    pub fn size(&self) -> glm::Vec3 {
+        match self {
+            BodyPart::ZombieLeftLeg | BodyPart::ZombieRightLeg |
+            BodyPart::ZombieLeftArm | BodyPart::ZombieRightArm => glm::vec3(U * 4., U * 12., U * 4.),
+            BodyPart::ZombieTorso => glm::vec3(U * 8., U * 12., U * 4.),
+            BodyPart::ZombieHead => glm::vec3(U * 8., U * 8., U * 8.),
+        }
+    }
+    /**Position of the joint relative to the minimal corner of the bone's box. Must agree with mobs.vert*/
+    pub fn joint_position(&self) -> glm::Vec3 {
+        match self {
+            BodyPart::ZombieLeftLeg => glm::vec3(U * 4., U * 12., U * 2.),
+            BodyPart::ZombieRightLeg => glm::vec3(U * 0., U * 12., U * 2.),
+            BodyPart::ZombieTorso => glm::vec3(U * 4., U * 6., U * 2.),
+            BodyPart::ZombieHead => glm::vec3(U * 4., U * 0., U * 4.),
+            BodyPart::ZombieLeftArm => glm::vec3(U * 4., U * 10., U * 2.),
+            BodyPart::ZombieRightArm => glm::vec3(U * 0., U * 10., U * 2.),
+        }
+    }
+
     pub fn absolute_position(&self, entity_abs_position: &glm::Vec3) -> glm::Vec3 {
         self.relative_position() + entity_abs_position
     }
@@ -81,12 +122,13 @@ impl BodyPart {
 }
 
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(FromPrimitive, Clone, Copy, PartialEq, Eq, Debug)]
 pub enum ZombieVariant {
     Zombie = 0,
     Steve = 1,
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Entity {
     Zombie(ZombieVariant)
 }
@@ -103,19 +145,69 @@ enum EntityBones {
     Zombie(/*left leg*/usize, /*right leg*/usize, /*torso*/usize, /*head*/usize, /*left arm*/usize, /*right arm*/usize)
 }
 
+/**Bone of an entity hit by a ray cast with Entities::ray_cast*/
+#[derive(Copy, Clone, Debug)]
+pub struct EntityHit {
+    pub entity_id: usize,
+    pub body_part: BodyPart,
+    /**Distance from the origin of the ray to the point where it entered the bone*/
+    pub distance: f32,
+}
+
 pub struct Entities {
     bones: Vec<BoneInstance>,
     bone_to_entity: Vec<usize>,
     entity_to_bones: Vec<EntityBones>,
+    entity_poses: Vec<(Entity, glm::Vec3, glm::Quat)>,
+    /**Velocity caused by external forces, such as knockback of explosions*/
+    velocities: Vec<glm::Vec3>,
 }
 
 impl Entities {
     pub fn new() -> Self {
-        Self { bones: vec![], bone_to_entity: vec![], entity_to_bones: vec![] }
+        Self { bones: vec![], bone_to_entity: vec![], entity_to_bones: vec![], entity_poses: vec![], velocities: vec![] }
     }
     pub fn bone_slice(&self) -> &[BoneInstance] {
         &self.bones
     }
+    pub fn len(&self) -> usize {
+        self.entity_poses.len()
+    }
+    pub fn entity(&self, entity_id: usize) -> Entity {
+        self.entity_poses[entity_id].0
+    }
+    pub fn position(&self, entity_id: usize) -> &glm::Vec3 {
+        &self.entity_poses[entity_id].1
+    }
+    pub fn rotation(&self, entity_id: usize) -> &glm::Quat {
+        &self.entity_poses[entity_id].2
+    }
+    pub fn velocity(&self, entity_id: usize) -> &glm::Vec3 {
+        &self.velocities[entity_id]
+    }
+    pub fn set_velocity(&mut self, entity_id: usize, velocity: &glm::Vec3) {
+        self.velocities[entity_id] = velocity.clone();
+    }
+    pub fn apply_impulse(&mut self, entity_id: usize, impulse: &glm::Vec3) {
+        self.velocities[entity_id] += impulse;
+    }
+    /**Entity owning the bone at the given index of bone_slice*/
+    pub fn bone_owner(&self, bone_idx: usize) -> usize {
+        self.bone_to_entity[bone_idx]
+    }
+    /**Closest bone of any entity hit by the ray no further than max_distance*/
+    pub fn ray_cast(&self, origin: &glm::Vec3, direction: &glm::Vec3, max_distance: f32) -> Option<EntityHit> {
+        let direction = glm::normalize(direction);
+        let mut closest: Option<EntityHit> = None;
+        for (bone_idx, bone) in self.bones.iter().enumerate() {
+            if let Some(distance) = bone.ray_intersection(origin, &direction) {
+                if distance <= max_distance && closest.map(|hit| distance < hit.distance).unwrap_or(true) {
+                    closest = Some(EntityHit { entity_id: self.bone_to_entity[bone_idx], body_part: bone.body_part(), distance });
+                }
+            }
+        }
+        closest
+    }
     fn add_bone(&mut self, owning_entity: usize, bone: BoneInstance) -> usize {
         let new_idx = self.bones.len();
         assert_eq!(self.bones.len(), self.bone_to_entity.len());
@@ -147,6 +239,9 @@ impl Entities {
         assert_eq!(self.bone_to_entity.len(), self.bones.len());
     }
     pub fn update(&mut self, entity_id: usize, entity_position: &glm::Vec3, entity_rotation:&glm::Quat) {
+        let pose = &mut self.entity_poses[entity_id];
+        pose.1 = entity_position.clone();
+        pose.2 = entity_rotation.clone();
         match self.entity_to_bones[entity_id]{
             EntityBones::Zombie(left_leg, right_leg, torso, head, left_arm, right_arm) => {
                 self.bones[left_leg].update(entity_position, entity_rotation);
@@ -173,6 +268,8 @@ impl Entities {
                     self.add_bone(entity_id, BoneInstance::new(entity_position, entity_rotation,BodyPart::ZombieRightArm, variant, glm::quat_identity())),
                 );
                 self.entity_to_bones.push(bones);
+                self.entity_poses.push((ent, entity_position.clone(), entity_rotation.clone()));
+                self.velocities.push(glm::vec3(0., 0., 0.));
                 entity_id
             }
         }
@@ -191,6 +288,8 @@ impl Entities {
             }
         }
         self.entity_to_bones.swap_remove(entity_id);
+        self.entity_poses.swap_remove(entity_id);
+        self.velocities.swap_remove(entity_id);
         assert_eq!(self.bone_to_entity.len(),self.bones.len())
     }
 }
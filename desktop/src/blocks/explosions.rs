@@ -0,0 +1,144 @@
+use std::collections::HashSet;
+use rand::random;
+use crate::blocks::block_properties::TNT;
+use crate::blocks::entities::Entities;
+use crate::blocks::raycast::ray_cast;
+use crate::blocks::world_blocks::WorldBlocks;
+
+/**Seconds between igniting TNT and its explosion*/
+pub const TNT_FUSE: f32 = 4.;
+/**TNT ignited by another explosion goes off after a random time from this range (in seconds)*/
+pub const CHAINED_FUSE: (f32, f32) = (0.5, 1.5);
+pub const TNT_POWER: f32 = 4.;
+/**Explosions are cast along rays going from the centre to every point on the surface of a cube with this many points per edge*/
+const RAYS_PER_EDGE: i32 = 16;
+/**Intensity lost by a ray with every block it passes, even if the block is air*/
+const ATTENUATION_PER_BLOCK: f32 = 1.05;
+/**Scales blast resistance of blocks to intensity lost when passing through them*/
+const RESISTANCE_SCALE: f32 = 0.2;
+/**Speed (in blocks per second) given to entities right next to the centre of an explosion of power 1*/
+const KNOCKBACK_PER_POWER: f32 = 4.;
+
+pub struct PrimedTnt {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    /**Seconds left until the explosion*/
+    pub fuse: f32,
+    pub power: f32,
+}
+
+impl PrimedTnt {
+    pub fn center(&self) -> glm::Vec3 {
+        glm::vec3(self.x as f32 + 0.5, self.y as f32 + 0.5, self.z as f32 + 0.5)
+    }
+}
+
+/**Ignited TNT blocks waiting for their fuse to burn out. TNT stays in the world as a regular block until it explodes*/
+pub struct Explosions {
+    primed: Vec<PrimedTnt>,
+}
+
+impl Explosions {
+    pub fn new() -> Self {
+        Self { primed: Vec::new() }
+    }
+    pub fn is_primed(&self, x: i32, y: i32, z: i32) -> bool {
+        self.primed.iter().any(|tnt| (tnt.x, tnt.y, tnt.z) == (x, y, z))
+    }
+    /**Returns false if the TNT was already burning*/
+    pub fn ignite(&mut self, x: i32, y: i32, z: i32, fuse: f32) -> bool {
+        if self.is_primed(x, y, z) {
+            false
+        } else {
+            self.primed.push(PrimedTnt { x, y, z, fuse, power: TNT_POWER });
+            true
+        }
+    }
+    /**TNT hit by an explosion goes off sooner than TNT lit by hand*/
+    pub fn ignite_chained(&mut self, x: i32, y: i32, z: i32) -> bool {
+        let (from, to) = CHAINED_FUSE;
+        self.ignite(x, y, z, from + random::<f32>() * (to - from))
+    }
+    /**Burns fuses for delta seconds and returns TNT that should explode now*/
+    pub fn update(&mut self, delta: f32) -> Vec<PrimedTnt> {
+        let mut exploding = Vec::new();
+        let mut i = 0;
+        while i < self.primed.len() {
+            self.primed[i].fuse -= delta;
+            if self.primed[i].fuse <= 0. {
+                exploding.push(self.primed.swap_remove(i));
+            } else {
+                i += 1;
+            }
+        }
+        exploding
+    }
+}
+
+/**Result of casting an explosion against the world, before anything is changed*/
+pub struct Blast {
+    /**Blocks that should be removed*/
+    pub destroyed: Vec<(i32, i32, i32)>,
+    /**TNT blocks reached by the explosion. They are not destroyed, but ignited*/
+    pub ignited: Vec<(i32, i32, i32)>,
+}
+
+/**Casts rays from the centre in all directions. Every ray starts with intensity proportional to the power
+(with some randomness) and loses some of it with every block, depending on its blast resistance.
+Blocks are destroyed for as long as the intensity stays positive. Blocks with negative resistance stop the ray.*/
+pub fn cast_blast(blocks: &WorldBlocks, center: &glm::Vec3, power: f32) -> Blast {
+    let mut destroyed = HashSet::new();
+    let mut ignited = HashSet::new();
+    let max_distance = power * 1.3 / ATTENUATION_PER_BLOCK + 1.;
+    let edge = (RAYS_PER_EDGE - 1) as f32;
+    for i in 0..RAYS_PER_EDGE {
+        for j in 0..RAYS_PER_EDGE {
+            for k in 0..RAYS_PER_EDGE {
+                let on_surface = i == 0 || j == 0 || k == 0 || i == RAYS_PER_EDGE - 1 || j == RAYS_PER_EDGE - 1 || k == RAYS_PER_EDGE - 1;
+                if !on_surface {
+                    continue;
+                }
+                let direction = glm::normalize(&glm::vec3(i as f32 / edge * 2. - 1., j as f32 / edge * 2. - 1., k as f32 / edge * 2. - 1.));
+                let mut intensity = power * (0.7 + random::<f32>() * 0.6);
+                ray_cast(center.as_slice(), (direction * max_distance).as_slice(), |x, y, z, _, _, _| {
+                    let (x, y, z) = (x as i32, y as i32, z as i32);
+                    let block = match blocks.try_get_block(x, y, z) {
+                        Some(&block) => block,
+                        None => return Some(())
+                    };
+                    let resistance = block.blast_resistance();
+                    if resistance < 0. {
+                        return Some(());
+                    }
+                    intensity -= ATTENUATION_PER_BLOCK + resistance * RESISTANCE_SCALE;
+                    if intensity <= 0. {
+                        return Some(());
+                    }
+                    if block.is_same_kind(TNT) {
+                        ignited.insert((x, y, z));
+                    } else if !block.is_air() {
+                        destroyed.insert((x, y, z));
+                    }
+                    None
+                });
+            }
+        }
+    }
+    Blast { destroyed: destroyed.into_iter().collect(), ignited: ignited.into_iter().collect() }
+}
+
+/**Pushes entities away from the centre. The closer they are, the stronger the push*/
+pub fn knockback(entities: &mut Entities, center: &glm::Vec3, power: f32) {
+    let radius = power * 2.;
+    for entity_id in 0..entities.len() {
+        let offset = entities.position(entity_id) - center;
+        let distance = glm::length(&offset);
+        if distance < radius {
+            let direction = if distance > 0.001 { offset / distance } else { glm::vec3(0., 1., 0.) };
+            let strength = (1. - distance / radius) * power * KNOCKBACK_PER_POWER;
+            // entities are lifted a little, so that they don't just slide along the ground
+            entities.apply_impulse(entity_id, &((direction + glm::vec3(0., 0.5, 0.)) * strength));
+        }
+    }
+}
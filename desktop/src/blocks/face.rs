@@ -2,9 +2,21 @@ use crate::render_gl::data::u8_u8_u8_u8;
 use crate::blocks::block::Block;
 use crate::render_gl::data::VertexAttribPointers;
 use crate::blocks::face_orientation::FaceOrientation;
-use crate::blocks::world_size::{CHUNK_WIDTH, CHUNK_HEIGHT, CHUNK_DEPTH};
+use crate::blocks::chunk_coords::{CHUNK_WIDTH, CHUNK_HEIGHT, CHUNK_DEPTH};
 use crate::render_gl::data::VertexAttrib;
 
+/**Two highest bits of tex_id hold the FaceShape, the rest is the id of the texture*/
+const SHAPE_SHIFT: u32 = 30;
+const TEXTURE_MASK: u32 = (1 << SHAPE_SHIFT) - 1;
+
+/**Part of the block height covered by a face. The vertex shader squeezes faces of slabs into their half*/
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum FaceShape {
+    Full = 0,
+    LowerHalf = 1,
+    UpperHalf = 2,
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 #[repr(C, packed)]
 #[derive(VertexAttribPointers)]
@@ -12,15 +24,48 @@ pub struct Face {
     #[location = 11]
     #[divisor = 1]
     coords: u8_u8_u8_u8,
+    /**Sky light and block light seen by the face, ambient occlusion of its 4 corners (2 bits each)
+    and size of the quad along both tangent axes (4 bits each, storing size-1)*/
+    #[location = 12]
+    #[divisor = 1]
+    shading: u8_u8_u8_u8,
     #[location = 13]
     #[divisor = 1]
     tex_id: u32,
 }
 
+fn encode_texture(texture_id: u32, shape: FaceShape) -> u32 {
+    assert!(texture_id <= TEXTURE_MASK);
+    texture_id | ((shape as u32) << SHAPE_SHIFT)
+}
+
 impl Face {
     pub fn update_texture(&mut self, new_block: Block) {
         let ort = self.block_orientation();
-        self.tex_id = new_block.texture_id(ort);
+        self.tex_id = encode_texture(new_block.texture_id(ort), new_block.face_shape());
+    }
+    pub fn light(&self) -> (u8, u8) {
+        (self.shading.d0, self.shading.d1)
+    }
+    pub fn ambient_occlusion(&self) -> u8 {
+        self.shading.d2
+    }
+    /**Shading consists of sky light, block light and ambient occlusion. Returns true if the shading has changed*/
+    pub fn set_shading(&mut self, (sky_light, block_light, ambient_occlusion): (u8, u8, u8)) -> bool {
+        let new_shading = u8_u8_u8_u8::new(sky_light, block_light, ambient_occlusion, self.shading.d3);
+        let changed = *new_shading.as_u32() != *self.shading.as_u32();
+        self.shading = new_shading;
+        changed
+    }
+    /**Number of blocks covered by the quad along the first and second tangent axis of its orientation.
+    Faces built one per block are 1x1, merged faces can be up to 16x16.*/
+    pub fn size(&self) -> (usize, usize) {
+        ((self.shading.d3 & 0xF) as usize + 1, (self.shading.d3 >> 4) as usize + 1)
+    }
+    pub fn set_size(&mut self, width: usize, height: usize) {
+        assert!(1 <= width && width <= 16);
+        assert!(1 <= height && height <= 16);
+        self.shading.d3 = (width - 1) as u8 | (((height - 1) as u8) << 4);
     }
     pub fn coords_and_ort(&self) -> u32 {
         self.coords.as_u32().clone()
@@ -44,7 +89,14 @@ impl Face {
         self.coords.d3
     }
     pub fn texture_id(&self) -> u32 {
-        self.tex_id
+        self.tex_id & TEXTURE_MASK
+    }
+    pub fn shape(&self) -> FaceShape {
+        match self.tex_id >> SHAPE_SHIFT {
+            1 => FaceShape::LowerHalf,
+            2 => FaceShape::UpperHalf,
+            _ => FaceShape::Full,
+        }
     }
     pub fn block_x(&self) -> usize {
         self.coords.d0 as usize
@@ -64,7 +116,7 @@ impl Face {
         assert!((z as usize) < CHUNK_DEPTH);
         u8_u8_u8_u8::from((x, y, z, orientation as u8)).as_u32().clone()
     }
-    pub fn from_coords_and_ort(x: u8, y: u8, z: u8, orientation: FaceOrientation, texture_id: u32) -> Self {
+    pub fn from_coords_and_ort(x: u8, y: u8, z: u8, orientation: FaceOrientation, texture_id: u32, (sky_light, block_light, ambient_occlusion): (u8, u8, u8)) -> Self {
         assert!((x as usize) < CHUNK_WIDTH);
         assert!((y as usize) < CHUNK_HEIGHT);
         assert!((z as usize) < CHUNK_DEPTH);
@@ -72,6 +124,34 @@ impl Face {
             std::mem::size_of::<FaceOrientation>(),
             std::mem::size_of::<u8>()
         );
-        Self { coords: u8_u8_u8_u8::from((x, y, z, orientation as u8)), tex_id: texture_id }
+        Self { coords: u8_u8_u8_u8::from((x, y, z, orientation as u8)), shading: u8_u8_u8_u8::new(sky_light, block_light, ambient_occlusion, 0), tex_id: encode_texture(texture_id, FaceShape::Full) }
+    }
+    /**Face of the given block with its texture and shape*/
+    pub fn of_block(x: u8, y: u8, z: u8, orientation: FaceOrientation, block: Block, shading: (u8, u8, u8)) -> Self {
+        let mut face = Self::from_coords_and_ort(x, y, z, orientation, 0, shading);
+        face.update_texture(block);
+        face
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::block_properties::{SLAB, STONE};
+    use crate::blocks::block_state::BlockState;
+    use crate::blocks::block_registry;
+
+    #[test]
+    fn shape_is_stored_next_to_texture() {
+        block_registry::init_for_tests();
+        let top_slab = SLAB.with_state(BlockState::default().with_top_half(true));
+        let mut face = Face::of_block(1, 2, 3, FaceOrientation::XPlus, top_slab, (15, 0, 0xFF));
+        assert_eq!(face.shape(), FaceShape::UpperHalf);
+        assert_eq!(face.texture_id(), top_slab.texture_id(FaceOrientation::XPlus));
+        face.update_texture(SLAB);
+        assert_eq!(face.shape(), FaceShape::LowerHalf);
+        face.update_texture(STONE);
+        assert_eq!(face.shape(), FaceShape::Full);
+        assert_eq!(face.texture_id(), STONE.texture_id(FaceOrientation::XPlus));
+        assert_eq!(face.coords_and_ort(), Face::encode_coords_and_ort(1, 2, 3, FaceOrientation::XPlus));
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use crate::blocks::face::Face;
+use crate::blocks::face_orientation::FaceOrientation;
+
+/**Faces stored contiguously (ready to be uploaded to GPU) together with an index from coords_and_ort to their slot.
+The index is kept in sync across swap_remove, so looking up, inserting and removing faces takes constant time.*/
+pub struct FaceList {
+    faces: Vec<Face>,
+    index: HashMap<u32, usize>,
+    /**Set whenever faces change and reset once they are uploaded to GPU*/
+    has_faces_to_update: bool,
+    /**Set when the same faces were only reordered, so the buffer needs to be uploaded again but its bounds didn't change*/
+    is_reordered: bool,
+}
+
+impl FaceList {
+    pub fn new() -> Self {
+        Self { faces: Vec::new(), index: HashMap::new(), has_faces_to_update: false, is_reordered: false }
+    }
+    pub fn len(&self) -> usize {
+        self.faces.len()
+    }
+    pub fn as_slice(&self) -> &[Face] {
+        self.faces.as_slice()
+    }
+    pub fn has_faces_to_update(&self) -> bool {
+        self.has_faces_to_update
+    }
+    /**Returns true if faces have changed since the last call. Uploading changed faces also uploads their new order*/
+    pub(crate) fn take_faces_to_update(&mut self) -> bool {
+        self.is_reordered = false;
+        std::mem::replace(&mut self.has_faces_to_update, false)
+    }
+    /**Returns true if faces were reordered (but otherwise didn't change) since they were last uploaded*/
+    pub(crate) fn take_reordered(&mut self) -> bool {
+        !self.has_faces_to_update && std::mem::replace(&mut self.is_reordered, false)
+    }
+    fn set_faces(&mut self, faces: Vec<Face>) {
+        self.index = faces.iter().enumerate().map(|(slot, face)| (face.coords_and_ort(), slot)).collect();
+        assert_eq!(self.index.len(), faces.len());
+        self.faces = faces;
+    }
+    /**Replaces all faces. No two faces may share coords_and_ort.*/
+    pub(crate) fn replace_all(&mut self, faces: Vec<Face>) {
+        self.set_faces(faces);
+        self.has_faces_to_update = true;
+    }
+    /**Orders faces by decreasing key (such as the distance from the camera), so that the furthest ones are drawn first*/
+    pub(crate) fn sort_back_to_front(&mut self, mut key: impl FnMut(&Face) -> f32) {
+        let mut keyed: Vec<(f32, Face)> = self.faces.iter().map(|face| (key(face), *face)).collect();
+        keyed.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        self.set_faces(keyed.into_iter().map(|(_, face)| face).collect());
+        self.is_reordered = true;
+    }
+    pub fn position(&self, coords_and_ort: u32) -> Option<usize> {
+        self.index.get(&coords_and_ort).cloned()
+    }
+    pub fn get(&self, coords_and_ort: u32) -> Option<&Face> {
+        self.position(coords_and_ort).map(|slot| &self.faces[slot])
+    }
+    /**Coordinates and orientation of the returned face must not be changed, otherwise the index gets out of sync*/
+    pub(crate) fn get_mut(&mut self, coords_and_ort: u32) -> Option<&mut Face> {
+        let slot = self.position(coords_and_ort)?;
+        self.has_faces_to_update = true;
+        Some(&mut self.faces[slot])
+    }
+    pub fn contains_block(&self, x: u8, y: u8, z: u8) -> bool {
+        FaceOrientation::ALL.iter().any(|&ort| self.index.contains_key(&Face::encode_coords_and_ort(x, y, z, ort)))
+    }
+    pub(crate) fn push(&mut self, face: Face) {
+        let prev = self.index.insert(face.coords_and_ort(), self.faces.len());
+        assert!(prev.is_none(), "Face at {},{},{} {:?} already exists", face.x(), face.y(), face.z(), face.block_orientation());
+        self.faces.push(face);
+        self.has_faces_to_update = true;
+    }
+    pub(crate) fn remove(&mut self, coords_and_ort: u32) -> Option<Face> {
+        let slot = self.index.remove(&coords_and_ort)?;
+        let face = self.faces.swap_remove(slot);
+        if let Some(moved) = self.faces.get(slot) {
+            self.index.insert(moved.coords_and_ort(), slot);
+        }
+        self.has_faces_to_update = true;
+        Some(face)
+    }
+    /**Removes all faces of the block and returns them*/
+    pub(crate) fn remove_block(&mut self, x: u8, y: u8, z: u8) -> Vec<Face> {
+        FaceOrientation::ALL.iter().filter_map(|&ort| self.remove(Face::encode_coords_and_ort(x, y, z, ort))).collect()
+    }
+    /**Calls f on every face of the block*/
+    pub(crate) fn for_each_block_face_mut(&mut self, x: u8, y: u8, z: u8, mut f: impl FnMut(&mut Face)) {
+        for &ort in FaceOrientation::ALL.iter() {
+            if let Some(face) = self.get_mut(Face::encode_coords_and_ort(x, y, z, ort)) {
+                f(face)
+            }
+        }
+    }
+    /**Coordinates and orientation of faces must not be changed by f. Returns true if f reported a change of any face*/
+    pub(crate) fn update_all(&mut self, mut f: impl FnMut(&mut Face) -> bool) -> bool {
+        let mut changed = false;
+        for face in self.faces.iter_mut() {
+            changed |= f(face);
+        }
+        self.has_faces_to_update |= changed;
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn face(x: u8, ort: FaceOrientation) -> Face {
+        Face::from_coords_and_ort(x, 1, 2, ort, x as u32, (15, 0, 0xFF))
+    }
+
+    /**Every face can be found at the slot the index points to*/
+    fn assert_index_in_sync(list: &FaceList) {
+        assert_eq!(list.index.len(), list.len());
+        for (slot, face) in list.as_slice().iter().enumerate() {
+            assert_eq!(list.position(face.coords_and_ort()), Some(slot));
+        }
+    }
+
+    #[test]
+    fn swap_remove_keeps_index_in_sync() {
+        let mut list = FaceList::new();
+        for x in 0..5 {
+            list.push(face(x, FaceOrientation::YPlus));
+        }
+        list.push(face(0, FaceOrientation::XMinus));
+        assert!(list.take_faces_to_update());
+        // removing from the middle moves the last face into the freed slot
+        let removed = list.remove(face(1, FaceOrientation::YPlus).coords_and_ort()).unwrap();
+        assert_eq!(removed.texture_id(), 1);
+        assert!(list.has_faces_to_update());
+        assert_eq!(list.position(face(0, FaceOrientation::XMinus).coords_and_ort()), Some(1));
+        assert_index_in_sync(&list);
+        // removing the last face moves nothing
+        list.remove(face(4, FaceOrientation::YPlus).coords_and_ort()).unwrap();
+        assert_index_in_sync(&list);
+        assert!(list.remove(face(4, FaceOrientation::YPlus).coords_and_ort()).is_none());
+        assert_eq!(list.remove_block(0, 1, 2).len(), 2);
+        assert!(!list.contains_block(0, 1, 2));
+        assert!(list.contains_block(2, 1, 2));
+        assert_index_in_sync(&list);
+        assert_eq!(list.len(), 2);
+    }
+
+    #[test]
+    fn replace_all_and_sorting_rebuild_the_index() {
+        let mut list = FaceList::new();
+        list.push(face(7, FaceOrientation::ZPlus));
+        list.replace_all((0..4).map(|x| face(x, FaceOrientation::ZMinus)).collect());
+        assert!(list.get(face(7, FaceOrientation::ZPlus).coords_and_ort()).is_none());
+        assert_index_in_sync(&list);
+        list.sort_back_to_front(|face| face.x() as f32);
+        let order: Vec<u8> = list.as_slice().iter().map(|face| face.x()).collect();
+        assert_eq!(order, vec![3, 2, 1, 0]);
+        assert_index_in_sync(&list);
+        list.get_mut(face(2, FaceOrientation::ZMinus).coords_and_ort()).unwrap().set_shading((3, 4, 0xFF));
+        assert_eq!(list.get(face(2, FaceOrientation::ZMinus).coords_and_ort()).unwrap().light(), (3, 4));
+    }
+
+    #[test]
+    fn sorting_only_marks_faces_as_reordered() {
+        let mut list = FaceList::new();
+        list.replace_all((0..4).map(|x| face(x, FaceOrientation::ZMinus)).collect());
+        list.sort_back_to_front(|face| face.x() as f32);
+        // changed faces have to be uploaded anyway, which takes care of the new order too
+        assert!(!list.take_reordered());
+        assert!(list.take_faces_to_update());
+        assert!(!list.is_reordered);
+        list.sort_back_to_front(|face| -(face.x() as f32));
+        assert!(!list.has_faces_to_update());
+        assert!(list.is_reordered);
+        assert!(list.take_reordered());
+        assert!(!list.take_reordered());
+        // sorting and then changing a face needs a full upload
+        list.sort_back_to_front(|face| face.x() as f32);
+        list.remove(face(0, FaceOrientation::ZMinus).coords_and_ort()).unwrap();
+        assert!(!list.take_reordered());
+        assert!(list.take_faces_to_update());
+        assert!(!list.take_reordered());
+    }
+
+    #[test]
+    #[should_panic]
+    fn duplicate_faces_are_rejected() {
+        let mut list = FaceList::new();
+        list.push(face(3, FaceOrientation::YPlus));
+        list.push(face(3, FaceOrientation::YPlus));
+    }
+}
@@ -10,6 +10,7 @@ pub enum FaceOrientation {
 }
 
 impl FaceOrientation {
+    pub const ALL: [FaceOrientation; 6] = [FaceOrientation::YPlus, FaceOrientation::YMinus, FaceOrientation::XPlus, FaceOrientation::XMinus, FaceOrientation::ZPlus, FaceOrientation::ZMinus];
     pub fn is_side(&self) -> bool {
         (self.clone() as u8) > 1
     }
@@ -24,4 +25,19 @@ impl FaceOrientation {
             }
         }
     }
+    /**Inverse of offset. Returns None if the vector is not a unit vector along one of the axes*/
+    pub fn from_offset(offset: (i32, i32, i32)) -> Option<FaceOrientation> {
+        FaceOrientation::ALL.iter().cloned().find(|ort| ort.offset() == offset)
+    }
+    /**Unit vector pointing out of the face*/
+    pub fn offset(&self) -> (i32, i32, i32) {
+        match self {
+            FaceOrientation::YPlus => (0, 1, 0),
+            FaceOrientation::YMinus => (0, -1, 0),
+            FaceOrientation::XPlus => (1, 0, 0),
+            FaceOrientation::XMinus => (-1, 0, 0),
+            FaceOrientation::ZPlus => (0, 0, 1),
+            FaceOrientation::ZMinus => (0, 0, -1),
+        }
+    }
 }
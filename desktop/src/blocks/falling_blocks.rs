@@ -0,0 +1,245 @@
+use crate::blocks::block::Block;
+use crate::blocks::face::Face;
+use crate::blocks::face_orientation::FaceOrientation;
+use crate::blocks::world_blocks::WorldBlocks;
+use crate::blocks::light::{light_at, LightChannel, MAX_LIGHT};
+use crate::blocks::greedy_mesher::NO_OCCLUSION;
+use crate::blocks::chunk_coords::CHUNK_HEIGHT;
+use crate::render_gl::instanced_logical_model::InstancedLogicalModel;
+use crate::render_gl::buffer::{BufferDynamicDraw, DynamicBuffer};
+use crate::render_gl::shader::UniformVec3fv;
+use crate::render_gl::Program;
+
+/**Blocks per second squared*/
+pub const GRAVITY: f32 = 20.;
+/**Blocks per second*/
+pub const TERMINAL_VELOCITY: f32 = 40.;
+
+/**Block that lost its support and falls down as an entity until it lands and becomes a block again.
+Falling blocks only move vertically, so they stay aligned with the column they fell from.*/
+pub struct FallingBlock {
+    block: Block,
+    x: i32,
+    z: i32,
+    /**Height of the bottom of the block*/
+    y: f32,
+    velocity: f32,
+    /**Light of the cell in which the block currently is, used to shade its faces*/
+    light: (u8, u8),
+    model: InstancedLogicalModel<Face, BufferDynamicDraw>,
+}
+
+impl FallingBlock {
+    pub fn new(block: Block, x: i32, y: i32, z: i32, gl: &gl::Gl) -> Self {
+        let mut me = Self {
+            block,
+            x,
+            z,
+            y: y as f32,
+            velocity: 0.,
+            light: (MAX_LIGHT, 0),
+            model: InstancedLogicalModel::new(DynamicBuffer::with_capacity(6, gl), gl),
+        };
+        me.update_model();
+        me
+    }
+    pub fn block(&self) -> Block {
+        self.block
+    }
+    pub fn position(&self) -> glm::Vec3 {
+        glm::vec3(self.x as f32, self.y, self.z as f32)
+    }
+    fn update_model(&mut self) {
+        let (sky, block_light) = self.light;
+        let block = self.block;
+        let faces: Vec<Face> = FaceOrientation::ALL.iter()
+            .map(|&ort| Face::of_block(0, 0, 0, ort, block, (sky, block_light, NO_OCCLUSION)))
+            .collect();
+        self.model.ibo_mut().update(&faces);
+    }
+    /**Light is sampled at the centre of the block*/
+    fn update_light(&mut self, blocks: &WorldBlocks) {
+        let y = (self.y + 0.5).floor() as i32;
+        let light = (
+            light_at(blocks, LightChannel::Sky, self.x, y, self.z).unwrap_or(MAX_LIGHT),
+            light_at(blocks, LightChannel::Block, self.x, y, self.z).unwrap_or(0),
+        );
+        if light != self.light {
+            self.light = light;
+            self.update_model();
+        }
+    }
+    /**Moves the block down and returns the height of the cell in which it landed, if it did*/
+    fn fall(&mut self, blocks: &WorldBlocks, delta: f32) -> Option<i32> {
+        fall(blocks, self.x, self.z, &mut self.y, &mut self.velocity, delta)
+    }
+    fn gl_draw(&self, chunk_location_uniform: UniformVec3fv, shader: &Program) {
+        shader.set_uniform_vec3fv(chunk_location_uniform, self.position().as_slice());
+        self.model.draw_instanced_triangles(0, /*one quad=2 triangles=6 vertices*/6, self.model.ibo().len());
+    }
+}
+
+/**Moves a block in column (x,z) down by one step and returns the height of the cell in which it landed, if it did.
+A block whose column is not loaded hangs in the air until the chunk comes back, instead of landing on the missing chunk*/
+fn fall(blocks: &WorldBlocks, x: i32, z: i32, y: &mut f32, velocity: &mut f32, delta: f32) -> Option<i32> {
+    if !blocks.is_block_loaded(x, 0, z) {
+        return None;
+    }
+    *velocity = (*velocity + GRAVITY * delta).min(TERMINAL_VELOCITY);
+    let new_y = *y - *velocity * delta;
+    // every cell entered during this step is checked, so that fast blocks can't tunnel through thin floors
+    let mut cell_y = y.floor() as i32 - 1;
+    while cell_y >= new_y.floor() as i32 {
+        if blocks.contains_solid_block_in(x, cell_y, z, x + 1, cell_y + 1, z + 1) {
+            return Some(cell_y + 1);
+        }
+        cell_y -= 1;
+    }
+    *y = new_y;
+    None
+}
+
+pub struct FallingBlocks {
+    falling: Vec<FallingBlock>,
+}
+
+impl FallingBlocks {
+    pub fn new() -> Self {
+        Self { falling: Vec::new() }
+    }
+    pub fn len(&self) -> usize {
+        self.falling.len()
+    }
+    pub fn iter(&self) -> std::slice::Iter<'_, FallingBlock> {
+        self.falling.iter()
+    }
+    /**Block at (x,y,z) should start falling if it has gravity and there is nothing solid below it.
+    Blocks at the bottom of the world and above chunks that are not loaded are supported*/
+    pub fn is_unsupported(blocks: &WorldBlocks, x: i32, y: i32, z: i32) -> bool {
+        match blocks.try_get_block(x, y, z) {
+            Some(block) if block.has_gravity() => !blocks.contains_solid_block_in(x, y - 1, z, x + 1, y, z + 1),
+            _ => false,
+        }
+    }
+    /**Cell in which a block that landed at height y is placed. If the cell got occupied while the block was falling,
+    the block lands on top of whatever is there. If that stacks it past the top of the world,
+    it takes the highest free cell of the column instead. Returns None if the column is not loaded or has no free cell*/
+    pub fn landing_cell(blocks: &WorldBlocks, x: i32, y: i32, z: i32) -> Option<i32> {
+        let is_free = |y: i32| blocks.try_get_block(x, y, z).map(|b| !b.is_solid()).unwrap_or(false);
+        (y.max(0)..CHUNK_HEIGHT as i32).find(|&y| is_free(y))
+            .or_else(|| (0..y.min(CHUNK_HEIGHT as i32)).rev().find(|&y| is_free(y)))
+    }
+    pub fn spawn(&mut self, block: Block, x: i32, y: i32, z: i32, gl: &gl::Gl) {
+        self.falling.push(FallingBlock::new(block, x, y, z, gl))
+    }
+    /**Simulates all falling blocks for delta seconds and returns those that landed together with the cell they landed in*/
+    pub fn update(&mut self, blocks: &WorldBlocks, delta: f32) -> Vec<(i32, i32, i32, Block)> {
+        let mut landed = Vec::new();
+        let mut i = 0;
+        while i < self.falling.len() {
+            let falling = &mut self.falling[i];
+            if let Some(y) = falling.fall(blocks, delta) {
+                landed.push((falling.x, y, falling.z, falling.block));
+                self.falling.swap_remove(i);
+            } else {
+                falling.update_light(blocks);
+                i += 1;
+            }
+        }
+        landed
+    }
+    pub fn gl_draw(&self, chunk_location_uniform: UniformVec3fv, shader: &Program) {
+        for falling in self.falling.iter() {
+            falling.gl_draw(chunk_location_uniform, shader);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::block_properties::{STONE, SAND};
+    use crate::blocks::block_registry::init_for_tests;
+    use crate::blocks::chunk_blocks::ChunkBlocks;
+
+    /**Single chunk at the origin filled with stone up to (excluding) y=10*/
+    fn ground() -> WorldBlocks {
+        init_for_tests();
+        let mut blocks = WorldBlocks::new();
+        let mut chunk = ChunkBlocks::new();
+        chunk.fill_level(0, 10, STONE);
+        blocks.insert_chunk((0, 0), chunk);
+        blocks
+    }
+
+    /**Simulates a block falling in column (x,z) from height y until it lands, calling on_step after every step*/
+    fn drop_from(blocks: &mut WorldBlocks, x: i32, y: i32, z: i32, mut on_step: impl FnMut(&mut WorldBlocks, f32)) -> Option<i32> {
+        let (mut y, mut velocity) = (y as f32, 0.);
+        for _ in 0..1000 {
+            if let Some(landed) = fall(blocks, x, z, &mut y, &mut velocity, 0.02) {
+                return Some(landed);
+            }
+            on_step(blocks, y);
+        }
+        None
+    }
+
+    #[test]
+    fn lands_on_top_of_the_ground() {
+        let mut blocks = ground();
+        assert_eq!(drop_from(&mut blocks, 3, 40, 3, |_, _| {}), Some(10));
+        assert_eq!(FallingBlocks::landing_cell(&blocks, 3, 10, 3), Some(10));
+    }
+
+    #[test]
+    fn fast_blocks_do_not_tunnel_through_thin_floors() {
+        let mut blocks = ground();
+        blocks.no_update_set_block(3, 30, 3, STONE);
+        let (mut y, mut velocity) = (60., TERMINAL_VELOCITY);
+        let mut landed = None;
+        while landed.is_none() {
+            landed = fall(&blocks, 3, 3, &mut y, &mut velocity, 0.5);
+        }
+        assert_eq!(landed, Some(31));
+    }
+
+    #[test]
+    fn lands_on_a_block_placed_mid_fall() {
+        let mut blocks = ground();
+        let mut placed = false;
+        let landed = drop_from(&mut blocks, 3, 40, 3, |blocks, y| {
+            // the player puts a block right under the falling one, into the cell it is about to land in
+            if !placed && y < 10.5 {
+                blocks.no_update_set_block(3, 10, 3, STONE);
+                placed = true;
+            }
+        }).unwrap();
+        assert!(placed);
+        assert_eq!(FallingBlocks::landing_cell(&blocks, 3, landed, 3), Some(11));
+        // the block placed in the landing cell after the last step stacks the landed block on top of it too
+        assert_eq!(FallingBlocks::landing_cell(&blocks, 3, 10, 3), Some(11));
+    }
+
+    #[test]
+    fn landing_at_the_world_top_takes_the_highest_free_cell() {
+        let mut blocks = ground();
+        let top = CHUNK_HEIGHT as i32 - 1;
+        blocks.no_update_fill(3, 20, 3, 1, CHUNK_HEIGHT - 20, 1, STONE);
+        assert_eq!(FallingBlocks::landing_cell(&blocks, 3, top, 3), Some(19));
+        blocks.no_update_set_block(3, 15, 3, SAND);
+        assert_eq!(FallingBlocks::landing_cell(&blocks, 3, 20, 3), Some(19));
+        blocks.no_update_fill(3, 10, 3, 1, 10, 1, STONE);
+        assert_eq!(FallingBlocks::landing_cell(&blocks, 3, top, 3), None);
+    }
+
+    #[test]
+    fn blocks_wait_above_unloaded_chunks() {
+        let mut blocks = ground();
+        let (mut y, mut velocity) = (40., 0.);
+        for _ in 0..100 {
+            assert_eq!(fall(&blocks, 100, 3, &mut y, &mut velocity, 0.1), None);
+        }
+        assert_eq!(y, 40.);
+        assert_eq!(FallingBlocks::landing_cell(&blocks, 100, 40, 3), None);
+    }
+}
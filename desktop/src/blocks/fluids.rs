@@ -0,0 +1,308 @@
+use crate::blocks::block::Block;
+use crate::blocks::block_properties::{WATER, LAVA, COBBLESTONE, OBSIDIAN};
+use crate::blocks::face_orientation::FaceOrientation;
+use crate::blocks::world_blocks::WorldBlocks;
+
+/**Fluid level of blocks falling down. Level 0 is a source and levels in between are flowing blocks
+whose number is their horizontal distance from the closest source*/
+pub const FALLING: u8 = 8;
+
+const HORIZONTAL: [FaceOrientation; 4] = [FaceOrientation::XPlus, FaceOrientation::XMinus, FaceOrientation::ZPlus, FaceOrientation::ZMinus];
+
+#[derive(Clone)]
+pub struct FluidKind {
+    pub block: Block,
+    /**How far (in blocks) the fluid spreads sideways from a source*/
+    pub max_distance: u8,
+    /**Number of ticks between a change in the neighbourhood and the reaction of the fluid*/
+    pub delay: u64,
+    /**Flowing blocks between two sources become a source themselves*/
+    pub renewable: bool,
+}
+
+pub const DEFAULT_FLUIDS: [FluidKind; 2] = [
+    FluidKind { block: WATER, max_distance: 7, delay: 5, renewable: true },
+    FluidKind { block: LAVA, max_distance: 3, delay: 30, renewable: false },
+];
+
+/**Cellular simulation of water and lava. Fluid blocks are only simulated when they receive a scheduled tick
+after something changes in their neighbourhood, so that still lakes and oceans cost nothing. The simulation does not
+modify the world by itself, but returns changes that the World applies through its regular editing API.*/
+pub struct Fluids {
+    kinds: Vec<FluidKind>,
+}
+
+impl Fluids {
+    pub fn new() -> Self {
+        Self::with_kinds(DEFAULT_FLUIDS.to_vec())
+    }
+    pub fn with_kinds(kinds: Vec<FluidKind>) -> Self {
+        Self { kinds }
+    }
+    pub fn kind_of(&self, block: Block) -> Option<&FluidKind> {
+        self.kinds.iter().find(|kind| kind.block.is_same_kind(block))
+    }
+    pub fn is_fluid(&self, block: Block) -> bool {
+        self.kind_of(block).is_some()
+    }
+    /**Returns blocks that should change when the fluid at (x,y,z) receives its tick*/
+    pub fn tick(&self, blocks: &WorldBlocks, x: i32, y: i32, z: i32) -> Vec<(i32, i32, i32, Block)> {
+        let mut changes = Vec::new();
+        self.flow(blocks, (x, y, z), &mut changes);
+        changes
+    }
+    fn flow(&self, blocks: &WorldBlocks, (x, y, z): (i32, i32, i32), changes: &mut Vec<(i32, i32, i32, Block)>) {
+        let block = match blocks.try_get_block(x, y, z) {
+            Some(&block) => block,
+            None => return
+        };
+        let kind = match self.kind_of(block) {
+            Some(kind) => kind,
+            None => return
+        };
+        let level = block.state().fluid_level();
+        if let Some(solidified) = self.solidify(blocks, kind, level, x, y, z) {
+            changes.push((x, y, z, solidified));
+            return;
+        }
+        if level != 0 {
+            match self.expected_level(blocks, kind, x, y, z) {
+                None => {
+                    changes.push((x, y, z, Block::air()));
+                    return;
+                }
+                Some(expected) if expected != level => {
+                    // neighbours will be scheduled again once this change is applied
+                    changes.push((x, y, z, fluid(kind, expected)));
+                    return;
+                }
+                _ => {}
+            }
+        }
+        if self.can_flow_into(blocks, kind, x, y - 1, z, FALLING) {
+            changes.push((x, y - 1, z, fluid(kind, FALLING)));
+        } else if self.spreads_sideways(blocks, kind, x, y, z) {
+            let spread = horizontal_distance(level) + 1;
+            if spread <= kind.max_distance {
+                for ort in HORIZONTAL.iter() {
+                    let (dx, _, dz) = ort.offset();
+                    if self.can_flow_into(blocks, kind, x + dx, y, z + dz, spread) {
+                        changes.push((x + dx, y, z + dz, fluid(kind, spread)));
+                    }
+                }
+            }
+        }
+    }
+    /**Lava touching water turns into obsidian if it is a source and into cobblestone otherwise*/
+    fn solidify(&self, blocks: &WorldBlocks, kind: &FluidKind, level: u8, x: i32, y: i32, z: i32) -> Option<Block> {
+        if !kind.block.is_same_kind(LAVA) {
+            return None;
+        }
+        let touches_water = FaceOrientation::ALL.iter()
+            .filter(|&&ort| ort != FaceOrientation::YMinus)
+            .any(|ort| {
+                let (dx, dy, dz) = ort.offset();
+                blocks.try_get_block(x + dx, y + dy, z + dz).map(|b| b.is_same_kind(WATER)).unwrap_or(false)
+            });
+        if touches_water {
+            Some(if level == 0 { OBSIDIAN } else { COBBLESTONE })
+        } else {
+            None
+        }
+    }
+    /**Level that a flowing block should have given its neighbours, or None if it should dry out*/
+    fn expected_level(&self, blocks: &WorldBlocks, kind: &FluidKind, x: i32, y: i32, z: i32) -> Option<u8> {
+        if self.fluid_level_at(blocks, kind, x, y + 1, z).is_some() {
+            return Some(FALLING);
+        }
+        let mut sources = 0;
+        let mut closest = None;
+        for ort in HORIZONTAL.iter() {
+            let (dx, _, dz) = ort.offset();
+            let (nx, nz) = (x + dx, z + dz);
+            if let Some(level) = self.fluid_level_at(blocks, kind, nx, y, nz) {
+                if level == 0 {
+                    sources += 1;
+                }
+                if self.spreads_sideways(blocks, kind, nx, y, nz) {
+                    let distance = horizontal_distance(level) + 1;
+                    closest = Some(closest.map_or(distance, |c: u8| c.min(distance)));
+                }
+            }
+        }
+        if kind.renewable && sources >= 2 && !self.is_flowing_or_air(blocks, kind, x, y - 1, z) {
+            return Some(0);
+        }
+        closest.filter(|&distance| distance <= kind.max_distance)
+    }
+    fn fluid_level_at(&self, blocks: &WorldBlocks, kind: &FluidKind, x: i32, y: i32, z: i32) -> Option<u8> {
+        blocks.try_get_block(x, y, z)
+            .filter(|b| b.is_same_kind(kind.block))
+            .map(|b| b.state().fluid_level())
+    }
+    /**Fluid spreads sideways only once it lands on something it can't fall through*/
+    fn spreads_sideways(&self, blocks: &WorldBlocks, kind: &FluidKind, x: i32, y: i32, z: i32) -> bool {
+        match blocks.try_get_block(x, y - 1, z) {
+            Some(below) => !below.is_air() && !below.is_same_kind(kind.block),
+            None => true,
+        }
+    }
+    fn is_flowing_or_air(&self, blocks: &WorldBlocks, kind: &FluidKind, x: i32, y: i32, z: i32) -> bool {
+        match blocks.try_get_block(x, y, z) {
+            Some(b) => b.is_air() || (b.is_same_kind(kind.block) && b.state().fluid_level() != 0),
+            None => false,
+        }
+    }
+    /**Fluid can flow into air and into its own flowing blocks that are further away from a source*/
+    fn can_flow_into(&self, blocks: &WorldBlocks, kind: &FluidKind, x: i32, y: i32, z: i32, level: u8) -> bool {
+        match blocks.try_get_block(x, y, z) {
+            Some(b) if b.is_air() => true,
+            Some(b) if b.is_same_kind(kind.block) => {
+                let current = b.state().fluid_level();
+                current != 0 && current != FALLING && (level == FALLING || current > level)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn fluid(kind: &FluidKind, level: u8) -> Block {
+    kind.block.with_state(kind.block.state().with_fluid_level(level))
+}
+
+/**Falling blocks spread sideways as if they were sources*/
+fn horizontal_distance(level: u8) -> u8 {
+    if level == FALLING { 0 } else { level }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::block_properties::STONE;
+    use crate::blocks::block_registry::init_for_tests;
+    use crate::blocks::chunk_blocks::ChunkBlocks;
+
+    /**Single chunk at the origin filled with stone up to (excluding) y=10*/
+    fn ground() -> WorldBlocks {
+        init_for_tests();
+        let mut blocks = WorldBlocks::new();
+        let mut chunk = ChunkBlocks::new();
+        chunk.fill_level(0, 10, STONE);
+        blocks.insert_chunk((0, 0), chunk);
+        blocks
+    }
+
+    fn level(block: Block, level: u8) -> Block {
+        block.with_state(block.state().with_fluid_level(level))
+    }
+
+    /**Ticks every block in the lower part of the chunk and applies the changes until nothing changes any more*/
+    fn settle(fluids: &Fluids, blocks: &mut WorldBlocks) {
+        for _ in 0..100 {
+            let mut changed = false;
+            for x in 0..16 {
+                for z in 0..16 {
+                    for y in 0..20 {
+                        for (cx, cy, cz, block) in fluids.tick(blocks, x, y, z) {
+                            if *blocks.get_block(cx, cy, cz) != block {
+                                blocks.no_update_set_block(cx, cy, cz, block);
+                                changed = true;
+                            }
+                        }
+                    }
+                }
+            }
+            if !changed {
+                return;
+            }
+        }
+        panic!("Fluids did not settle");
+    }
+
+    #[test]
+    fn water_falls_and_then_spreads_up_to_max_distance() {
+        let fluids = Fluids::new();
+        let mut blocks = ground();
+        blocks.no_update_set_block(4, 12, 4, WATER);
+        settle(&fluids, &mut blocks);
+        assert_eq!(*blocks.get_block(4, 12, 4), WATER);
+        assert_eq!(*blocks.get_block(4, 11, 4), level(WATER, FALLING));
+        assert_eq!(*blocks.get_block(4, 10, 4), level(WATER, FALLING));
+        // nothing spreads sideways while falling
+        assert!(blocks.get_block(5, 11, 4).is_air());
+        for distance in 1..=7 {
+            assert_eq!(*blocks.get_block(4 + distance, 10, 4), level(WATER, distance as u8));
+            assert_eq!(*blocks.get_block(4, 10, 4 + distance), level(WATER, distance as u8));
+        }
+        assert!(blocks.get_block(12, 10, 4).is_air());
+        assert_eq!(*blocks.get_block(6, 10, 6), level(WATER, 4));
+    }
+
+    #[test]
+    fn lava_spreads_less_than_water() {
+        let fluids = Fluids::new();
+        let mut blocks = ground();
+        blocks.no_update_set_block(8, 10, 8, LAVA);
+        settle(&fluids, &mut blocks);
+        assert_eq!(*blocks.get_block(11, 10, 8), level(LAVA, 3));
+        assert!(blocks.get_block(12, 10, 8).is_air());
+    }
+
+    #[test]
+    fn flowing_water_dries_out_without_source() {
+        let fluids = Fluids::new();
+        let mut blocks = ground();
+        blocks.no_update_set_block(4, 12, 4, WATER);
+        settle(&fluids, &mut blocks);
+        blocks.no_update_set_block(4, 12, 4, Block::air());
+        settle(&fluids, &mut blocks);
+        for x in 0..16 {
+            for z in 0..16 {
+                for y in 10..13 {
+                    assert!(blocks.get_block(x, y, z).is_air(), "water left at {},{},{}", x, y, z);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn water_between_two_sources_becomes_a_source() {
+        let fluids = Fluids::new();
+        let mut blocks = ground();
+        blocks.no_update_set_block(3, 10, 4, WATER);
+        blocks.no_update_set_block(5, 10, 4, WATER);
+        settle(&fluids, &mut blocks);
+        assert_eq!(*blocks.get_block(4, 10, 4), WATER);
+        // the new source keeps the water around even after one of the original sources is gone
+        blocks.no_update_set_block(3, 10, 4, Block::air());
+        settle(&fluids, &mut blocks);
+        assert_eq!(*blocks.get_block(4, 10, 4), WATER);
+        assert_eq!(*blocks.get_block(3, 10, 4), level(WATER, 1));
+    }
+
+    #[test]
+    fn lava_is_not_renewable() {
+        let fluids = Fluids::new();
+        let mut blocks = ground();
+        blocks.no_update_set_block(3, 10, 4, LAVA);
+        blocks.no_update_set_block(5, 10, 4, LAVA);
+        settle(&fluids, &mut blocks);
+        assert_eq!(*blocks.get_block(4, 10, 4), level(LAVA, 1));
+    }
+
+    #[test]
+    fn lava_touching_water_solidifies() {
+        let fluids = Fluids::new();
+        let mut blocks = ground();
+        blocks.no_update_set_block(4, 10, 4, LAVA);
+        blocks.no_update_set_block(5, 10, 4, WATER);
+        assert_eq!(fluids.tick(&blocks, 4, 10, 4), vec![(4, 10, 4, OBSIDIAN)]);
+        blocks.no_update_set_block(4, 10, 4, level(LAVA, 2));
+        assert_eq!(fluids.tick(&blocks, 4, 10, 4), vec![(4, 10, 4, COBBLESTONE)]);
+        // water below lava does not count
+        blocks.no_update_set_block(5, 10, 4, STONE);
+        blocks.no_update_set_block(4, 11, 4, LAVA);
+        blocks.no_update_set_block(4, 10, 4, WATER);
+        assert!(fluids.tick(&blocks, 4, 11, 4).iter().all(|&(_, _, _, b)| b.is_same_kind(LAVA)));
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use crate::blocks::face::{Face, FaceShape};
+use crate::blocks::face_orientation::FaceOrientation;
+use crate::blocks::chunk_coords::{CHUNK_WIDTH, CHUNK_HEIGHT, CHUNK_DEPTH, SECTION_HEIGHT};
+
+/**Maximal size of a merged quad along either axis (limited by the 4 bits available in Face)*/
+pub const MAX_QUAD_SIZE: usize = 16;
+/**Ambient occlusion value of a face whose corners are not occluded at all*/
+pub(crate) const NO_OCCLUSION: u8 = 0xFF;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum MeshingMode {
+    /**One quad per visible block face. Faces can be updated incrementally when a block changes.*/
+    PerFace,
+    /**Coplanar faces with the same texture and shading are merged into larger quads with tiled textures.
+    Requires far fewer vertices, but the whole chunk mesh is rebuilt whenever a block changes.*/
+    Greedy,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MeshStats {
+    /**Number of quads across all loaded chunks*/
+    pub instances: usize,
+    /**Number of vertices drawn per frame (each quad takes 6)*/
+    pub vertices: usize,
+    /**Time it took to build faces of all chunks the last time they were computed from scratch*/
+    pub rebuild_time: Duration,
+}
+
+/**Indices of the first tangent axis, second tangent axis and the normal axis. Tangent axes match those used for ambient occlusion*/
+fn axes(ort: FaceOrientation) -> (usize, usize, usize) {
+    match ort {
+        FaceOrientation::YPlus | FaceOrientation::YMinus => (0, 2, 1),
+        FaceOrientation::XPlus | FaceOrientation::XMinus => (1, 2, 0),
+        FaceOrientation::ZPlus | FaceOrientation::ZMinus => (0, 1, 2),
+    }
+}
+
+/**Number of blocks the face spans along the Y axis*/
+pub(crate) fn vertical_extent(face: &Face) -> usize {
+    let (u_axis, v_axis, _) = axes(face.block_orientation());
+    let (width, height) = face.size();
+    if u_axis == 1 { width } else if v_axis == 1 { height } else { 1 }
+}
+
+/**Centre of the quad in chunk coordinates. Merged faces extend from their block along both tangent axes*/
+pub(crate) fn face_centre(face: &Face) -> glm::Vec3 {
+    let ort = face.block_orientation();
+    let (u_axis, v_axis, _) = axes(ort);
+    let (width, height) = face.size();
+    let (dx, dy, dz) = ort.offset();
+    let mut centre = glm::vec3(face.block_x() as f32 + 0.5, face.block_y() as f32 + 0.5, face.block_z() as f32 + 0.5);
+    centre[u_axis] += (width - 1) as f32 / 2.;
+    centre[v_axis] += (height - 1) as f32 / 2.;
+    centre + glm::vec3(dx as f32, dy as f32, dz as f32) * 0.5
+}
+
+const CHUNK_SIZE: [usize; 3] = [CHUNK_WIDTH, CHUNK_HEIGHT, CHUNK_DEPTH];
+
+/**Faces of slabs are never merged, because the shader squeezes the whole quad into half of a block*/
+fn can_merge(a: &Face, b: &Face) -> bool {
+    a.shape() == FaceShape::Full && b.shape() == FaceShape::Full && a.texture_id() == b.texture_id() && a.light() == b.light() && a.ambient_occlusion() == NO_OCCLUSION && b.ambient_occlusion() == NO_OCCLUSION
+}
+
+/**Greedily merges 1x1 faces of a chunk into rectangles. Each layer of faces sharing orientation
+is swept row by row, growing every quad first along the row and then along the column.*/
+pub fn merge_faces(faces: &[Face]) -> Vec<Face> {
+    let mut layers: HashMap<(u8, usize), Vec<Face>> = HashMap::new();
+    for face in faces {
+        assert_eq!(face.size(), (1, 1));
+        let ort = face.block_orientation();
+        let coords = [face.block_x(), face.block_y(), face.block_z()];
+        layers.entry((ort as u8, coords[axes(ort).2])).or_default().push(*face);
+    }
+    let mut merged = Vec::with_capacity(faces.len());
+    for (_, layer) in layers {
+        let ort = layer[0].block_orientation();
+        let (u_axis, v_axis, _) = axes(ort);
+        let (u_size, v_size) = (CHUNK_SIZE[u_axis], CHUNK_SIZE[v_axis]);
+        let mut grid: Vec<Option<Face>> = vec![None; u_size * v_size];
+        for face in layer {
+            let coords = [face.block_x(), face.block_y(), face.block_z()];
+            grid[coords[u_axis] + coords[v_axis] * u_size] = Some(face);
+        }
+        for v in 0..v_size {
+            for u in 0..u_size {
+                let mut face = match grid[u + v * u_size].take() {
+                    Some(face) => face,
+                    None => continue
+                };
+                let mergeable = |cell: &Option<Face>| cell.as_ref().map(|other| can_merge(&face, other)).unwrap_or(false);
+                // quads must not cross boundaries of sections, because sections are drawn (or skipped) one at a time
+                let within_section = |axis: usize, from: usize, to: usize| axis != 1 || from / SECTION_HEIGHT == to / SECTION_HEIGHT;
+                let mut width = 1;
+                while width < MAX_QUAD_SIZE && u + width < u_size && within_section(u_axis, u, u + width) && mergeable(&grid[u + width + v * u_size]) {
+                    width += 1;
+                }
+                let mut height = 1;
+                while height < MAX_QUAD_SIZE && v + height < v_size && within_section(v_axis, v, v + height) &&
+                    (u..u + width).all(|cu| mergeable(&grid[cu + (v + height) * u_size])) {
+                    height += 1;
+                }
+                for cv in v..v + height {
+                    for cu in u..u + width {
+                        grid[cu + cv * u_size] = None;
+                    }
+                }
+                face.set_size(width, height);
+                merged.push(face);
+            }
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn face(x: usize, y: usize, z: usize, ort: FaceOrientation, texture_id: u32, ambient_occlusion: u8) -> Face {
+        Face::from_coords_and_ort(x as u8, y as u8, z as u8, ort, texture_id, (15, 0, ambient_occlusion))
+    }
+
+    fn area(faces: &[Face]) -> usize {
+        faces.iter().map(|f| f.size().0 * f.size().1).sum()
+    }
+
+    #[test]
+    fn rectangle_becomes_single_quad() {
+        let faces: Vec<Face> = (0..3).flat_map(|x| (0..2).map(move |z| face(x, 4, z, FaceOrientation::YPlus, 1, NO_OCCLUSION))).collect();
+        let merged = merge_faces(&faces);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].size(), (3, 2));
+        assert_eq!((merged[0].block_x(), merged[0].block_y(), merged[0].block_z()), (0, 4, 0));
+        assert_eq!(face_centre(&merged[0]), glm::vec3(1.5, 5., 1.));
+    }
+
+    #[test]
+    fn different_textures_and_occluded_faces_are_not_merged() {
+        let faces = vec![
+            face(0, 0, 0, FaceOrientation::YPlus, 1, NO_OCCLUSION),
+            face(1, 0, 0, FaceOrientation::YPlus, 2, NO_OCCLUSION),
+            face(2, 0, 0, FaceOrientation::YPlus, 2, 0x7F),
+            face(3, 0, 0, FaceOrientation::YPlus, 2, 0x7F),
+            // same position, but facing the other way
+            face(0, 0, 0, FaceOrientation::YMinus, 1, NO_OCCLUSION),
+        ];
+        let merged = merge_faces(&faces);
+        assert_eq!(merged.len(), faces.len());
+        assert!(merged.iter().all(|f| f.size() == (1, 1)));
+    }
+
+    #[test]
+    fn quads_stop_at_section_boundaries_and_max_size() {
+        let faces: Vec<Face> = (SECTION_HEIGHT - 2..SECTION_HEIGHT + 2).map(|y| face(0, y, 0, FaceOrientation::XPlus, 1, NO_OCCLUSION)).collect();
+        let merged = merge_faces(&faces);
+        assert_eq!(merged.len(), 2);
+        assert!(merged.iter().all(|f| vertical_extent(f) == 2));
+        let row: Vec<Face> = (0..CHUNK_WIDTH).map(|x| face(x, 0, 0, FaceOrientation::ZMinus, 1, NO_OCCLUSION)).collect();
+        let merged = merge_faces(&row);
+        assert_eq!(area(&merged), CHUNK_WIDTH);
+        assert!(merged.iter().all(|f| f.size().0 <= MAX_QUAD_SIZE));
+    }
+}
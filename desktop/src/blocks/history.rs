@@ -0,0 +1,179 @@
+use std::collections::VecDeque;
+use crate::blocks::block::Block;
+
+/**Default limit on the number of edits remembered by History, across all transactions*/
+pub const MAX_EDITS: usize = 1 << 16;
+/**Default limit on the number of transactions that can be undone*/
+pub const MAX_TRANSACTIONS: usize = 256;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Edit {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub old: Block,
+    pub new: Block,
+}
+
+/**Edits that are undone and redone together, such as all blocks of a placed schematic*/
+#[derive(Clone, Debug, Default)]
+pub struct Transaction {
+    edits: Vec<Edit>,
+}
+
+impl Transaction {
+    pub fn edits(&self) -> &[Edit] {
+        &self.edits
+    }
+    pub fn len(&self) -> usize {
+        self.edits.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+}
+
+/**Journal of edits made by the player. Only edits made while a transaction is open are recorded,
+so that changes made by the simulation (fluids, falling blocks, explosions) are not undone.
+Once the limits are exceeded, the oldest transactions are forgotten.*/
+pub struct History {
+    undo: VecDeque<Transaction>,
+    redo: Vec<Transaction>,
+    /**Transaction being recorded. Nested begins are merged into the outermost transaction*/
+    current: Option<Transaction>,
+    depth: usize,
+    /**Sum of lengths of all transactions in undo and redo*/
+    total_edits: usize,
+    max_edits: usize,
+    max_transactions: usize,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::with_limits(MAX_EDITS, MAX_TRANSACTIONS)
+    }
+    pub fn with_limits(max_edits: usize, max_transactions: usize) -> Self {
+        Self { undo: VecDeque::new(), redo: Vec::new(), current: None, depth: 0, total_edits: 0, max_edits, max_transactions }
+    }
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+    pub fn is_recording(&self) -> bool {
+        self.current.is_some()
+    }
+    pub fn begin(&mut self) {
+        if self.depth == 0 {
+            self.current = Some(Transaction::default());
+        }
+        self.depth += 1;
+    }
+    /**Closes the transaction opened by the matching begin. Empty transactions are dropped.
+    Committing anything makes the edits that were undone impossible to redo*/
+    pub fn commit(&mut self) {
+        assert!(self.depth > 0, "commit without begin");
+        self.depth -= 1;
+        if self.depth > 0 {
+            return;
+        }
+        let transaction = self.current.take().unwrap();
+        if transaction.is_empty() {
+            return;
+        }
+        for redo in self.redo.drain(..) {
+            self.total_edits -= redo.len();
+        }
+        self.total_edits += transaction.len();
+        self.undo.push_back(transaction);
+        self.enforce_limits();
+    }
+    /**Does nothing unless a transaction is open*/
+    pub fn record(&mut self, x: i32, y: i32, z: i32, old: Block, new: Block) {
+        if let Some(transaction) = &mut self.current {
+            transaction.edits.push(Edit { x, y, z, old, new });
+        }
+    }
+    fn enforce_limits(&mut self) {
+        while self.undo.len() > 1 && (self.total_edits > self.max_edits || self.undo.len() > self.max_transactions) {
+            let oldest = self.undo.pop_front().unwrap();
+            self.total_edits -= oldest.len();
+        }
+    }
+    /**Moves the last transaction onto the redo stack. The caller is responsible for reverting its edits*/
+    pub fn pop_undo(&mut self) -> Option<&Transaction> {
+        let transaction = self.undo.pop_back()?;
+        self.redo.push(transaction);
+        self.redo.last()
+    }
+    /**Moves the last undone transaction back onto the undo stack. The caller is responsible for applying its edits again*/
+    pub fn pop_redo(&mut self) -> Option<&Transaction> {
+        let transaction = self.redo.pop()?;
+        self.undo.push_back(transaction);
+        self.undo.back()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transaction(history: &mut History, edits: usize) {
+        history.begin();
+        for i in 0..edits {
+            history.record(i as i32, 0, 0, Block::air(), Block::new(1));
+        }
+        history.commit();
+    }
+
+    #[test]
+    fn nested_transactions_are_merged() {
+        let mut history = History::new();
+        history.record(0, 0, 0, Block::air(), Block::new(1));
+        assert_eq!(history.undo_len(), 0);
+        history.begin();
+        history.record(0, 0, 0, Block::air(), Block::new(1));
+        history.begin();
+        history.record(1, 0, 0, Block::air(), Block::new(1));
+        history.commit();
+        assert!(history.is_recording());
+        history.commit();
+        assert_eq!(history.undo_len(), 1);
+        assert_eq!(history.pop_undo().unwrap().len(), 2);
+        transaction(&mut history, 0);
+        assert_eq!(history.redo_len(), 1);
+    }
+
+    #[test]
+    fn undo_and_redo_move_transactions_between_stacks() {
+        let mut history = History::new();
+        transaction(&mut history, 1);
+        transaction(&mut history, 2);
+        assert_eq!(history.pop_undo().unwrap().len(), 2);
+        assert_eq!(history.pop_undo().unwrap().len(), 1);
+        assert!(history.pop_undo().is_none());
+        assert_eq!(history.pop_redo().unwrap().len(), 1);
+        assert_eq!((history.undo_len(), history.redo_len()), (1, 1));
+        // a new edit makes the undone one impossible to redo
+        transaction(&mut history, 3);
+        assert_eq!(history.redo_len(), 0);
+        assert_eq!(history.total_edits, 4);
+    }
+
+    #[test]
+    fn oldest_transactions_are_forgotten_over_the_limits() {
+        let mut history = History::with_limits(10, 3);
+        for _ in 0..5 {
+            transaction(&mut history, 1);
+        }
+        assert_eq!(history.undo_len(), 3);
+        transaction(&mut history, 9);
+        assert_eq!(history.undo_len(), 2);
+        assert_eq!(history.total_edits, 10);
+        // a single transaction over the limit is still kept
+        transaction(&mut history, 20);
+        assert_eq!(history.undo_len(), 1);
+        assert_eq!(history.total_edits, 20);
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::{HashSet, VecDeque};
+use crate::blocks::world_blocks::WorldBlocks;
+use crate::blocks::chunk_coords::{ChunkPos, CHUNK_WIDTH, CHUNK_DEPTH, CHUNK_HEIGHT, for_each_neighbour, block_pos_into_chunk_pos, chunk_pos_into_block_pos, absolute_block_to_chunk_block_position, is_height_in_bounds, for_each_neighbouring_chunk};
+use crate::blocks::face_orientation::FaceOrientation;
+
+pub const MAX_LIGHT: u8 = 15;
+
+/**Sky light comes from above and travels down without any loss through blocks that do not filter light.
+Block light is emitted by blocks such as lava. Both spread to neighbours losing at least one level per block.*/
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum LightChannel {
+    Sky,
+    Block,
+}
+
+const CHANNELS: [LightChannel; 2] = [LightChannel::Sky, LightChannel::Block];
+
+/**Level of light arriving at a block with the given light filter, coming from a neighbour lit with the given level*/
+fn attenuate(channel: LightChannel, level: u8, filter: u8, downwards: bool) -> u8 {
+    if filter >= MAX_LIGHT {
+        0
+    } else if channel == LightChannel::Sky && downwards && level == MAX_LIGHT && filter == 0 {
+        MAX_LIGHT
+    } else {
+        level.saturating_sub(filter.max(1))
+    }
+}
+
+/**Returns None if the block lies outside of world height or in a chunk that is not loaded*/
+pub fn light_at(blocks: &WorldBlocks, channel: LightChannel, x: i32, y: i32, z: i32) -> Option<u8> {
+    if !is_height_in_bounds(y) {
+        return None;
+    }
+    let chunk = blocks.get_chunk(block_pos_into_chunk_pos(x, z))?;
+    let (x, y, z) = absolute_block_to_chunk_block_position(x, y, z);
+    let (x, y, z) = (x as usize, y as usize, z as usize);
+    Some(match channel {
+        LightChannel::Sky => chunk.sky_light(x, y, z),
+        LightChannel::Block => chunk.block_light(x, y, z),
+    })
+}
+
+fn set_light_at(blocks: &mut WorldBlocks, channel: LightChannel, x: i32, y: i32, z: i32, level: u8, changed: &mut HashSet<ChunkPos>) {
+    let chunk_pos = block_pos_into_chunk_pos(x, z);
+    let chunk = blocks.get_chunk_mut(chunk_pos).unwrap();
+    let (lx, ly, lz) = absolute_block_to_chunk_block_position(x, y, z);
+    let (lx, ly, lz) = (lx as usize, ly as usize, lz as usize);
+    match channel {
+        LightChannel::Sky => chunk.set_sky_light(lx, ly, lz, level),
+        LightChannel::Block => chunk.set_block_light(lx, ly, lz, level),
+    }
+    changed.insert(chunk_pos);
+    // Faces of blocks in the neighbouring chunk may look into this block
+    if lx == 0 { changed.insert((chunk_pos.0 - 1, chunk_pos.1)); }
+    if lx == CHUNK_WIDTH - 1 { changed.insert((chunk_pos.0 + 1, chunk_pos.1)); }
+    if lz == 0 { changed.insert((chunk_pos.0, chunk_pos.1 - 1)); }
+    if lz == CHUNK_DEPTH - 1 { changed.insert((chunk_pos.0, chunk_pos.1 + 1)); }
+}
+
+/**Light seen by a face, that is the light of the block in front of it. Returns (sky light, block light)*/
+pub fn face_light(blocks: &WorldBlocks, x: i32, y: i32, z: i32, ort: FaceOrientation) -> (u8, u8) {
+    let (dx, dy, dz) = ort.offset();
+    let (x, y, z) = (x + dx, y + dy, z + dz);
+    if y >= CHUNK_HEIGHT as i32 {
+        return (MAX_LIGHT, 0);
+    }
+    (light_at(blocks, LightChannel::Sky, x, y, z).unwrap_or(0), light_at(blocks, LightChannel::Block, x, y, z).unwrap_or(0))
+}
+
+/**Breadth-first flood fill spreading light from the queued blocks to their neighbours, as long as it makes them brighter*/
+fn propagate(blocks: &mut WorldBlocks, channel: LightChannel, queue: &mut VecDeque<(i32, i32, i32)>, changed: &mut HashSet<ChunkPos>) {
+    while let Some((x, y, z)) = queue.pop_front() {
+        let level = match light_at(blocks, channel, x, y, z) {
+            Some(level) if level > 1 => level,
+            _ => continue
+        };
+        for_each_neighbour(x, y, z, |nx, ny, nz, ort| {
+            let filter = match blocks.try_get_block(nx, ny, nz) {
+                Some(neighbour) => neighbour.light_filter(),
+                None => return
+            };
+            let new_level = attenuate(channel, level, filter, ort == FaceOrientation::YMinus);
+            if new_level > light_at(blocks, channel, nx, ny, nz).unwrap() {
+                set_light_at(blocks, channel, nx, ny, nz, new_level, changed);
+                queue.push_back((nx, ny, nz));
+            }
+        });
+    }
+}
+
+/**Breadth-first flood fill darkening all blocks that were lit by the queued blocks (with their previous levels).
+Blocks that turn out to be lit by some other source are queued for refilling.*/
+fn remove(blocks: &mut WorldBlocks, channel: LightChannel, removal: &mut VecDeque<(i32, i32, i32, u8)>, refill: &mut VecDeque<(i32, i32, i32)>, changed: &mut HashSet<ChunkPos>) {
+    while let Some((x, y, z, level)) = removal.pop_front() {
+        for_each_neighbour(x, y, z, |nx, ny, nz, ort| {
+            let neighbour_level = match light_at(blocks, channel, nx, ny, nz) {
+                Some(l) if l > 0 => l,
+                _ => return
+            };
+            let lit_from_above = channel == LightChannel::Sky && ort == FaceOrientation::YMinus && level == MAX_LIGHT && neighbour_level == MAX_LIGHT;
+            if neighbour_level < level || lit_from_above {
+                set_light_at(blocks, channel, nx, ny, nz, 0, changed);
+                removal.push_back((nx, ny, nz, neighbour_level));
+                let emission = blocks.get_block(nx, ny, nz).light_emission();
+                if channel == LightChannel::Block && emission > 0 {
+                    set_light_at(blocks, channel, nx, ny, nz, emission, changed);
+                    refill.push_back((nx, ny, nz));
+                }
+            } else {
+                refill.push_back((nx, ny, nz));
+            }
+        });
+    }
+}
+
+/**Incrementally recomputes light after the block at the given position has changed.
+Returns chunks, whose faces may see different light now.*/
+pub fn update_light_at(blocks: &mut WorldBlocks, x: i32, y: i32, z: i32) -> HashSet<ChunkPos> {
+    let mut changed = HashSet::new();
+    let block = *blocks.get_block(x, y, z);
+    for &channel in CHANNELS.iter() {
+        let mut removal = VecDeque::new();
+        let mut refill = VecDeque::new();
+        let old_level = light_at(blocks, channel, x, y, z).unwrap();
+        if old_level > 0 {
+            set_light_at(blocks, channel, x, y, z, 0, &mut changed);
+            removal.push_back((x, y, z, old_level));
+            remove(blocks, channel, &mut removal, &mut refill, &mut changed);
+        }
+        let own_level = match channel {
+            LightChannel::Block => block.light_emission(),
+            LightChannel::Sky if y == CHUNK_HEIGHT as i32 - 1 => attenuate(channel, MAX_LIGHT, block.light_filter(), true),
+            LightChannel::Sky => 0
+        };
+        if own_level > 0 {
+            set_light_at(blocks, channel, x, y, z, own_level, &mut changed);
+            refill.push_back((x, y, z));
+        }
+        // Neighbours might now shine into the changed block
+        for_each_neighbour(x, y, z, |nx, ny, nz, _| refill.push_back((nx, ny, nz)));
+        propagate(blocks, channel, &mut refill, &mut changed);
+    }
+    changed
+}
+
+/**Computes light of the whole chunk from scratch. Light flows in from loaded neighbours and out into them.
+Returns chunks, whose faces may see different light now.*/
+pub fn compute_chunk_light(blocks: &mut WorldBlocks, chunk_pos: ChunkPos) -> HashSet<ChunkPos> {
+    let mut changed = HashSet::new();
+    let mut sky_queue = VecDeque::new();
+    let mut block_queue = VecDeque::new();
+    let (from_x, from_z) = chunk_pos_into_block_pos(chunk_pos.0, chunk_pos.1);
+    // Lowest block of each column reached by unobstructed sky light
+    let mut open_sky = [[CHUNK_HEIGHT; CHUNK_DEPTH]; CHUNK_WIDTH];
+    {
+        let chunk = blocks.get_chunk_mut(chunk_pos).unwrap();
+        for x in 0..CHUNK_WIDTH {
+            for z in 0..CHUNK_DEPTH {
+                let mut level = MAX_LIGHT;
+                for y in (0..CHUNK_HEIGHT).rev() {
+                    let block = *chunk.get_block(x, y, z);
+                    level = attenuate(LightChannel::Sky, level, block.light_filter(), true);
+                    chunk.set_sky_light(x, y, z, level);
+                    if level == MAX_LIGHT {
+                        open_sky[x][z] = y;
+                    }
+                    chunk.set_block_light(x, y, z, block.light_emission());
+                    if block.light_emission() > 0 {
+                        block_queue.push_back((from_x + x as i32, y as i32, from_z + z as i32));
+                    }
+                }
+            }
+        }
+    }
+    {
+        let chunk = blocks.get_chunk(chunk_pos).unwrap();
+        for x in 0..CHUNK_WIDTH {
+            for z in 0..CHUNK_DEPTH {
+                // Sky light only needs to spread sideways where the neighbouring column is darker
+                let mut darker_neighbour_below = 0;
+                let mut consider = |nx: i32, nz: i32| {
+                    let top = if 0 <= nx && nx < CHUNK_WIDTH as i32 && 0 <= nz && nz < CHUNK_DEPTH as i32 {
+                        open_sky[nx as usize][nz as usize]
+                    } else {
+                        CHUNK_HEIGHT
+                    };
+                    darker_neighbour_below = darker_neighbour_below.max(top);
+                };
+                consider(x as i32 + 1, z as i32);
+                consider(x as i32 - 1, z as i32);
+                consider(x as i32, z as i32 + 1);
+                consider(x as i32, z as i32 - 1);
+                for y in 0..CHUNK_HEIGHT {
+                    let level = chunk.sky_light(x, y, z);
+                    if level > 1 && (level < MAX_LIGHT || y < darker_neighbour_below) {
+                        sky_queue.push_back((from_x + x as i32, y as i32, from_z + z as i32));
+                    }
+                }
+            }
+        }
+    }
+    // Light of loaded neighbours flows into this chunk wherever it is brighter than the blocks next to it
+    let chunk = blocks.get_chunk(chunk_pos).unwrap();
+    for_each_neighbouring_chunk(chunk_pos.0, chunk_pos.1, |neighbour_pos| {
+        if let Some(neighbour) = blocks.get_chunk(neighbour_pos) {
+            let (neighbour_x, neighbour_z) = chunk_pos_into_block_pos(neighbour_pos.0, neighbour_pos.1);
+            let (dx, dz) = (neighbour_pos.0 - chunk_pos.0, neighbour_pos.1 - chunk_pos.1);
+            for i in 0..CHUNK_WIDTH.max(CHUNK_DEPTH) {
+                // (x,z) in the neighbour and (my_x,my_z) in this chunk are adjacent
+                let (x, z, my_x, my_z) = match (dx, dz) {
+                    (1, _) if i < CHUNK_DEPTH => (0, i, CHUNK_WIDTH - 1, i),
+                    (-1, _) if i < CHUNK_DEPTH => (CHUNK_WIDTH - 1, i, 0, i),
+                    (_, 1) if i < CHUNK_WIDTH => (i, 0, i, CHUNK_DEPTH - 1),
+                    (_, -1) if i < CHUNK_WIDTH => (i, CHUNK_DEPTH - 1, i, 0),
+                    _ => continue
+                };
+                for y in 0..CHUNK_HEIGHT {
+                    let pos = (neighbour_x + x as i32, y as i32, neighbour_z + z as i32);
+                    if neighbour.sky_light(x, y, z) > chunk.sky_light(my_x, y, my_z) + 1 {
+                        sky_queue.push_back(pos);
+                    }
+                    if neighbour.block_light(x, y, z) > chunk.block_light(my_x, y, my_z) + 1 {
+                        block_queue.push_back(pos);
+                    }
+                }
+            }
+        }
+    });
+    propagate(blocks, LightChannel::Sky, &mut sky_queue, &mut changed);
+    propagate(blocks, LightChannel::Block, &mut block_queue, &mut changed);
+    changed.insert(chunk_pos);
+    for_each_neighbouring_chunk(chunk_pos.0, chunk_pos.1, |neighbour_pos| { changed.insert(neighbour_pos); });
+    for &chunk_pos in changed.iter() {
+        if let Some(chunk) = blocks.get_chunk_mut(chunk_pos) {
+            chunk.compact_light();
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::block::Block;
+    use crate::blocks::block_properties::{STONE, LAVA};
+    use crate::blocks::block_registry::init_for_tests;
+    use crate::blocks::chunk_blocks::ChunkBlocks;
+
+    /**Single chunk at the origin filled with stone up to (excluding) the given height*/
+    fn ground(height: usize) -> WorldBlocks {
+        init_for_tests();
+        let mut blocks = WorldBlocks::new();
+        let mut chunk = ChunkBlocks::new();
+        chunk.fill_level(0, height, STONE);
+        blocks.insert_chunk((0, 0), chunk);
+        blocks
+    }
+
+    fn sky(blocks: &WorldBlocks, x: i32, y: i32, z: i32) -> u8 {
+        light_at(blocks, LightChannel::Sky, x, y, z).unwrap()
+    }
+
+    fn emitted(blocks: &WorldBlocks, x: i32, y: i32, z: i32) -> u8 {
+        light_at(blocks, LightChannel::Block, x, y, z).unwrap()
+    }
+
+    #[test]
+    fn sky_light_falls_down_and_spreads_under_overhangs() {
+        let mut blocks = ground(10);
+        // roof over the half of the chunk with x < 8
+        blocks.no_update_fill(0, 20, 0, 8, 1, CHUNK_DEPTH, STONE);
+        compute_chunk_light(&mut blocks, (0, 0));
+        assert_eq!(sky(&blocks, 12, 10, 5), MAX_LIGHT);
+        assert_eq!(sky(&blocks, 12, 9, 5), 0);
+        assert_eq!(sky(&blocks, 3, 21, 5), MAX_LIGHT);
+        assert_eq!(sky(&blocks, 7, 19, 5), MAX_LIGHT - 1);
+        assert_eq!(sky(&blocks, 6, 15, 5), MAX_LIGHT - 2);
+        assert_eq!(sky(&blocks, 0, 10, 5), MAX_LIGHT - 8);
+    }
+
+    #[test]
+    fn block_light_spreads_through_caves_and_is_removed() {
+        let mut blocks = ground(10);
+        blocks.no_update_fill(8, 5, 8, 4, 1, 1, Block::air());
+        blocks.no_update_set_block(8, 5, 8, LAVA);
+        compute_chunk_light(&mut blocks, (0, 0));
+        assert_eq!(sky(&blocks, 10, 5, 8), 0);
+        assert_eq!(emitted(&blocks, 8, 5, 8), MAX_LIGHT);
+        assert_eq!(emitted(&blocks, 9, 5, 8), MAX_LIGHT - 1);
+        assert_eq!(emitted(&blocks, 11, 5, 8), MAX_LIGHT - 3);
+        blocks.no_update_set_block(9, 5, 8, STONE);
+        update_light_at(&mut blocks, 9, 5, 8);
+        assert_eq!(emitted(&blocks, 9, 5, 8), 0);
+        assert_eq!(emitted(&blocks, 10, 5, 8), 0);
+        assert_eq!(emitted(&blocks, 11, 5, 8), 0);
+    }
+
+    #[test]
+    fn opening_a_shaft_lets_sky_light_in() {
+        let mut blocks = ground(10);
+        compute_chunk_light(&mut blocks, (0, 0));
+        blocks.no_update_set_block(4, 9, 4, Block::air());
+        blocks.no_update_set_block(4, 8, 4, Block::air());
+        update_light_at(&mut blocks, 4, 9, 4);
+        update_light_at(&mut blocks, 4, 8, 4);
+        assert_eq!(sky(&blocks, 4, 8, 4), MAX_LIGHT);
+        assert_eq!(sky(&blocks, 4, 7, 4), 0);
+    }
+}
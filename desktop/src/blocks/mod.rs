@@ -1,22 +1,48 @@
 mod world_blocks;
 pub mod block_properties;
+pub mod block_registry;
 mod entities;
 mod chunk_faces;
+mod face_list;
 mod face;
 mod block;
+mod block_state;
 mod face_orientation;
-mod world_size;
+mod chunk_coords;
+mod chunk_blocks;
+mod chunk_generator;
+mod schematic;
+mod region;
+mod light;
+mod ambient_occlusion;
+mod greedy_mesher;
+mod ticks;
+mod history;
+mod fluids;
+mod falling_blocks;
+mod explosions;
 mod world;
 mod raycast;
 mod world_faces;
+mod section_visibility;
+mod chunk_mesh;
+mod workers;
+pub mod persistence;
 pub use world::World;
+pub use world_blocks::WorldBlocks;
 pub use block::Block;
+pub use block_state::Axis;
 pub use face::Face;
 pub use face_orientation::FaceOrientation;
 pub use entities::BodyPart;
 pub use entities::Entity;
-pub use entities::Entities;
 pub use entities::BoneInstance;
 pub use entities::ZombieVariant;
 pub use chunk_faces::ChunkFaces;
-pub use world_size::WorldSize;
\ No newline at end of file
+pub use chunk_coords::{CHUNK_WIDTH, CHUNK_DEPTH, CHUNK_HEIGHT};
+pub use chunk_blocks::ChunkBlocks;
+pub use chunk_generator::ChunkGenerator;
+pub use schematic::Schematic;
+pub use raycast::RayTarget;
+pub use region::Region;
+pub use greedy_mesher::MeshingMode;
\ No newline at end of file
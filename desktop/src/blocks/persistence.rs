@@ -0,0 +1,481 @@
+use std::io::{self, Read, Write, BufReader, BufWriter};
+use std::fs::{self, File};
+use std::path::Path;
+use crate::blocks::block::Block;
+use crate::blocks::block_properties::find_by_name;
+use crate::blocks::block_registry::registry;
+use crate::blocks::block_state::BlockState;
+use crate::blocks::entities::{Entities, Entity, ZombieVariant};
+use crate::blocks::chunk_coords::{ChunkPos, CHUNK_WIDTH, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_VOLUME};
+use crate::blocks::chunk_blocks::ChunkBlocks;
+
+/**Every saved world is a directory. The header file stores everything that is not a block
+ (block-id table, player and entities) and every chunk lives in its own region file
+ named after the chunk coordinates. Version 1 of the format described worlds of fixed size,
+ whose region files were named after the chunk index. Since version 3 ids stored in chunk files
+ carry the block state in their upper 16 bits (older saves simply have all states zeroed).*/
+pub const HEADER_FILE: &str = "world.dat";
+pub const CHUNKS_DIR: &str = "chunks";
+pub const FORMAT_VERSION: u32 = 3;
+const HEADER_MAGIC: [u8; 4] = *b"GLEW";
+const CHUNK_MAGIC: [u8; 4] = *b"GLEC";
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "I/O error")]
+    Io(#[cause] io::Error),
+    #[fail(display = "File {} is not a world save", name)]
+    BadMagic { name: String },
+    #[fail(display = "Unsupported save format version {}", version)]
+    UnsupportedVersion { version: u32 },
+    #[fail(display = "Save refers to unknown block {:?}", name)]
+    UnknownBlock { name: String },
+    #[fail(display = "Save refers to block id {} that is missing from the block table", id)]
+    BlockIdOutOfTable { id: u32 },
+    #[fail(display = "Save contains unknown entity kind {}", kind)]
+    UnknownEntity { kind: u8 },
+    #[fail(display = "Chunk {} is corrupted", name)]
+    CorruptedChunk { name: String },
+}
+
+impl From<io::Error> for Error {
+    fn from(other: io::Error) -> Self {
+        Error::Io(other)
+    }
+}
+
+pub struct PlayerPose {
+    pub location: glm::Vec3,
+    pub rotation: glm::Quat,
+}
+
+/**Maps block ids stored in chunk files to blocks of the currently running game and back.
+ Ids of a save never change, even if the game itself reorders or adds blocks.*/
+pub(crate) struct BlockTable {
+    blocks: Vec<Block>,
+    save_ids: Vec<u32>,
+}
+
+impl BlockTable {
+    pub fn identity() -> Self {
+        Self::from_saved(vec![])
+    }
+    /**Blocks of the running game that the save doesn't know about yet are appended at the end of the table*/
+    fn from_saved(mut blocks: Vec<Block>) -> Self {
+        let mut save_ids = vec![u32::MAX; registry().len()];
+        for (save_id, block) in blocks.iter().enumerate() {
+            save_ids[block.idx() as usize] = save_id as u32;
+        }
+        for idx in 0..registry().len() {
+            if save_ids[idx] == u32::MAX {
+                save_ids[idx] = blocks.len() as u32;
+                blocks.push(Block::new(idx as u32));
+            }
+        }
+        Self { blocks, save_ids }
+    }
+    pub fn block(&self, save_id: u32) -> Option<Block> {
+        self.blocks.get(save_id as usize).cloned()
+    }
+    pub fn save_id(&self, block: Block) -> u32 {
+        self.save_ids[block.idx() as usize]
+    }
+    /**Save id in the lower 16 bits and block state in the upper 16 bits*/
+    pub fn encode(&self, block: Block) -> u32 {
+        self.save_id(block) | (block.state().bits() as u32) << 16
+    }
+    pub fn decode(&self, id: u32) -> Option<Block> {
+        self.block(id & 0xFFFF).map(|block| block.with_state(BlockState::from_bits((id >> 16) as u16)))
+    }
+}
+
+pub(crate) struct Header {
+    /**Width and depth (in chunks) of worlds saved in format version 1*/
+    pub legacy_size: Option<(usize, usize)>,
+    pub block_table: BlockTable,
+    pub player: PlayerPose,
+}
+
+fn write_u8(w: &mut impl Write, v: u8) -> io::Result<()> {
+    w.write_all(&[v])
+}
+
+fn write_u32(w: &mut impl Write, v: u32) -> io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+fn write_i32(w: &mut impl Write, v: i32) -> io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+fn write_f32(w: &mut impl Write, v: f32) -> io::Result<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+fn write_str(w: &mut impl Write, v: &str) -> io::Result<()> {
+    assert!(v.len() <= u16::MAX as usize);
+    w.write_all(&(v.len() as u16).to_le_bytes())?;
+    w.write_all(v.as_bytes())
+}
+
+fn write_floats(w: &mut impl Write, v: &[f32]) -> io::Result<()> {
+    for &f in v {
+        write_f32(w, f)?;
+    }
+    Ok(())
+}
+
+fn read_u8(r: &mut impl Read) -> io::Result<u8> {
+    let mut b = [0u8; 1];
+    r.read_exact(&mut b)?;
+    Ok(b[0])
+}
+
+fn read_u32(r: &mut impl Read) -> io::Result<u32> {
+    let mut b = [0u8; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+fn read_i32(r: &mut impl Read) -> io::Result<i32> {
+    let mut b = [0u8; 4];
+    r.read_exact(&mut b)?;
+    Ok(i32::from_le_bytes(b))
+}
+
+fn read_f32(r: &mut impl Read) -> io::Result<f32> {
+    let mut b = [0u8; 4];
+    r.read_exact(&mut b)?;
+    Ok(f32::from_le_bytes(b))
+}
+
+fn read_str(r: &mut impl Read) -> Result<String, Error> {
+    let mut len = [0u8; 2];
+    r.read_exact(&mut len)?;
+    let mut b = vec![0u8; u16::from_le_bytes(len) as usize];
+    r.read_exact(&mut b)?;
+    String::from_utf8(b).map_err(|e| Error::Io(io::Error::new(io::ErrorKind::InvalidData, e)))
+}
+
+fn read_vec3(r: &mut impl Read) -> io::Result<glm::Vec3> {
+    Ok(glm::vec3(read_f32(r)?, read_f32(r)?, read_f32(r)?))
+}
+
+fn read_quat(r: &mut impl Read) -> io::Result<glm::Quat> {
+    Ok(glm::quat(read_f32(r)?, read_f32(r)?, read_f32(r)?, read_f32(r)?))
+}
+
+/**Returns the format version*/
+fn check_magic(r: &mut impl Read, magic: [u8; 4], name: &str) -> Result<u32, Error> {
+    let mut b = [0u8; 4];
+    r.read_exact(&mut b)?;
+    if b != magic {
+        return Err(Error::BadMagic { name: name.to_string() });
+    }
+    let version = read_u32(r)?;
+    if version < 1 || version > FORMAT_VERSION {
+        return Err(Error::UnsupportedVersion { version });
+    }
+    Ok(version)
+}
+
+fn chunk_file_name(chunk_pos: ChunkPos) -> String {
+    format!("{}_{}.chunk", chunk_pos.0, chunk_pos.1)
+}
+
+fn legacy_chunk_file_name(chunk_idx: usize) -> String {
+    format!("{}.chunk", chunk_idx)
+}
+
+/**Writes into a temporary file next to the target and then renames it over the target,
+ so that a crash in the middle of saving leaves the previous file intact.*/
+fn write_atomically(path: &Path, write: impl FnOnce(&mut BufWriter<File>) -> Result<(), Error>) -> Result<(), Error> {
+    let mut tmp_name = path.file_name().unwrap().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+    let result = File::create(&tmp).map_err(Error::from).and_then(|file| {
+        let mut w = BufWriter::new(file);
+        write(&mut w)?;
+        w.flush()?;
+        w.get_ref().sync_all()?;
+        Ok(())
+    });
+    match result {
+        Ok(()) => Ok(fs::rename(&tmp, path)?),
+        Err(err) => {
+            let _ = fs::remove_file(&tmp);
+            Err(err)
+        }
+    }
+}
+
+pub(crate) fn save_header(dir: &Path, block_table: &BlockTable, player: &PlayerPose, entities: &Entities) -> Result<(), Error> {
+    fs::create_dir_all(dir)?;
+    write_atomically(&dir.join(HEADER_FILE), |w| {
+        w.write_all(&HEADER_MAGIC)?;
+        write_u32(w, FORMAT_VERSION)?;
+        write_u32(w, block_table.blocks.len() as u32)?;
+        for block in block_table.blocks.iter() {
+            write_str(w, block.name())?;
+        }
+        write_floats(w, player.location.as_slice())?;
+        write_floats(w, player.rotation.coords.as_slice())?;
+        write_u32(w, entities.len() as u32)?;
+        for entity_id in 0..entities.len() {
+            match entities.entity(entity_id) {
+                Entity::Zombie(variant) => {
+                    write_u8(w, 0)?;
+                    write_u8(w, variant as u8)?;
+                }
+            }
+            write_floats(w, entities.position(entity_id).as_slice())?;
+            write_floats(w, entities.rotation(entity_id).coords.as_slice())?;
+        }
+        Ok(())
+    })
+}
+
+pub(crate) fn load_header(dir: &Path, entities: &mut Entities) -> Result<Header, Error> {
+    let mut r = BufReader::new(File::open(dir.join(HEADER_FILE))?);
+    let version = check_magic(&mut r, HEADER_MAGIC, HEADER_FILE)?;
+    let legacy_size = if version == 1 {
+        let width = read_u32(&mut r)? as usize;
+        let depth = read_u32(&mut r)? as usize;
+        Some((width, depth))
+    } else {
+        None
+    };
+    let table_len = read_u32(&mut r)?;
+    let mut block_table = Vec::with_capacity(table_len as usize);
+    for _ in 0..table_len {
+        let name = read_str(&mut r)?;
+        block_table.push(find_by_name(&name).ok_or(Error::UnknownBlock { name })?);
+    }
+    let block_table = BlockTable::from_saved(block_table);
+    let player = PlayerPose { location: read_vec3(&mut r)?, rotation: read_quat(&mut r)? };
+    let entity_count = read_u32(&mut r)?;
+    for _ in 0..entity_count {
+        let entity = match read_u8(&mut r)? {
+            0 => {
+                let variant = read_u8(&mut r)?;
+                let variant: ZombieVariant = num_traits::FromPrimitive::from_u8(variant).ok_or(Error::UnknownEntity { kind: 0 })?;
+                Entity::Zombie(variant)
+            }
+            kind => return Err(Error::UnknownEntity { kind })
+        };
+        let position = read_vec3(&mut r)?;
+        let rotation = read_quat(&mut r)?;
+        entities.push(entity, &position, &rotation);
+    }
+    Ok(Header { legacy_size, block_table, player })
+}
+
+/**Blocks are stored column by column in run-length encoded pairs (block id with state, run length), because
+ most of every chunk is air or long layers of the same block.*/
+pub(crate) fn save_chunk(dir: &Path, block_table: &BlockTable, chunk_pos: ChunkPos, chunk: &ChunkBlocks) -> Result<(), Error> {
+    let chunks_dir = dir.join(CHUNKS_DIR);
+    fs::create_dir_all(&chunks_dir)?;
+    write_atomically(&chunks_dir.join(chunk_file_name(chunk_pos)), |w| {
+        w.write_all(&CHUNK_MAGIC)?;
+        write_u32(w, FORMAT_VERSION)?;
+        write_i32(w, chunk_pos.0)?;
+        write_i32(w, chunk_pos.1)?;
+        let mut runs: Vec<(u32, u32)> = Vec::new();
+        for x in 0..CHUNK_WIDTH {
+            for z in 0..CHUNK_DEPTH {
+                for y in 0..CHUNK_HEIGHT {
+                    let id = block_table.encode(*chunk.get_block(x, y, z));
+                    match runs.last_mut() {
+                        Some((last_id, run)) if *last_id == id => *run += 1,
+                        _ => runs.push((id, 1)),
+                    }
+                }
+            }
+        }
+        write_u32(w, runs.len() as u32)?;
+        for (id, run) in runs {
+            write_u32(w, id)?;
+            write_u32(w, run)?;
+        }
+        Ok(())
+    })
+}
+
+fn read_runs(r: &mut impl Read, name: &str, block_table: &BlockTable) -> Result<ChunkBlocks, Error> {
+    let mut chunk = ChunkBlocks::new();
+    let run_count = read_u32(r)?;
+    let mut offset = 0usize;
+    for _ in 0..run_count {
+        let id = read_u32(r)?;
+        let run = read_u32(r)? as usize;
+        let block = block_table.decode(id).ok_or(Error::BlockIdOutOfTable { id })?;
+        if offset + run > CHUNK_VOLUME {
+            return Err(Error::CorruptedChunk { name: name.to_string() });
+        }
+        for i in offset..offset + run {
+            let y = i % CHUNK_HEIGHT;
+            let z = (i / CHUNK_HEIGHT) % CHUNK_DEPTH;
+            let x = i / (CHUNK_HEIGHT * CHUNK_DEPTH);
+            chunk.set_block(x, y, z, block);
+        }
+        offset += run;
+    }
+    if offset != CHUNK_VOLUME {
+        return Err(Error::CorruptedChunk { name: name.to_string() });
+    }
+    Ok(chunk)
+}
+
+/**Returns None if the chunk was never saved.*/
+pub(crate) fn load_chunk(dir: &Path, chunk_pos: ChunkPos, block_table: &BlockTable) -> Result<Option<ChunkBlocks>, Error> {
+    let name = chunk_file_name(chunk_pos);
+    let path = dir.join(CHUNKS_DIR).join(&name);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let mut r = BufReader::new(File::open(path)?);
+    check_magic(&mut r, CHUNK_MAGIC, &name)?;
+    if (read_i32(&mut r)?, read_i32(&mut r)?) != chunk_pos {
+        return Err(Error::CorruptedChunk { name });
+    }
+    read_runs(&mut r, &name, block_table).map(Some)
+}
+
+/**Loads region file written in format version 1, where chunks were keyed by index instead of coordinates.*/
+pub(crate) fn load_legacy_chunk(dir: &Path, chunk_idx: usize, block_table: &BlockTable) -> Result<Option<ChunkBlocks>, Error> {
+    let name = legacy_chunk_file_name(chunk_idx);
+    let path = dir.join(CHUNKS_DIR).join(&name);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let mut r = BufReader::new(File::open(path)?);
+    check_magic(&mut r, CHUNK_MAGIC, &name)?;
+    if read_u32(&mut r)? as usize != chunk_idx {
+        return Err(Error::CorruptedChunk { name });
+    }
+    read_runs(&mut r, &name, block_table).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::blocks::block_properties::{AIR, STONE, DIRT, SLAB};
+    use crate::blocks::block_registry::init_for_tests;
+
+    fn temp_save_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("glengine_persistence_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn player() -> PlayerPose {
+        PlayerPose { location: glm::vec3(1., 70., -3.), rotation: glm::quat(0., 0.6, 0., 0.8) }
+    }
+
+    #[test]
+    fn chunk_round_trip_with_remapped_table() {
+        init_for_tests();
+        let dir = temp_save_dir("round_trip");
+        // the save knows only a few blocks and in a different order than the registry
+        let block_table = BlockTable::from_saved(vec![STONE, DIRT, AIR]);
+        assert_eq!(block_table.save_id(STONE), 0);
+        assert_eq!(block_table.save_id(AIR), 2);
+        assert_eq!(block_table.block(block_table.save_id(SLAB)), Some(SLAB));
+        let half = SLAB.with_state(BlockState::from_bits(1));
+        let mut chunk = ChunkBlocks::new();
+        chunk.fill_level(0, 3, STONE);
+        chunk.set_block(4, 3, 5, DIRT);
+        chunk.set_block(7, 40, 1, half);
+        save_header(&dir, &block_table, &player(), &Entities::new()).unwrap();
+        save_chunk(&dir, &block_table, (-2, 5), &chunk).unwrap();
+
+        let header = load_header(&dir, &mut Entities::new()).unwrap();
+        assert!(header.legacy_size.is_none());
+        assert_eq!(header.block_table.blocks, block_table.blocks);
+        assert_eq!(header.player.location, player().location);
+        assert_eq!(header.player.rotation, player().rotation);
+        let loaded = load_chunk(&dir, (-2, 5), &header.block_table).unwrap().unwrap();
+        for x in 0..CHUNK_WIDTH {
+            for z in 0..CHUNK_DEPTH {
+                for y in 0..CHUNK_HEIGHT {
+                    assert_eq!(loaded.get_block(x, y, z), chunk.get_block(x, y, z));
+                }
+            }
+        }
+        assert_eq!(*loaded.get_block(7, 40, 1), half);
+        assert!(load_chunk(&dir, (5, -2), &header.block_table).unwrap().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn saving_leaves_no_temporary_files() {
+        init_for_tests();
+        let dir = temp_save_dir("no_temporary");
+        let block_table = BlockTable::identity();
+        save_header(&dir, &block_table, &player(), &Entities::new()).unwrap();
+        save_chunk(&dir, &block_table, (0, 0), &ChunkBlocks::new()).unwrap();
+        save_chunk(&dir, &block_table, (0, 0), &ChunkBlocks::new()).unwrap();
+        let names: Vec<String> = fs::read_dir(&dir).unwrap().chain(fs::read_dir(dir.join(CHUNKS_DIR)).unwrap())
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert!(names.iter().all(|name| !name.ends_with(".tmp")), "{:?}", names);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn loads_version_1_save() {
+        init_for_tests();
+        let dir = temp_save_dir("version_1");
+        fs::create_dir_all(dir.join(CHUNKS_DIR)).unwrap();
+        let mut header = Vec::new();
+        header.extend_from_slice(&HEADER_MAGIC);
+        write_u32(&mut header, 1).unwrap();
+        // fixed world of 2x1 chunks
+        write_u32(&mut header, 2).unwrap();
+        write_u32(&mut header, 1).unwrap();
+        write_u32(&mut header, 2).unwrap();
+        write_str(&mut header, DIRT.name()).unwrap();
+        write_str(&mut header, AIR.name()).unwrap();
+        write_floats(&mut header, player().location.as_slice()).unwrap();
+        write_floats(&mut header, player().rotation.coords.as_slice()).unwrap();
+        write_u32(&mut header, 0).unwrap();
+        fs::write(dir.join(HEADER_FILE), header).unwrap();
+        let mut chunk = Vec::new();
+        chunk.extend_from_slice(&CHUNK_MAGIC);
+        write_u32(&mut chunk, 1).unwrap();
+        write_u32(&mut chunk, 1).unwrap();
+        // the first column is dirt and the rest is air
+        write_u32(&mut chunk, 2).unwrap();
+        write_u32(&mut chunk, 0).unwrap();
+        write_u32(&mut chunk, CHUNK_HEIGHT as u32).unwrap();
+        write_u32(&mut chunk, 1).unwrap();
+        write_u32(&mut chunk, (CHUNK_VOLUME - CHUNK_HEIGHT) as u32).unwrap();
+        fs::write(dir.join(CHUNKS_DIR).join(legacy_chunk_file_name(1)), chunk).unwrap();
+
+        let header = load_header(&dir, &mut Entities::new()).unwrap();
+        assert_eq!(header.legacy_size, Some((2, 1)));
+        assert_eq!(header.block_table.save_id(DIRT), 0);
+        assert!(load_legacy_chunk(&dir, 0, &header.block_table).unwrap().is_none());
+        let loaded = load_legacy_chunk(&dir, 1, &header.block_table).unwrap().unwrap();
+        assert_eq!(*loaded.get_block(0, 0, 0), DIRT);
+        assert_eq!(*loaded.get_block(0, CHUNK_HEIGHT - 1, 0), DIRT);
+        assert!(loaded.get_block(0, 0, 1).is_air());
+        assert!(loaded.get_block(1, 0, 0).is_air());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        for &version in [0, FORMAT_VERSION + 1].iter() {
+            let mut header = Vec::new();
+            header.extend_from_slice(&HEADER_MAGIC);
+            write_u32(&mut header, version).unwrap();
+            match check_magic(&mut header.as_slice(), HEADER_MAGIC, HEADER_FILE) {
+                Err(Error::UnsupportedVersion { version: found }) => assert_eq!(found, version),
+                other => panic!("version {} was not rejected: {:?}", version, other.map_err(|e| e.to_string())),
+            }
+        }
+        assert!(matches!(check_magic(&mut &b"GLEC\x01\0\0\0"[..], HEADER_MAGIC, HEADER_FILE), Err(Error::BadMagic { .. })));
+    }
+}
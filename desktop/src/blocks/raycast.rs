@@ -1,3 +1,79 @@
+use crate::blocks::block::Block;
+use crate::blocks::face_orientation::FaceOrientation;
+use crate::blocks::entities::EntityHit;
+
+/**Block hit by a ray cast with World::raycast*/
+#[derive(Copy, Clone, Debug)]
+pub struct RaycastHit {
+    pub block_pos: (i32, i32, i32),
+    /**Empty cell in front of the hit face, through which the ray entered the block.
+    Equal to block_pos if the ray started inside of the block*/
+    pub adjacent_pos: (i32, i32, i32),
+    /**Face of the block through which the ray entered it*/
+    pub face: FaceOrientation,
+    /**Distance from the origin of the ray to the point where it entered the block*/
+    pub distance: f32,
+    pub block: Block,
+}
+
+impl RaycastHit {
+    /**Point at which the ray entered the block*/
+    pub fn point(&self, origin: &glm::Vec3, direction: &glm::Vec3) -> glm::Vec3 {
+        origin + glm::normalize(direction) * self.distance
+    }
+}
+
+/**Whatever a ray hit first*/
+#[derive(Copy, Clone, Debug)]
+pub enum RayTarget {
+    Block(RaycastHit),
+    Entity(EntityHit),
+}
+
+impl RayTarget {
+    pub fn distance(&self) -> f32 {
+        match self {
+            RayTarget::Block(hit) => hit.distance,
+            RayTarget::Entity(hit) => hit.distance,
+        }
+    }
+}
+
+/**Face of a block that a ray going along the direction enters first, judging only by the direction*/
+pub fn entry_face(direction: &glm::Vec3) -> FaceOrientation {
+    let abs = glm::abs(direction);
+    if abs.x >= abs.y && abs.x >= abs.z {
+        if direction.x > 0. { FaceOrientation::XMinus } else { FaceOrientation::XPlus }
+    } else if abs.y >= abs.z {
+        if direction.y > 0. { FaceOrientation::YMinus } else { FaceOrientation::YPlus }
+    } else {
+        if direction.z > 0. { FaceOrientation::ZMinus } else { FaceOrientation::ZPlus }
+    }
+}
+
+/**Distance along a normalized direction at which the ray enters the axis-aligned box (slab method).
+Returns 0 if the origin lies inside the box and None if the ray misses it or the box lies behind the origin*/
+pub fn ray_aabb(origin: &glm::Vec3, direction: &glm::Vec3, min: &glm::Vec3, max: &glm::Vec3) -> Option<f32> {
+    let mut t_near = 0f32;
+    let mut t_far = f32::INFINITY;
+    for axis in 0..3 {
+        if direction[axis] == 0. {
+            if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                return None;
+            }
+        } else {
+            let t0 = (min[axis] - origin[axis]) / direction[axis];
+            let t1 = (max[axis] - origin[axis]) / direction[axis];
+            t_near = t_near.max(t0.min(t1));
+            t_far = t_far.min(t0.max(t1));
+            if t_near > t_far {
+                return None;
+            }
+        }
+    }
+    Some(t_near)
+}
+
 
 pub fn ray_cast<T, F: FnMut(f32, f32, f32, f32, f32, f32) -> Option<T>>(start: &[f32], distance_and_direction: &[f32], mut f: F) -> Option<T> {
     //initial point A
@@ -0,0 +1,176 @@
+use crate::blocks::block::Block;
+use crate::blocks::schematic::Schematic;
+use crate::blocks::world_blocks::WorldBlocks;
+
+/**Axis-aligned box of blocks given by two opposite corners, both of which belong to the region*/
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Region {
+    min: (i32, i32, i32),
+    max: (i32, i32, i32),
+}
+
+impl Region {
+    /**Corners can be given in any order*/
+    pub fn from_corners(a: (i32, i32, i32), b: (i32, i32, i32)) -> Self {
+        Self {
+            min: (a.0.min(b.0), a.1.min(b.1), a.2.min(b.2)),
+            max: (a.0.max(b.0), a.1.max(b.1), a.2.max(b.2)),
+        }
+    }
+    pub fn min(&self) -> (i32, i32, i32) {
+        self.min
+    }
+    pub fn max(&self) -> (i32, i32, i32) {
+        self.max
+    }
+    pub fn width(&self) -> usize {
+        (self.max.0 - self.min.0 + 1) as usize
+    }
+    pub fn height(&self) -> usize {
+        (self.max.1 - self.min.1 + 1) as usize
+    }
+    pub fn depth(&self) -> usize {
+        (self.max.2 - self.min.2 + 1) as usize
+    }
+    pub fn volume(&self) -> usize {
+        self.width() * self.height() * self.depth()
+    }
+    pub fn contains(&self, x: i32, y: i32, z: i32) -> bool {
+        self.min.0 <= x && x <= self.max.0 && self.min.1 <= y && y <= self.max.1 && self.min.2 <= z && z <= self.max.2
+    }
+    pub fn is_on_boundary(&self, x: i32, y: i32, z: i32) -> bool {
+        x == self.min.0 || x == self.max.0 || y == self.min.1 || y == self.max.1 || z == self.min.2 || z == self.max.2
+    }
+    pub fn for_each(&self, mut f: impl FnMut(i32, i32, i32)) {
+        for x in self.min.0..=self.max.0 {
+            for z in self.min.2..=self.max.2 {
+                for y in self.min.1..=self.max.1 {
+                    f(x, y, z)
+                }
+            }
+        }
+    }
+    /**Changes that set every block of the region*/
+    pub fn fill(&self, block: Block) -> Vec<(i32, i32, i32, Block)> {
+        let mut changes = Vec::with_capacity(self.volume());
+        self.for_each(|x, y, z| changes.push((x, y, z, block)));
+        changes
+    }
+    /**Changes that set the walls, floor and ceiling of the region and clear its inside*/
+    pub fn hollow_box(&self, block: Block) -> Vec<(i32, i32, i32, Block)> {
+        let mut changes = Vec::with_capacity(self.volume());
+        self.for_each(|x, y, z| changes.push((x, y, z, if self.is_on_boundary(x, y, z) { block } else { Block::air() })));
+        changes
+    }
+    /**Changes that turn every block of the same kind as old (regardless of its state) into new*/
+    pub fn replace(&self, blocks: &WorldBlocks, old: Block, new: Block) -> Vec<(i32, i32, i32, Block)> {
+        let mut changes = Vec::new();
+        self.for_each(|x, y, z| {
+            if blocks.try_get_block(x, y, z).map(|b| b.is_same_kind(old)).unwrap_or(false) {
+                changes.push((x, y, z, new))
+            }
+        });
+        changes
+    }
+    /**Copies blocks of the region into a schematic whose origin is the minimal corner. Blocks that are not loaded become air*/
+    pub fn copy(&self, blocks: &WorldBlocks) -> Schematic {
+        let mut schematic = Schematic::new(self.width(), self.height(), self.depth());
+        self.for_each(|x, y, z| {
+            if let Some(&block) = blocks.try_get_block(x, y, z) {
+                schematic.set_block((x - self.min.0) as usize, (y - self.min.1) as usize, (z - self.min.2) as usize, block)
+            }
+        });
+        schematic
+    }
+}
+
+/**Changes that set all blocks whose centres lie within the radius from the centre of block (x,y,z).
+A hollow sphere only keeps a shell one block thick*/
+pub fn sphere(x: i32, y: i32, z: i32, radius: f32, block: Block, hollow: bool) -> Vec<(i32, i32, i32, Block)> {
+    let r = radius.ceil() as i32;
+    let mut changes = Vec::new();
+    for dx in -r..=r {
+        for dy in -r..=r {
+            for dz in -r..=r {
+                let distance = ((dx * dx + dy * dy + dz * dz) as f32).sqrt();
+                if distance <= radius && (!hollow || distance > radius - 1.) {
+                    changes.push((x + dx, y + dy, z + dz, block));
+                }
+            }
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::block_properties::{STONE, DIRT, GLASS, WOOD};
+    use crate::blocks::block_registry::init_for_tests;
+    use crate::blocks::block_state::{BlockState, Axis};
+    use crate::blocks::chunk_blocks::ChunkBlocks;
+
+    fn world() -> WorldBlocks {
+        init_for_tests();
+        let mut blocks = WorldBlocks::new();
+        let mut chunk = ChunkBlocks::new();
+        chunk.fill_level(0, 4, STONE);
+        blocks.insert_chunk((0, 0), chunk);
+        blocks
+    }
+
+    #[test]
+    fn corners_can_come_in_any_order() {
+        let region = Region::from_corners((5, 1, -2), (2, 4, 3));
+        assert_eq!(region, Region::from_corners((2, 1, 3), (5, 4, -2)));
+        assert_eq!(region.min(), (2, 1, -2));
+        assert_eq!(region.max(), (5, 4, 3));
+        assert_eq!((region.width(), region.height(), region.depth()), (4, 4, 6));
+        assert_eq!(region.volume(), 96);
+        assert!(region.contains(5, 4, 3) && region.contains(2, 1, -2));
+        assert!(!region.contains(6, 4, 3) && !region.contains(2, 0, -2));
+        let single = Region::from_corners((7, 7, 7), (7, 7, 7));
+        assert_eq!(single.volume(), 1);
+        assert_eq!(single.fill(STONE), vec![(7, 7, 7, STONE)]);
+    }
+
+    #[test]
+    fn hollow_box_has_walls_and_empty_inside() {
+        let changes = Region::from_corners((0, 0, 0), (3, 3, 3)).hollow_box(GLASS);
+        assert_eq!(changes.len(), 64);
+        let walls = changes.iter().filter(|&&(_, _, _, b)| b == GLASS).count();
+        assert_eq!(walls, 64 - 8);
+        for &(x, y, z, block) in &changes {
+            let inside = (1..=2).contains(&x) && (1..=2).contains(&y) && (1..=2).contains(&z);
+            assert_eq!(block.is_air(), inside, "{},{},{}", x, y, z);
+        }
+    }
+
+    #[test]
+    fn replace_ignores_state_and_unloaded_blocks() {
+        let mut blocks = world();
+        let turned_log = WOOD.with_state(BlockState::default().with_axis(Axis::X));
+        blocks.no_update_set_block(1, 4, 1, WOOD);
+        blocks.no_update_set_block(2, 4, 1, turned_log);
+        blocks.no_update_set_block(3, 4, 1, DIRT);
+        // the region reaches into the unloaded chunk at x < 0
+        let mut changes = Region::from_corners((-2, 4, 1), (3, 4, 1)).replace(&blocks, WOOD, GLASS);
+        changes.sort_by_key(|&(x, _, _, _)| x);
+        assert_eq!(changes, vec![(1, 4, 1, GLASS), (2, 4, 1, GLASS)]);
+    }
+
+    #[test]
+    fn copy_starts_at_the_minimal_corner() {
+        let mut blocks = world();
+        blocks.no_update_set_block(2, 4, 3, DIRT);
+        let schematic = Region::from_corners((3, 4, 4), (1, 3, 2)).copy(&blocks);
+        assert_eq!((schematic.width(), schematic.height(), schematic.depth()), (3, 2, 3));
+        assert_eq!(*schematic.get_block(1, 1, 1), DIRT);
+        assert_eq!(*schematic.get_block(0, 0, 0), STONE);
+        assert!(schematic.get_block(0, 1, 0).is_air());
+        // unloaded blocks are copied as air
+        let outside = Region::from_corners((-1, 0, 0), (0, 0, 0)).copy(&blocks);
+        assert!(outside.get_block(0, 0, 0).is_air());
+        assert_eq!(*outside.get_block(1, 0, 0), STONE);
+    }
+}
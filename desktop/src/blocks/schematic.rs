@@ -0,0 +1,242 @@
+use crate::blocks::block::Block;
+use crate::blocks::block_state::Axis;
+use crate::blocks::chunk_blocks::ChunkBlocks;
+use crate::blocks::chunk_coords::{chunk_pos_into_block_pos, is_height_in_bounds, CHUNK_DEPTH, CHUNK_WIDTH};
+
+/**A multi-block structure (tree, house, copied region) that can be stamped into the world.
+ Block (origin_x, origin_y, origin_z) of the schematic lands at the position it is placed at.*/
+#[derive(Clone)]
+pub struct Schematic {
+    width: usize,
+    height: usize,
+    depth: usize,
+    blocks: Vec<Block>,
+    origin: (i32, i32, i32),
+    /**If true, air blocks of the schematic do not overwrite existing blocks of the world*/
+    air_keeps_existing: bool,
+}
+
+impl Schematic {
+    pub fn new(width: usize, height: usize, depth: usize) -> Self {
+        Self::from_blocks(width, height, depth, vec![Block::air(); width * height * depth])
+    }
+    /**Blocks are indexed the same way as in chunks, that is z * width + x + y * width * depth*/
+    pub fn from_blocks(width: usize, height: usize, depth: usize, blocks: Vec<Block>) -> Self {
+        assert_eq!(blocks.len(), width * height * depth);
+        Self { width, height, depth, blocks, origin: (0, 0, 0), air_keeps_existing: false }
+    }
+    pub fn with_origin(mut self, x: i32, y: i32, z: i32) -> Self {
+        self.origin = (x, y, z);
+        self
+    }
+    pub fn with_air_keeps_existing(mut self, air_keeps_existing: bool) -> Self {
+        self.air_keeps_existing = air_keeps_existing;
+        self
+    }
+    pub fn origin(&self) -> (i32, i32, i32) {
+        self.origin
+    }
+    pub fn set_origin(&mut self, x: i32, y: i32, z: i32) {
+        self.origin = (x, y, z)
+    }
+    pub fn air_keeps_existing(&self) -> bool {
+        self.air_keeps_existing
+    }
+    pub fn set_air_keeps_existing(&mut self, air_keeps_existing: bool) {
+        self.air_keeps_existing = air_keeps_existing
+    }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+    fn idx(&self, x: usize, y: usize, z: usize) -> usize {
+        assert!(x < self.width && y < self.height && z < self.depth);
+        z * self.width + x + y * self.width * self.depth
+    }
+    pub fn get_block(&self, x: usize, y: usize, z: usize) -> &Block {
+        &self.blocks[self.idx(x, y, z)]
+    }
+    pub fn get_block_mut(&mut self, x: usize, y: usize, z: usize) -> &mut Block {
+        let idx = self.idx(x, y, z);
+        &mut self.blocks[idx]
+    }
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, block: Block) {
+        *self.get_block_mut(x, y, z) = block
+    }
+    /**Same as set_block but silently ignores positions outside of the schematic*/
+    pub fn try_set_block(&mut self, x: i32, y: i32, z: i32, block: Block) {
+        if 0 <= x && 0 <= y && 0 <= z && (x as usize) < self.width && (y as usize) < self.height && (z as usize) < self.depth {
+            self.set_block(x as usize, y as usize, z as usize, block)
+        }
+    }
+    pub fn fill(&mut self, from_x: usize, from_y: usize, from_z: usize, width: usize, height: usize, depth: usize, block: Block) {
+        for y in from_y..from_y + height {
+            for z in from_z..from_z + depth {
+                for x in from_x..from_x + width {
+                    self.set_block(x, y, z, block)
+                }
+            }
+        }
+    }
+    /**Copy of the schematic turned by the given number of quarter turns around the Y axis.
+    Oriented blocks are turned along with it and the origin stays at the same block*/
+    pub fn rotated_y(&self, turns: usize) -> Self {
+        let mut rotated = self.clone();
+        for _ in 0..turns % 4 {
+            rotated = rotated.rotated_quarter();
+        }
+        rotated
+    }
+    /**A quarter turn maps (x, z) to (depth - 1 - z, x), which takes ZMinus to XPlus*/
+    fn rotated_quarter(&self) -> Self {
+        let mut rotated = Self::new(self.depth, self.height, self.width);
+        for y in 0..self.height {
+            for z in 0..self.depth {
+                for x in 0..self.width {
+                    rotated.set_block(self.depth - 1 - z, y, x, self.get_block(x, y, z).rotated_y(1))
+                }
+            }
+        }
+        let (x, y, z) = self.origin;
+        rotated.origin = (self.depth as i32 - 1 - z, y, x);
+        rotated.air_keeps_existing = self.air_keeps_existing;
+        rotated
+    }
+    /**Copy of the schematic reflected across a plane perpendicular to the given axis.
+    Oriented blocks are reflected along with it and the origin stays at the same block*/
+    pub fn mirrored(&self, axis: Axis) -> Self {
+        let mut mirrored = self.clone();
+        for y in 0..self.height {
+            for z in 0..self.depth {
+                for x in 0..self.width {
+                    let (mx, my, mz) = match axis {
+                        Axis::X => (self.width - 1 - x, y, z),
+                        Axis::Y => (x, self.height - 1 - y, z),
+                        Axis::Z => (x, y, self.depth - 1 - z),
+                    };
+                    mirrored.set_block(mx, my, mz, self.get_block(x, y, z).mirrored(axis))
+                }
+            }
+        }
+        let (x, y, z) = self.origin;
+        mirrored.origin = match axis {
+            Axis::X => (self.width as i32 - 1 - x, y, z),
+            Axis::Y => (x, self.height as i32 - 1 - y, z),
+            Axis::Z => (x, y, self.depth as i32 - 1 - z),
+        };
+        mirrored
+    }
+    /**Calls f with absolute position and block for every block that would be written
+    when placing the schematic at (x,y,z). Air is skipped if air_keeps_existing is set.*/
+    pub fn for_each_placed(&self, x: i32, y: i32, z: i32, mut f: impl FnMut(i32, i32, i32, Block)) {
+        let (from_x, from_y, from_z) = (x - self.origin.0, y - self.origin.1, z - self.origin.2);
+        for sy in 0..self.height {
+            for sz in 0..self.depth {
+                for sx in 0..self.width {
+                    let &block = self.get_block(sx, sy, sz);
+                    if !(self.air_keeps_existing && block.is_air()) {
+                        f(from_x + sx as i32, from_y + sy as i32, from_z + sz as i32, block)
+                    }
+                }
+            }
+        }
+    }
+    /**Writes the part of the schematic placed at (x,y,z) that overlaps with the given chunk.
+    This lets world generators place structures crossing chunk borders one chunk at a time.*/
+    pub fn stamp_into_chunk(&self, x: i32, y: i32, z: i32, chunk_x: i32, chunk_z: i32, chunk: &mut ChunkBlocks) {
+        let (chunk_from_x, chunk_from_z) = chunk_pos_into_block_pos(chunk_x, chunk_z);
+        let (from_x, from_z) = (x - self.origin.0, z - self.origin.2);
+        if from_x + self.width as i32 <= chunk_from_x || chunk_from_x + CHUNK_WIDTH as i32 <= from_x ||
+            from_z + self.depth as i32 <= chunk_from_z || chunk_from_z + CHUNK_DEPTH as i32 <= from_z {
+            return;
+        }
+        self.for_each_placed(x, y, z, |bx, by, bz, block| {
+            let (local_x, local_z) = (bx - chunk_from_x, bz - chunk_from_z);
+            if 0 <= local_x && local_x < CHUNK_WIDTH as i32 && 0 <= local_z && local_z < CHUNK_DEPTH as i32 && is_height_in_bounds(by) {
+                chunk.set_block(local_x as usize, by as usize, local_z as usize, block)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::block_properties::{STONE, DIRT, CRAFTING, WOOD, SLAB};
+    use crate::blocks::block_registry::init_for_tests;
+    use crate::blocks::block_state::BlockState;
+    use crate::blocks::face_orientation::FaceOrientation;
+
+    /**3 wide, 1 high and 2 deep, with stone at (0,0,0) and dirt at (2,0,1)*/
+    fn sample() -> Schematic {
+        init_for_tests();
+        let mut schematic = Schematic::new(3, 1, 2).with_origin(0, 0, 1);
+        schematic.set_block(0, 0, 0, STONE);
+        schematic.set_block(2, 0, 1, DIRT);
+        schematic
+    }
+
+    fn placed(schematic: &Schematic) -> Vec<(i32, i32, i32, Block)> {
+        let mut blocks = Vec::new();
+        schematic.for_each_placed(10, 20, 30, |x, y, z, b| if !b.is_air() { blocks.push((x, y, z, b)) });
+        blocks.sort_by_key(|&(x, y, z, _)| (x, y, z));
+        blocks
+    }
+
+    #[test]
+    fn quarter_turn_moves_blocks_and_origin() {
+        let rotated = sample().rotated_y(1);
+        assert_eq!((rotated.width(), rotated.height(), rotated.depth()), (2, 1, 3));
+        // (x, z) goes to (depth - 1 - z, x)
+        assert_eq!(*rotated.get_block(1, 0, 0), STONE);
+        assert_eq!(*rotated.get_block(0, 0, 2), DIRT);
+        assert_eq!(rotated.origin(), (0, 0, 0));
+        assert_eq!(placed(&rotated), vec![(10, 20, 32, DIRT), (11, 20, 30, STONE)]);
+        let original = sample();
+        assert_eq!(placed(&original.rotated_y(4)), placed(&original));
+        assert_eq!(placed(&original.rotated_y(2)), placed(&original.rotated_y(1).rotated_y(1)));
+    }
+
+    #[test]
+    fn oriented_blocks_turn_with_the_schematic() {
+        init_for_tests();
+        let mut schematic = Schematic::new(1, 1, 2);
+        schematic.set_block(0, 0, 0, CRAFTING.with_state(BlockState::default().with_facing(FaceOrientation::ZMinus)));
+        schematic.set_block(0, 0, 1, WOOD.with_state(BlockState::default().with_axis(Axis::X)));
+        let rotated = schematic.rotated_y(1);
+        assert_eq!(rotated.get_block(1, 0, 0).state().facing(), FaceOrientation::XPlus);
+        assert_eq!(rotated.get_block(0, 0, 0).state().axis(), Axis::Z);
+    }
+
+    #[test]
+    fn mirroring_flips_blocks_origin_and_states() {
+        let mirrored = sample().mirrored(Axis::X);
+        assert_eq!(*mirrored.get_block(2, 0, 0), STONE);
+        assert_eq!(*mirrored.get_block(0, 0, 1), DIRT);
+        assert_eq!(mirrored.origin(), (2, 0, 1));
+        let twice = mirrored.mirrored(Axis::X);
+        assert_eq!(placed(&twice), placed(&sample()));
+        let mut slab = Schematic::new(1, 2, 1);
+        slab.set_block(0, 0, 0, SLAB);
+        let flipped = slab.mirrored(Axis::Y);
+        assert!(flipped.get_block(0, 0, 0).is_air());
+        assert!(flipped.get_block(0, 1, 0).state().is_top_half());
+    }
+
+    #[test]
+    fn air_can_keep_existing_blocks() {
+        let schematic = sample();
+        let mut count = 0;
+        schematic.for_each_placed(0, 0, 0, |_, _, _, _| count += 1);
+        assert_eq!(count, 6);
+        let schematic = schematic.with_air_keeps_existing(true);
+        let mut count = 0;
+        schematic.for_each_placed(0, 0, 0, |_, _, _, _| count += 1);
+        assert_eq!(count, 2);
+    }
+}
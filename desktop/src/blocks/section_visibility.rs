@@ -0,0 +1,178 @@
+use std::collections::{HashSet, VecDeque};
+use crate::blocks::chunk_blocks::ChunkBlocks;
+use crate::blocks::chunk_coords::{ChunkPos, CHUNK_WIDTH, CHUNK_DEPTH, CHUNK_HEIGHT, SECTION_HEIGHT, SECTIONS, block_pos_into_chunk_pos};
+use crate::blocks::face_orientation::FaceOrientation;
+use crate::blocks::world_faces::WorldFaces;
+
+/**Chunk position and index of the section within the chunk (counted from the bottom)*/
+pub type SectionPos = (ChunkPos, usize);
+
+/**Which faces of a section can be seen from which other faces, looking through blocks that show faces of their neighbours
+(air, water, glass, leaves...). Bit number a*6+b is set if faces a and b are connected*/
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct SectionVisibility(u64);
+
+impl SectionVisibility {
+    /**Every face sees every other face, as in an empty section*/
+    pub const ALL: Self = Self((1 << 36) - 1);
+    pub const NONE: Self = Self(0);
+
+    pub fn connects(&self, a: FaceOrientation, b: FaceOrientation) -> bool {
+        self.0 & (1 << (a as u64 * 6 + b as u64)) != 0
+    }
+    fn connect(&mut self, a: FaceOrientation, b: FaceOrientation) {
+        self.0 |= (1 << (a as u64 * 6 + b as u64)) | (1 << (b as u64 * 6 + a as u64))
+    }
+    /**Flood fills every group of see-through blocks in the section and connects all faces of the section touched by the same group*/
+    pub fn compute(chunk: &ChunkBlocks, section: usize) -> Self {
+        if chunk.is_section_empty(section) {
+            return Self::ALL;
+        }
+        let from_y = section * SECTION_HEIGHT;
+        let index = |x: usize, y: usize, z: usize| x + (z + y * CHUNK_DEPTH) * CHUNK_WIDTH;
+        let mut visited = vec![false; CHUNK_WIDTH * SECTION_HEIGHT * CHUNK_DEPTH];
+        let mut visibility = Self::NONE;
+        let mut stack = Vec::new();
+        for y in 0..SECTION_HEIGHT {
+            for z in 0..CHUNK_DEPTH {
+                for x in 0..CHUNK_WIDTH {
+                    if visited[index(x, y, z)] || !chunk.get_block(x, from_y + y, z).show_neighboring_faces() {
+                        continue;
+                    }
+                    visited[index(x, y, z)] = true;
+                    stack.push((x, y, z));
+                    let mut touched: Vec<FaceOrientation> = Vec::with_capacity(6);
+                    while let Some((x, y, z)) = stack.pop() {
+                        for &ort in FaceOrientation::ALL.iter() {
+                            let (dx, dy, dz) = ort.offset();
+                            let (nx, ny, nz) = (x as i32 + dx, y as i32 + dy, z as i32 + dz);
+                            if nx < 0 || ny < 0 || nz < 0 || nx >= CHUNK_WIDTH as i32 || ny >= SECTION_HEIGHT as i32 || nz >= CHUNK_DEPTH as i32 {
+                                if !touched.contains(&ort) {
+                                    touched.push(ort);
+                                }
+                                continue;
+                            }
+                            let (nx, ny, nz) = (nx as usize, ny as usize, nz as usize);
+                            if !visited[index(nx, ny, nz)] && chunk.get_block(nx, from_y + ny, nz).show_neighboring_faces() {
+                                visited[index(nx, ny, nz)] = true;
+                                stack.push((nx, ny, nz));
+                            }
+                        }
+                    }
+                    for &a in touched.iter() {
+                        for &b in touched.iter() {
+                            visibility.connect(a, b);
+                        }
+                    }
+                }
+            }
+        }
+        visibility
+    }
+}
+
+/**The section that is next to the given one in the direction of the face, if it is loaded*/
+fn neighbour(faces: &WorldFaces, ((chunk_x, chunk_z), section): SectionPos, ort: FaceOrientation) -> Option<SectionPos> {
+    let (dx, dy, dz) = ort.offset();
+    let section = section as i32 + dy;
+    if section < 0 || section >= SECTIONS as i32 {
+        return None;
+    }
+    let chunk_pos = (chunk_x + dx, chunk_z + dz);
+    if faces.contains_key(&chunk_pos) {
+        Some((chunk_pos, section as usize))
+    } else {
+        None
+    }
+}
+
+/**Sections that might be visible from the camera. Starting at the section of the camera, the search
+only leaves a section through a face that is connected to the face it came in through, and never turns back
+towards the camera. Sections rejected by in_view (such as those outside of the frustum) are not entered.
+If the camera is in a chunk that is not loaded, all sections accepted by in_view are returned*/
+pub(crate) fn visible_sections(faces: &WorldFaces, camera: &glm::Vec3, in_view: impl Fn(SectionPos) -> bool) -> Vec<SectionPos> {
+    let start_chunk = block_pos_into_chunk_pos(camera.x.floor() as i32, camera.z.floor() as i32);
+    if !faces.contains_key(&start_chunk) {
+        return faces.keys()
+            .flat_map(|&chunk_pos| (0..SECTIONS).map(move |section| (chunk_pos, section)))
+            .filter(|&pos| in_view(pos))
+            .collect();
+    }
+    let start_section = (camera.y.floor() as i32).max(0).min(CHUNK_HEIGHT as i32 - 1) as usize / SECTION_HEIGHT;
+    let start = (start_chunk, start_section);
+    let mut visible = Vec::new();
+    let mut visited = HashSet::new();
+    // section, face through which it was entered and directions travelled so far (one bit per FaceOrientation)
+    let mut queue: VecDeque<(SectionPos, Option<FaceOrientation>, u8)> = VecDeque::new();
+    visited.insert(start);
+    queue.push_back((start, None, 0));
+    while let Some((pos, entered_through, directions)) = queue.pop_front() {
+        visible.push(pos);
+        let visibility = faces[&pos.0].visibility(pos.1);
+        for &ort in FaceOrientation::ALL.iter() {
+            if directions & (1 << ort.opposite() as u8) != 0 {
+                continue;
+            }
+            if let Some(entered_through) = entered_through {
+                if !visibility.connects(entered_through, ort) {
+                    continue;
+                }
+            }
+            if let Some(next) = neighbour(faces, pos, ort) {
+                if !visited.contains(&next) && in_view(next) {
+                    visited.insert(next);
+                    queue.push_back((next, Some(ort.opposite()), directions | (1 << ort as u8)));
+                }
+            }
+        }
+    }
+    visible
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::block::Block;
+    use crate::blocks::block_properties::STONE;
+    use crate::blocks::block_registry::init_for_tests;
+    use FaceOrientation::*;
+
+    #[test]
+    fn empty_and_solid_sections() {
+        init_for_tests();
+        let mut chunk = ChunkBlocks::new();
+        assert_eq!(SectionVisibility::compute(&chunk, 1), SectionVisibility::ALL);
+        chunk.fill_level(SECTION_HEIGHT, SECTION_HEIGHT, STONE);
+        assert_eq!(SectionVisibility::compute(&chunk, 1), SectionVisibility::NONE);
+    }
+
+    #[test]
+    fn floor_separates_top_from_bottom() {
+        init_for_tests();
+        let mut chunk = ChunkBlocks::new();
+        chunk.fill_level(SECTION_HEIGHT / 2, 1, STONE);
+        let visibility = SectionVisibility::compute(&chunk, 0);
+        assert!(!visibility.connects(YPlus, YMinus));
+        assert!(visibility.connects(YPlus, XPlus));
+        assert!(visibility.connects(YMinus, ZMinus));
+        assert!(visibility.connects(XPlus, XMinus));
+        assert!(visibility.connects(ZMinus, XPlus));
+    }
+
+    #[test]
+    fn vertical_shaft_connects_only_top_and_bottom() {
+        init_for_tests();
+        let mut chunk = ChunkBlocks::new();
+        chunk.fill_level(0, SECTION_HEIGHT, STONE);
+        for y in 0..SECTION_HEIGHT {
+            chunk.set_block(5, y, 5, Block::air());
+        }
+        let visibility = SectionVisibility::compute(&chunk, 0);
+        assert!(visibility.connects(YPlus, YMinus));
+        assert!(visibility.connects(YMinus, YPlus));
+        for &ort in [XPlus, XMinus, ZPlus, ZMinus].iter() {
+            assert!(!visibility.connects(YPlus, ort));
+            assert!(!visibility.connects(ort, ort.opposite()));
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashSet};
+use std::time::Duration;
+use crate::blocks::chunk_coords::{ChunkPos, CHUNK_WIDTH, CHUNK_DEPTH, CHUNK_HEIGHT, chunk_pos_into_block_pos};
+use crate::worldgen::noise::Rng;
+
+/**Default length of a single tick. Simulation runs at the same speed regardless of the frame rate*/
+pub const TICK_LENGTH: Duration = Duration::from_millis(50);
+/**Every loaded chunk receives this many random ticks per tick, so that on average
+each block is ticked once every CHUNK_VOLUME / RANDOM_TICKS_PER_CHUNK ticks*/
+pub const RANDOM_TICKS_PER_CHUNK: u32 = 48;
+/**Never run more ticks than this at once, so that a long frame doesn't freeze the game*/
+const MAX_TICKS_PER_UPDATE: u64 = 10;
+
+/**Keeps simulation time of blocks. Blocks can ask for a scheduled tick at their position after a given
+number of ticks (which is how fluids and falling blocks react to changes around them), while random ticks
+hit random blocks of every loaded chunk (which is how slow processes, such as plant growth, happen).
+What a tick does is decided by World, based on the block found at the ticked position.*/
+pub struct TickScheduler {
+    tick_length: Duration,
+    /**Time that passed, but was too short to make another tick*/
+    leftover: Duration,
+    tick: u64,
+    queue: BinaryHeap<Reverse<(u64, (i32, i32, i32))>>,
+    scheduled: HashSet<(i32, i32, i32)>,
+    random_ticks_per_chunk: u32,
+    rng: Rng,
+}
+
+impl TickScheduler {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            tick_length: TICK_LENGTH,
+            leftover: Duration::default(),
+            tick: 0,
+            queue: BinaryHeap::new(),
+            scheduled: HashSet::new(),
+            random_ticks_per_chunk: RANDOM_TICKS_PER_CHUNK,
+            rng: Rng::new(seed),
+        }
+    }
+    /**Number of ticks since the scheduler was created*/
+    pub fn current_tick(&self) -> u64 {
+        self.tick
+    }
+    pub fn tick_length(&self) -> Duration {
+        self.tick_length
+    }
+    pub fn set_tick_length(&mut self, tick_length: Duration) {
+        self.tick_length = tick_length
+    }
+    pub fn random_ticks_per_chunk(&self) -> u32 {
+        self.random_ticks_per_chunk
+    }
+    pub fn set_random_ticks_per_chunk(&mut self, random_ticks_per_chunk: u32) {
+        self.random_ticks_per_chunk = random_ticks_per_chunk
+    }
+    /**Number of positions waiting for a scheduled tick*/
+    pub fn pending(&self) -> usize {
+        self.scheduled.len()
+    }
+    pub fn is_scheduled(&self, x: i32, y: i32, z: i32) -> bool {
+        self.scheduled.contains(&(x, y, z))
+    }
+    /**Asks for a tick at the position after the given number of ticks (at least 1).
+    If the position is already waiting for a tick, nothing happens*/
+    pub fn schedule(&mut self, x: i32, y: i32, z: i32, delay: u64) {
+        if self.scheduled.insert((x, y, z)) {
+            self.queue.push(Reverse((self.tick + delay.max(1), (x, y, z))));
+        }
+    }
+    /**Returns the number of ticks that should be simulated after the given time has passed*/
+    pub fn advance(&mut self, elapsed: Duration) -> u64 {
+        self.leftover += elapsed;
+        let ticks = (self.leftover.as_nanos() / self.tick_length.as_nanos().max(1)) as u64;
+        self.leftover -= self.tick_length * ticks as u32;
+        ticks.min(MAX_TICKS_PER_UPDATE)
+    }
+    /**Starts the next tick and returns all positions whose scheduled tick is due*/
+    pub fn next_tick(&mut self) -> Vec<(i32, i32, i32)> {
+        self.tick += 1;
+        let mut due = Vec::new();
+        while let Some(&Reverse((tick, pos))) = self.queue.peek() {
+            if tick > self.tick {
+                break;
+            }
+            self.queue.pop();
+            self.scheduled.remove(&pos);
+            due.push(pos);
+        }
+        due
+    }
+    /**Random positions within the given chunks that receive a random tick in the current tick*/
+    pub fn random_ticks<'a>(&mut self, chunks: impl IntoIterator<Item=&'a ChunkPos>) -> Vec<(i32, i32, i32)> {
+        let mut positions = Vec::new();
+        for &(chunk_x, chunk_z) in chunks {
+            let (from_x, from_z) = chunk_pos_into_block_pos(chunk_x, chunk_z);
+            for _ in 0..self.random_ticks_per_chunk {
+                positions.push((
+                    from_x + self.rng.range(0, CHUNK_WIDTH as i32),
+                    self.rng.range(0, CHUNK_HEIGHT as i32),
+                    from_z + self.rng.range(0, CHUNK_DEPTH as i32),
+                ));
+            }
+        }
+        positions
+    }
+    pub fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_that_is_too_short_for_a_tick_is_kept_for_later() {
+        let mut ticks = TickScheduler::new(1);
+        assert_eq!(ticks.advance(Duration::from_millis(120)), 2);
+        assert_eq!(ticks.advance(Duration::from_millis(20)), 0);
+        // 20ms left from the first call, 20ms from the second and 10ms now
+        assert_eq!(ticks.advance(Duration::from_millis(10)), 1);
+        assert_eq!(ticks.advance(Duration::from_millis(49)), 0);
+    }
+
+    #[test]
+    fn long_frames_are_clamped_and_not_caught_up_later() {
+        let mut ticks = TickScheduler::new(1);
+        assert_eq!(ticks.advance(Duration::from_secs(2)), MAX_TICKS_PER_UPDATE);
+        assert_eq!(ticks.advance(Duration::from_millis(0)), 0);
+        assert_eq!(ticks.advance(Duration::from_millis(50)), 1);
+    }
+
+    #[test]
+    fn scheduled_ticks_are_due_in_order() {
+        let mut ticks = TickScheduler::new(1);
+        ticks.schedule(0, 0, 0, 3);
+        ticks.schedule(1, 0, 0, 1);
+        ticks.schedule(2, 0, 0, 2);
+        assert_eq!(ticks.pending(), 3);
+        assert_eq!(ticks.next_tick(), vec![(1, 0, 0)]);
+        assert_eq!(ticks.next_tick(), vec![(2, 0, 0)]);
+        assert!(ticks.is_scheduled(0, 0, 0));
+        assert_eq!(ticks.next_tick(), vec![(0, 0, 0)]);
+        assert_eq!(ticks.pending(), 0);
+        assert!(ticks.next_tick().is_empty());
+        assert_eq!(ticks.current_tick(), 4);
+    }
+
+    #[test]
+    fn position_is_scheduled_only_once() {
+        let mut ticks = TickScheduler::new(1);
+        ticks.schedule(5, 6, 7, 2);
+        ticks.schedule(5, 6, 7, 1);
+        assert_eq!(ticks.pending(), 1);
+        // the first request wins
+        assert!(ticks.next_tick().is_empty());
+        assert_eq!(ticks.next_tick(), vec![(5, 6, 7)]);
+        // once due, the position can be scheduled again
+        ticks.schedule(5, 6, 7, 1);
+        assert_eq!(ticks.next_tick(), vec![(5, 6, 7)]);
+    }
+
+    #[test]
+    fn zero_delay_waits_for_the_next_tick() {
+        let mut ticks = TickScheduler::new(1);
+        ticks.schedule(0, 0, 0, 0);
+        assert!(ticks.is_scheduled(0, 0, 0));
+        assert_eq!(ticks.next_tick(), vec![(0, 0, 0)]);
+    }
+
+    #[test]
+    fn random_ticks_stay_inside_their_chunks() {
+        let mut ticks = TickScheduler::new(1);
+        ticks.set_random_ticks_per_chunk(100);
+        let chunks = [(0, 0), (-1, 2)];
+        let positions = ticks.random_ticks(chunks.iter());
+        assert_eq!(positions.len(), 200);
+        for (i, &(x, y, z)) in positions.iter().enumerate() {
+            let (from_x, from_z) = chunk_pos_into_block_pos(chunks[i / 100].0, chunks[i / 100].1);
+            assert!(from_x <= x && x < from_x + CHUNK_WIDTH as i32);
+            assert!(from_z <= z && z < from_z + CHUNK_DEPTH as i32);
+            assert!(0 <= y && y < CHUNK_HEIGHT as i32);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::sync::{Arc, Mutex, mpsc};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use crate::blocks::chunk_blocks::ChunkBlocks;
+use crate::blocks::chunk_coords::ChunkPos;
+use crate::blocks::chunk_generator::ChunkGenerator;
+use crate::blocks::chunk_mesh::ChunkMesh;
+use crate::blocks::greedy_mesher::MeshingMode;
+use crate::blocks::world_blocks::WorldBlocks;
+
+pub(crate) enum Job {
+    Generate { chunk_pos: ChunkPos },
+    /**Blocks hold copies of the chunk and its loaded neighbours taken when the job was submitted*/
+    Mesh { id: u64, chunk_pos: ChunkPos, blocks: WorldBlocks, meshing_mode: MeshingMode },
+}
+
+pub(crate) enum JobResult {
+    Generated { chunk_pos: ChunkPos, chunk: ChunkBlocks },
+    /**Revisions of the copied chunks, which tell if the mesh is already out of date*/
+    Meshed { id: u64, chunk_pos: ChunkPos, mesh: ChunkMesh, revisions: Vec<(ChunkPos, u64)> },
+}
+
+/**Threads that generate chunks and build their faces in the background. Jobs are taken from a shared queue
+in the order they were submitted and results are collected on the GL thread with try_recv.
+Dropping the workers lets the threads finish the jobs they are working on (queued jobs are skipped) and waits for them*/
+pub struct Workers {
+    jobs: Option<mpsc::Sender<Job>>,
+    stopped: Arc<AtomicBool>,
+    results: mpsc::Receiver<JobResult>,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+impl Workers {
+    pub fn new(thread_count: usize, generator: Arc<dyn ChunkGenerator>) -> Self {
+        let (jobs, job_queue) = mpsc::channel::<Job>();
+        let job_queue = Arc::new(Mutex::new(job_queue));
+        let (result_sender, results) = mpsc::channel();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let threads = (0..thread_count.max(1)).map(|i| {
+            let job_queue = job_queue.clone();
+            let result_sender = result_sender.clone();
+            let generator = generator.clone();
+            let stopped = stopped.clone();
+            thread::Builder::new().name(format!("chunk worker {}", i)).spawn(move || loop {
+                // the lock is released as soon as a job is taken, so other workers can take the next one
+                let job = match job_queue.lock().unwrap().recv() {
+                    Ok(job) => job,
+                    Err(_) => return
+                };
+                if stopped.load(Ordering::Relaxed) {
+                    return;
+                }
+                let result = match job {
+                    Job::Generate { chunk_pos } => {
+                        let mut chunk = ChunkBlocks::new();
+                        generator.generate(chunk_pos.0, chunk_pos.1, &mut chunk);
+                        JobResult::Generated { chunk_pos, chunk }
+                    }
+                    Job::Mesh { id, chunk_pos, blocks, meshing_mode } => {
+                        let mesh = ChunkMesh::build(&blocks, chunk_pos, meshing_mode);
+                        let revisions = blocks.chunks().map(|(&pos, chunk)| (pos, chunk.revision())).collect();
+                        JobResult::Meshed { id, chunk_pos, mesh, revisions }
+                    }
+                };
+                if result_sender.send(result).is_err() {
+                    return;
+                }
+            }).expect("Failed to spawn chunk worker")
+        }).collect();
+        Self { jobs: Some(jobs), stopped, results, threads }
+    }
+    pub(crate) fn submit(&self, job: Job) {
+        self.jobs.as_ref().unwrap().send(job).expect("Chunk workers have stopped")
+    }
+    /**Returns a finished job if there is any, without waiting*/
+    pub(crate) fn try_recv(&self) -> Option<JobResult> {
+        self.results.try_recv().ok()
+    }
+}
+
+impl Drop for Workers {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        // closing the queue wakes up workers that wait for jobs
+        self.jobs = None;
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
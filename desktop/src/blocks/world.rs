@@ -1,23 +1,528 @@
 use crate::blocks::world_blocks::WorldBlocks;
 use crate::blocks::chunk_faces::{ChunkFaces};
-use crate::blocks::world_size::{WorldSize, CHUNK_WIDTH, CHUNK_DEPTH, CHUNK_HEIGHT};
-use crate::render_gl::shader::UniformVec3fv;
+use crate::blocks::chunk_coords::{ChunkPos, CHUNK_WIDTH, CHUNK_DEPTH, for_each_neighbour, for_each_neighbouring_chunk, chunk_pos_into_block_pos, block_pos_into_chunk_pos, chunk_distance, SECTION_HEIGHT, SECTIONS};
+use crate::render_gl::shader::{UniformVec3fv, Uniform1f};
 use crate::render_gl::Program;
+use crate::render_gl::camera::Camera;
 use crate::blocks::block::Block;
-use crate::blocks::raycast::ray_cast;
+use crate::blocks::block_state::BlockState;
+use crate::blocks::raycast::{ray_cast, ray_aabb, entry_face, RaycastHit, RayTarget};
 use crate::blocks::world_faces::WorldFaces;
+use crate::blocks::entities::Entities;
+use crate::blocks::persistence;
+use crate::blocks::persistence::{PlayerPose, BlockTable};
+use crate::blocks::chunk_generator::ChunkGenerator;
+use crate::blocks::chunk_blocks::ChunkBlocks;
+use crate::blocks::schematic::Schematic;
+use crate::blocks::region::{self, Region};
+use crate::blocks::light;
+use crate::blocks::chunk_mesh::{ChunkMesh, face_shading};
+use crate::blocks::workers::{Workers, Job, JobResult};
+use crate::blocks::face_orientation::FaceOrientation;
+use crate::blocks::face::Face;
+use crate::blocks::section_visibility::{self, SectionVisibility, SectionPos};
+use crate::blocks::greedy_mesher::{MeshingMode, MeshStats};
+use crate::blocks::fluids::Fluids;
+use crate::blocks::falling_blocks::FallingBlocks;
+use crate::blocks::explosions::{self, Explosions, TNT_FUSE};
+use crate::blocks::block_properties::{TNT, GRASS, DIRT, SAPLING, SPAWNER};
+use crate::blocks::ticks::TickScheduler;
+use crate::blocks::history::History;
+use crate::blocks::light::LightChannel;
+use crate::blocks::entities::{Entity, ZombieVariant};
+use crate::worldgen::trees::random_tree;
+use crate::physics::body::{PhysicsBody, PLAYER_HALF_WIDTH, PLAYER_HEIGHT, PLAYER_EYE_HEIGHT};
+use rand::random;
+use std::time::{Duration, Instant};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::path::{Path, PathBuf};
+
+/**Saplings turn into trees on the random tick after reaching this stage*/
+const MAX_GROWTH: u8 = 7;
+/**Spawners don't spawn more zombies if there are this many entities within SPAWNER_RANGE*/
+const SPAWNER_MAX_NEARBY: usize = 4;
+const SPAWNER_RANGE: f32 = 8.;
+/**Undo and redo of transactions with more edits than this rebuild whole chunks instead of updating faces block by block*/
+const BATCH_THRESHOLD: usize = 64;
+/**Transparent faces are sorted again once the camera moves this many blocks away from where they were last sorted*/
+pub const TRANSPARENCY_SORT_THRESHOLD: f32 = 1.;
+/**Fragments of opaque and cutout faces that are less opaque than this are discarded*/
+const CUTOUT_ALPHA: f32 = 0.5;
+
+/**Sections (with any faces) drawn and skipped in the last call to gl_draw*/
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CullStats {
+    pub drawn: usize,
+    /**Outside of the frustum*/
+    pub culled: usize,
+    /**Inside of the frustum, but hidden behind opaque blocks*/
+    pub occluded: usize,
+}
 
 pub struct World {
     blocks: WorldBlocks,
     faces: WorldFaces,
+    entities: Entities,
+    player: PlayerPose,
+    generator: Arc<dyn ChunkGenerator>,
+    /**Chunks within this (Chebyshev) distance from the player are kept loaded*/
+    load_radius: i32,
+    /**If set, chunks are read from this directory when they are loaded and written back when they are unloaded*/
+    save_path: Option<PathBuf>,
+    block_table: BlockTable,
+    meshing_mode: MeshingMode,
+    /**Time it took to build faces in the last call to compute_faces*/
+    last_rebuild_time: Duration,
+    ticks: TickScheduler,
+    history: History,
+    fluids: Fluids,
+    falling_blocks: FallingBlocks,
+    explosions: Explosions,
+    frustum_culling: bool,
+    cave_culling: bool,
+    cull_stats: CullStats,
+    /**Generation and meshing happen on the GL thread if there are no workers*/
+    workers: Option<Workers>,
+    /**Chunks being generated by workers*/
+    pending_generation: HashSet<ChunkPos>,
+    /**Id of the latest meshing job of each chunk whose faces are being built by workers. Results of older jobs are dropped*/
+    pending_meshes: HashMap<ChunkPos, u64>,
+    next_job_id: u64,
+    /**Chunk around which chunks were loaded the last time. Generated chunks too far from it are dropped*/
+    load_center: ChunkPos,
+    gl: gl::Gl,
 }
 
 impl World {
-    pub fn new(width: usize, depth: usize, gl: &gl::Gl)->Self{
-        let size = WorldSize::new(width,depth);
+    pub fn new(load_radius: i32, generator: Box<dyn ChunkGenerator>, gl: &gl::Gl)->Self{
         Self{
-            blocks: WorldBlocks::new(size),
-            faces: WorldFaces::new(size, gl)
+            blocks: WorldBlocks::new(),
+            faces: WorldFaces::new(),
+            entities: Entities::new(),
+            player: PlayerPose { location: glm::vec3(0., 0., 0.), rotation: glm::quat_identity() },
+            generator: Arc::from(generator),
+            load_radius,
+            save_path: None,
+            block_table: BlockTable::identity(),
+            meshing_mode: MeshingMode::PerFace,
+            last_rebuild_time: Duration::default(),
+            ticks: TickScheduler::new(random()),
+            history: History::new(),
+            fluids: Fluids::new(),
+            falling_blocks: FallingBlocks::new(),
+            explosions: Explosions::new(),
+            frustum_culling: true,
+            cave_culling: true,
+            cull_stats: CullStats::default(),
+            workers: None,
+            pending_generation: HashSet::new(),
+            pending_meshes: HashMap::new(),
+            next_job_id: 0,
+            load_center: (0, 0),
+            gl: gl.clone(),
+        }
+    }
+    /**Writes the header and all loaded chunks into directory at the given path. The directory is created if necessary.
+    Chunks that are not loaded are expected to be already saved there.*/
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), persistence::Error> {
+        let path = path.as_ref();
+        persistence::save_header(path, &self.block_table, &self.player, &self.entities)?;
+        for (&chunk_pos, chunk) in self.blocks.chunks() {
+            persistence::save_chunk(path, &self.block_table, chunk_pos, chunk)?;
+        }
+        Ok(())
+    }
+    /**Loads a world previously written by save. Only the header is read immediately.
+    Chunks are read lazily by update_loaded_chunks and those that are missing from the save are generated.*/
+    pub fn load(path: impl AsRef<Path>, load_radius: i32, generator: Box<dyn ChunkGenerator>, gl: &gl::Gl) -> Result<Self, persistence::Error> {
+        let path = path.as_ref();
+        let mut world = Self::new(load_radius, generator, gl);
+        let header = persistence::load_header(path, &mut world.entities)?;
+        world.player = header.player;
+        world.block_table = header.block_table;
+        world.save_path = Some(path.to_path_buf());
+        if let Some((width, depth)) = header.legacy_size {
+            for chunk_idx in 0..width * depth {
+                if let Some(chunk) = persistence::load_legacy_chunk(path, chunk_idx, &world.block_table)? {
+                    let chunk_pos = ((chunk_idx % width) as i32, (chunk_idx / width) as i32);
+                    world.insert_chunk(chunk_pos, chunk);
+                }
+            }
+            world.compute_faces();
+        }
+        Ok(world)
+    }
+    pub fn save_path(&self) -> Option<&Path> {
+        self.save_path.as_ref().map(|p| p.as_path())
+    }
+    pub fn set_save_path(&mut self, path: Option<PathBuf>) {
+        self.save_path = path
+    }
+    pub fn load_radius(&self) -> i32 {
+        self.load_radius
+    }
+    pub fn set_load_radius(&mut self, load_radius: i32) {
+        self.load_radius = load_radius
+    }
+    pub fn meshing_mode(&self) -> MeshingMode {
+        self.meshing_mode
+    }
+    /**Switching the mode rebuilds faces of all loaded chunks*/
+    pub fn set_meshing_mode(&mut self, meshing_mode: MeshingMode) {
+        if self.meshing_mode != meshing_mode {
+            self.meshing_mode = meshing_mode;
+            self.compute_faces();
+        }
+    }
+    pub fn mesh_stats(&self) -> MeshStats {
+        let instances = self.faces.values().map(|c| c.len_opaque() + c.len_transparent()).sum();
+        MeshStats { instances, vertices: instances * 6, rebuild_time: self.last_rebuild_time }
+    }
+    pub fn cull_stats(&self) -> CullStats {
+        self.cull_stats
+    }
+    pub fn frustum_culling(&self) -> bool {
+        self.frustum_culling
+    }
+    /**With culling disabled all chunks are drawn, which is useful for comparing performance*/
+    pub fn set_frustum_culling(&mut self, frustum_culling: bool) {
+        self.frustum_culling = frustum_culling
+    }
+    pub fn cave_culling(&self) -> bool {
+        self.cave_culling
+    }
+    /**Cave culling skips sections that can't be seen from the camera, because there is no see-through path to them*/
+    pub fn set_cave_culling(&mut self, cave_culling: bool) {
+        self.cave_culling = cave_culling
+    }
+    pub fn fluids(&self) -> &Fluids {
+        &self.fluids
+    }
+    pub fn fluids_mut(&mut self) -> &mut Fluids {
+        &mut self.fluids
+    }
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+    pub fn history_mut(&mut self) -> &mut History {
+        &mut self.history
+    }
+    /**Runs f as a single transaction, so that all edits made by it are undone and redone together*/
+    pub fn edit<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.history.begin();
+        let result = f(self);
+        self.history.commit();
+        result
+    }
+    /**Reverts the last transaction through the regular editing path. Blocks that are no longer loaded are skipped.
+    Returns false if there was nothing to undo*/
+    pub fn undo(&mut self) -> bool {
+        let edits = match self.history.pop_undo() {
+            Some(transaction) => transaction.edits().to_vec(),
+            None => return false
+        };
+        self.update_batch_or_blocks(edits.iter().rev().map(|edit| (edit.x, edit.y, edit.z, edit.old)).collect());
+        true
+    }
+    /**Applies the last undone transaction again. Returns false if there was nothing to redo*/
+    pub fn redo(&mut self) -> bool {
+        let edits = match self.history.pop_redo() {
+            Some(transaction) => transaction.edits().to_vec(),
+            None => return false
+        };
+        self.update_batch_or_blocks(edits.iter().map(|edit| (edit.x, edit.y, edit.z, edit.new)).collect());
+        true
+    }
+    /**Small edits are cheaper to apply block by block, while large ones are cheaper to apply by rebuilding whole chunks*/
+    fn update_batch_or_blocks(&mut self, changes: Vec<(i32, i32, i32, Block)>) {
+        assert!(!self.history.is_recording(), "undo and redo can't be part of a transaction");
+        if changes.len() > BATCH_THRESHOLD {
+            self.update_batch(changes);
+        } else {
+            for (x, y, z, block) in changes {
+                self.update_set_block_if_loaded(x, y, z, block);
+            }
+        }
+    }
+    pub fn ticks(&self) -> &TickScheduler {
+        &self.ticks
+    }
+    pub fn ticks_mut(&mut self) -> &mut TickScheduler {
+        &mut self.ticks
+    }
+    /**Runs as many ticks as fit in the elapsed time. Every tick first handles all due scheduled ticks
+    and then random ticks of all loaded chunks. Changes go through update_block, so faces
+    are updated incrementally and only chunks that actually changed are uploaded again.*/
+    pub fn update_ticks(&mut self, elapsed: Duration) {
+        for _ in 0..self.ticks.advance(elapsed) {
+            for (x, y, z) in self.ticks.next_tick() {
+                self.scheduled_tick(x, y, z);
+            }
+            let chunks: Vec<ChunkPos> = self.blocks.chunk_positions().cloned().collect();
+            for (x, y, z) in self.ticks.random_ticks(chunks.iter()) {
+                self.random_tick(x, y, z);
+            }
+        }
+    }
+    /**Blocks that react to changes in their neighbourhood (fluids and blocks with gravity) ask for a scheduled tick*/
+    fn schedule_around(&mut self, x: i32, y: i32, z: i32) {
+        self.schedule_tick(x, y, z);
+        for &ort in FaceOrientation::ALL.iter() {
+            let (dx, dy, dz) = ort.offset();
+            self.schedule_tick(x + dx, y + dy, z + dz);
+        }
+    }
+    fn schedule_tick(&mut self, x: i32, y: i32, z: i32) {
+        let block = match self.blocks.try_get_block(x, y, z) {
+            Some(&block) => block,
+            None => return
+        };
+        if let Some(kind) = self.fluids.kind_of(block) {
+            self.ticks.schedule(x, y, z, kind.delay);
+        } else if block.has_gravity() {
+            self.ticks.schedule(x, y, z, 1);
+        }
+    }
+    fn scheduled_tick(&mut self, x: i32, y: i32, z: i32) {
+        let block = match self.blocks.try_get_block(x, y, z) {
+            Some(&block) => block,
+            None => return
+        };
+        if self.fluids.is_fluid(block) {
+            for (x, y, z, block) in self.fluids.tick(&self.blocks, x, y, z) {
+                self.update_set_block_if_loaded(x, y, z, block);
+            }
+        } else if block.has_gravity() {
+            self.drop_if_unsupported(x, y, z);
+        }
+    }
+    fn update_set_block_if_loaded(&mut self, x: i32, y: i32, z: i32, block: Block) -> bool {
+        self.blocks.is_block_loaded(x, y, z) && self.update_block(x, y, z, move |b| {
+            if *b != block {
+                *b = block;
+                true
+            } else {
+                false
+            }
+        })
+    }
+    fn light_above(&self, x: i32, y: i32, z: i32) -> u8 {
+        let sky = light::light_at(&self.blocks, LightChannel::Sky, x, y + 1, z).unwrap_or(0);
+        let block = light::light_at(&self.blocks, LightChannel::Block, x, y + 1, z).unwrap_or(0);
+        sky.max(block)
+    }
+    /**Slow processes: grass spreads onto lit dirt and dies when covered, saplings grow into trees and spawners spawn zombies*/
+    fn random_tick(&mut self, x: i32, y: i32, z: i32) {
+        let block = match self.blocks.try_get_block(x, y, z) {
+            Some(&block) => block,
+            None => return
+        };
+        let covered = self.blocks.try_get_block(x, y + 1, z).map(|b| !b.is_transparent()).unwrap_or(false);
+        if block.is_same_kind(GRASS) {
+            if covered {
+                self.update_set_block_if_loaded(x, y, z, DIRT);
+                return;
+            }
+            let rng = self.ticks.rng();
+            let (nx, ny, nz) = (x + rng.range(-1, 2), y + rng.range(-3, 2), z + rng.range(-1, 2));
+            let target_uncovered = self.blocks.try_get_block(nx, ny + 1, nz).map(|b| b.is_transparent()).unwrap_or(true);
+            if self.blocks.try_get_block(nx, ny, nz).map(|b| b.is_same_kind(DIRT)).unwrap_or(false) && target_uncovered && self.light_above(nx, ny, nz) >= 4 {
+                self.update_set_block_if_loaded(nx, ny, nz, GRASS);
+            }
+        } else if block.is_same_kind(SAPLING) {
+            if self.light_above(x, y, z) < 9 {
+                return;
+            }
+            let growth = block.state().growth();
+            if growth < MAX_GROWTH {
+                self.update_set_block_if_loaded(x, y, z, block.with_state(block.state().with_growth(growth + 1)));
+            } else {
+                let tree = random_tree(self.ticks.rng());
+                self.update_remove_block(x, y, z);
+                self.update_place_schematic(x, y, z, &tree);
+            }
+        } else if block.is_same_kind(SPAWNER) {
+            self.spawner_tick(x, y, z);
+        }
+    }
+    /**Spawns a zombie on a free spot near the spawner, unless there are already enough of them around*/
+    fn spawner_tick(&mut self, x: i32, y: i32, z: i32) {
+        let center = glm::vec3(x as f32 + 0.5, y as f32, z as f32 + 0.5);
+        let nearby = (0..self.entities.len())
+            .filter(|&entity_id| glm::distance(self.entities.position(entity_id), &center) < SPAWNER_RANGE)
+            .count();
+        if nearby >= SPAWNER_MAX_NEARBY {
+            return;
+        }
+        let rng = self.ticks.rng();
+        let (sx, sy, sz) = (x + rng.range(-4, 5), y + rng.range(-1, 2), z + rng.range(-4, 5));
+        let variant = if rng.chance(0.5) { ZombieVariant::Zombie } else { ZombieVariant::Steve };
+        let angle = rng.next_f32() * std::f32::consts::PI * 2.;
+        let is_air = |y: i32| self.blocks.try_get_block(sx, y, sz).map(|b| !b.is_solid()).unwrap_or(false);
+        let is_floor = self.blocks.try_get_block(sx, sy - 1, sz).map(|b| b.is_solid()).unwrap_or(false);
+        if is_floor && is_air(sy) && is_air(sy + 1) {
+            let position = glm::vec3(sx as f32 + 0.5, sy as f32, sz as f32 + 0.5);
+            self.entities.push(Entity::Zombie(variant), &position, &glm::quat_angle_axis(angle, &glm::vec3(0., 1., 0.)));
+        }
+    }
+    pub fn falling_blocks(&self) -> &FallingBlocks {
+        &self.falling_blocks
+    }
+    /**Moves falling blocks and turns those that landed back into blocks at their landing cell
+    (see FallingBlocks::landing_cell). Returns the number of landed blocks*/
+    pub fn update_falling_blocks(&mut self, elapsed: Duration) -> usize {
+        let mut landed = 0;
+        for (x, y, z, block) in self.falling_blocks.update(&self.blocks, elapsed.as_secs_f32()) {
+            // falling blocks only land in loaded columns, so the only way to lose one is a column that is solid all the way up
+            if let Some(y) = FallingBlocks::landing_cell(&self.blocks, x, y, z) {
+                self.update_block(x, y, z, move |b| {
+                    *b = block;
+                    true
+                });
+                landed += 1;
+            }
+        }
+        landed
+    }
+    pub fn explosions(&self) -> &Explosions {
+        &self.explosions
+    }
+    /**Lights the fuse of TNT at the given position. Returns false if there is no TNT or it is already burning*/
+    pub fn ignite(&mut self, x: i32, y: i32, z: i32) -> bool {
+        match self.blocks.try_get_block(x, y, z) {
+            Some(block) if block.is_same_kind(TNT) => self.explosions.ignite(x, y, z, TNT_FUSE),
+            _ => false
+        }
+    }
+    /**Burns fuses of primed TNT and detonates those that ran out. Returns the number of explosions*/
+    pub fn update_explosions(&mut self, elapsed: Duration) -> usize {
+        let mut exploded = 0;
+        for tnt in self.explosions.update(elapsed.as_secs_f32()) {
+            // TNT could have been mined while burning
+            if self.blocks.try_get_block(tnt.x, tnt.y, tnt.z).map(|b| b.is_same_kind(TNT)).unwrap_or(false) {
+                self.explode(&tnt.center(), tnt.power, Some((tnt.x, tnt.y, tnt.z)));
+                exploded += 1;
+            }
+        }
+        exploded
+    }
+    /**Destroys blocks around the centre in a single batched edit, ignites TNT caught in the blast and pushes entities away.
+    If the explosion was caused by a TNT block, its position should be given, so that it is removed in the same batch.
+    Returns the number of destroyed blocks*/
+    pub fn explode(&mut self, center: &glm::Vec3, power: f32, source: Option<(i32, i32, i32)>) -> usize {
+        let blast = explosions::cast_blast(&self.blocks, center, power);
+        for &pos in blast.ignited.iter() {
+            if Some(pos) != source {
+                let (x, y, z) = pos;
+                self.explosions.ignite_chained(x, y, z);
+            }
+        }
+        explosions::knockback(&mut self.entities, center, power);
+        let changes = blast.destroyed.iter().chain(source.iter()).map(|&(x, y, z)| (x, y, z, Block::air()));
+        self.update_batch(changes.collect::<Vec<_>>())
+    }
+    /**Integrates velocities of entities pushed by external forces (such as explosions) and stops them at walls.
+    Entities that are standing still are left alone*/
+    pub fn update_entity_motion(&mut self, elapsed: Duration) {
+        let delta = elapsed.as_secs_f32();
+        for entity_id in 0..self.entities.len() {
+            let mut velocity = self.entities.velocity(entity_id).clone();
+            if velocity == glm::vec3(0., 0., 0.) {
+                continue;
+            }
+            // mobs are as big as the player
+            let mut body = PhysicsBody::new(self.entities.position(entity_id).clone(), PLAYER_HALF_WIDTH, PLAYER_HEIGHT, PLAYER_EYE_HEIGHT);
+            body.set_velocity(&velocity);
+            body.simulate(&self.blocks, delta);
+            velocity = body.velocity().clone();
+            if body.is_on_ground() {
+                // friction of the ground
+                velocity *= (1. - 8. * delta).max(0.);
+                if glm::length(&velocity) < 0.1 {
+                    velocity = glm::vec3(0., 0., 0.);
+                }
+            }
+            let rotation = self.entities.rotation(entity_id).clone();
+            self.entities.set_velocity(entity_id, &velocity);
+            self.entities.update(entity_id, body.position(), &rotation);
+        }
+    }
+    /**Applies many changes at once. Unlike update_block, faces are not updated block by block,
+    but every affected chunk is rebuilt once, which is much faster for large edits.
+    Changes of blocks that are not loaded are skipped. Returns the number of changed blocks*/
+    pub fn update_batch(&mut self, changes: impl IntoIterator<Item=(i32, i32, i32, Block)>) -> usize {
+        let mut to_rebuild = HashSet::new();
+        let mut changed = Vec::new();
+        for (x, y, z, block) in changes {
+            match self.blocks.try_get_block(x, y, z) {
+                Some(&old) if old != block => {}
+                _ => continue
+            }
+            let old = std::mem::replace(&mut *self.blocks.get_block_mut(x, y, z), block);
+            self.history.record(x, y, z, old, block);
+            to_rebuild.extend(light::update_light_at(&mut self.blocks, x, y, z));
+            // faces and ambient occlusion of neighbours in adjacent chunks depend on this block too
+            for dx in -1..=1 {
+                for dz in -1..=1 {
+                    to_rebuild.insert(block_pos_into_chunk_pos(x + dx, z + dz));
+                }
+            }
+            changed.push((x, y, z));
+        }
+        for chunk_pos in to_rebuild {
+            if self.blocks.is_chunk_loaded(chunk_pos) {
+                self.compute_chunk_faces(chunk_pos);
+            }
+        }
+        for &(x, y, z) in changed.iter() {
+            self.schedule_around(x, y, z);
+        }
+        changed.len()
+    }
+    /**Sets every block of the region. Returns the number of changed blocks*/
+    pub fn fill_region(&mut self, region: &Region, block: Block) -> usize {
+        self.update_batch(region.fill(block))
+    }
+    /**Sets the walls, floor and ceiling of the region and clears its inside*/
+    pub fn hollow_box(&mut self, region: &Region, block: Block) -> usize {
+        self.update_batch(region.hollow_box(block))
+    }
+    /**Turns every block of the same kind as old (in any state) into new*/
+    pub fn replace_in_region(&mut self, region: &Region, old: Block, new: Block) -> usize {
+        let changes = region.replace(&self.blocks, old, new);
+        self.update_batch(changes)
+    }
+    pub fn fill_sphere(&mut self, x: i32, y: i32, z: i32, radius: f32, block: Block, hollow: bool) -> usize {
+        self.update_batch(region::sphere(x, y, z, radius, block, hollow))
+    }
+    /**Copies the region into a schematic with origin at its minimal corner*/
+    pub fn copy_region(&self, region: &Region) -> Schematic {
+        region.copy(&self.blocks)
+    }
+    /**Same as update_place_schematic, but applied as a single batch*/
+    pub fn paste(&mut self, x: i32, y: i32, z: i32, schematic: &Schematic) -> usize {
+        let mut changes = Vec::with_capacity(schematic.width() * schematic.height() * schematic.depth());
+        schematic.for_each_placed(x, y, z, |x, y, z, block| changes.push((x, y, z, block)));
+        self.update_batch(changes)
+    }
+    /**Lights TNT hit by the ray. Returns false if the ray hit something else or nothing at all*/
+    pub fn ray_cast_ignite(&mut self, origin: &glm::Vec3, direction: &glm::Vec3, max_distance: f32) -> bool {
+        match self.raycast(origin, direction, max_distance, |b| !b.is_air()) {
+            Some(hit) => {
+                let (x, y, z) = hit.block_pos;
+                self.ignite(x, y, z)
+            }
+            None => false
+        }
+    }
+    /**Turns the block into a falling one if it has gravity and lost its support.
+    Removing it may in turn leave the block above unsupported*/
+    fn drop_if_unsupported(&mut self, x: i32, y: i32, z: i32) {
+        if FallingBlocks::is_unsupported(&self.blocks, x, y, z) {
+            let block = *self.blocks.get_block(x, y, z);
+            self.falling_blocks.spawn(block, x, y, z, &self.gl);
+            self.update_remove_block(x, y, z);
         }
     }
     pub fn blocks(&self) -> &WorldBlocks {
@@ -26,11 +531,200 @@ impl World {
     pub fn blocks_mut(&mut self) -> &mut WorldBlocks {
         &mut self.blocks
     }
-    pub fn size(&self) -> &WorldSize {
-        self.blocks.size()
+    pub fn entities(&self) -> &Entities {
+        &self.entities
+    }
+    pub fn entities_mut(&mut self) -> &mut Entities {
+        &mut self.entities
+    }
+    pub fn player(&self) -> &PlayerPose {
+        &self.player
+    }
+    pub fn player_mut(&mut self) -> &mut PlayerPose {
+        &mut self.player
     }
 
-    pub fn update_set_block(&mut self, x: usize, y: usize, z: usize, block: Block) {
+    fn insert_chunk(&mut self, chunk_pos: ChunkPos, chunk: ChunkBlocks) {
+        self.blocks.insert_chunk(chunk_pos, chunk);
+        self.faces.insert(chunk_pos, ChunkFaces::new(&self.gl));
+    }
+    /**Reads the chunk from save directory or generates it if it was never saved. Faces are not computed.
+    If there are workers, chunks that need to be generated are handed over to them and false is returned*/
+    fn load_chunk(&mut self, chunk_pos: ChunkPos) -> Result<bool, persistence::Error> {
+        let saved = match &self.save_path {
+            Some(path) => persistence::load_chunk(path, chunk_pos, &self.block_table)?,
+            None => None
+        };
+        let chunk = match (saved, &self.workers) {
+            (Some(chunk), _) => chunk,
+            (None, Some(workers)) => {
+                if self.pending_generation.insert(chunk_pos) {
+                    workers.submit(Job::Generate { chunk_pos });
+                }
+                return Ok(false);
+            }
+            (None, None) => {
+                let mut chunk = ChunkBlocks::new();
+                self.generator.generate(chunk_pos.0, chunk_pos.1, &mut chunk);
+                chunk
+            }
+        };
+        self.insert_chunk(chunk_pos, chunk);
+        Ok(true)
+    }
+    /**Writes the chunk to save directory (if there is one) and frees its blocks and faces.*/
+    fn unload_chunk(&mut self, chunk_pos: ChunkPos) -> Result<(), persistence::Error> {
+        if let Some(chunk) = self.blocks.remove_chunk(chunk_pos) {
+            self.faces.remove(&chunk_pos);
+            self.pending_meshes.remove(&chunk_pos);
+            if let Some(path) = &self.save_path {
+                persistence::save_chunk(path, &self.block_table, chunk_pos, &chunk)?;
+            }
+        }
+        Ok(())
+    }
+    /**Loads (at most max_new_chunks) missing chunks within load radius around the given point, starting from the nearest ones,
+    and unloads chunks that are further than load radius + 1. The margin of one chunk prevents
+    chunks from being repeatedly loaded and unloaded when player walks back and forth along a chunk border.
+    Light and faces of affected chunks and their neighbours are recomputed.
+    If there are workers, chunks that are being generated count as new chunks, faces are built in the background
+    and results of the jobs that finished in the meantime are collected first.*/
+    pub fn update_loaded_chunks(&mut self, center: &glm::Vec3, max_new_chunks: usize) -> Result<(), persistence::Error> {
+        let center = block_pos_into_chunk_pos(center[0].floor() as i32, center[2].floor() as i32);
+        self.load_center = center;
+        self.collect_finished_jobs();
+        let mut to_rebuild: Vec<ChunkPos> = Vec::new();
+        let to_unload: Vec<ChunkPos> = self.blocks.chunk_positions()
+            .filter(|&&chunk_pos| chunk_distance(chunk_pos, center) > self.load_radius + 1)
+            .cloned().collect();
+        for chunk_pos in to_unload {
+            self.unload_chunk(chunk_pos)?;
+            for_each_neighbouring_chunk(chunk_pos.0, chunk_pos.1, |n| to_rebuild.push(n));
+        }
+        let mut to_load = Vec::new();
+        for chunk_x in center.0 - self.load_radius..=center.0 + self.load_radius {
+            for chunk_z in center.1 - self.load_radius..=center.1 + self.load_radius {
+                if !self.blocks.is_chunk_loaded((chunk_x, chunk_z)) && !self.pending_generation.contains(&(chunk_x, chunk_z)) {
+                    to_load.push((chunk_x, chunk_z));
+                }
+            }
+        }
+        to_load.sort_by_key(|&(chunk_x, chunk_z)| {
+            let (dx, dz) = (chunk_x - center.0, chunk_z - center.1);
+            dx * dx + dz * dz
+        });
+        to_load.truncate(max_new_chunks.saturating_sub(self.pending_generation.len()));
+        let mut loaded = Vec::new();
+        for chunk_pos in to_load {
+            if self.load_chunk(chunk_pos)? {
+                loaded.push(chunk_pos);
+            }
+        }
+        self.light_and_rebuild(loaded, to_rebuild);
+        Ok(())
+    }
+    /**Computes light of newly loaded chunks and then faces of them, their neighbours and the other given chunks*/
+    fn light_and_rebuild(&mut self, loaded: Vec<ChunkPos>, mut to_rebuild: Vec<ChunkPos>) {
+        let mut to_relight = HashSet::new();
+        for &chunk_pos in &loaded {
+            to_relight.extend(light::compute_chunk_light(&mut self.blocks, chunk_pos));
+            to_rebuild.push(chunk_pos);
+            for_each_neighbouring_chunk(chunk_pos.0, chunk_pos.1, |n| to_rebuild.push(n));
+        }
+        to_rebuild.sort();
+        to_rebuild.dedup();
+        for &chunk_pos in &to_rebuild {
+            if self.blocks.is_chunk_loaded(chunk_pos) {
+                self.request_chunk_faces(chunk_pos);
+            }
+            to_relight.remove(&chunk_pos);
+        }
+        self.refresh_face_shading(to_relight, |_| true);
+    }
+    /**Starts threads that generate chunks and build their faces in the background. Blocks are still edited
+    and faces uploaded to GPU on the calling thread*/
+    pub fn start_workers(&mut self, thread_count: usize) {
+        self.workers = Some(Workers::new(thread_count, self.generator.clone()));
+    }
+    /**Waits for the jobs in progress to finish. Chunks that were being generated or meshed will be requested again*/
+    pub fn stop_workers(&mut self) {
+        self.workers = None;
+        self.pending_generation.clear();
+        let pending: Vec<ChunkPos> = self.pending_meshes.drain().map(|(chunk_pos, _)| chunk_pos).collect();
+        for chunk_pos in pending {
+            self.compute_chunk_faces(chunk_pos);
+        }
+    }
+    /**Stops the workers without waiting for the faces they were building and writes all loaded chunks
+    to the save directory, if there is one. Call it before the world is dropped, so that no edit is lost*/
+    pub fn shutdown(&mut self) -> Result<(), persistence::Error> {
+        self.workers = None;
+        self.pending_generation.clear();
+        self.pending_meshes.clear();
+        match self.save_path.clone() {
+            Some(path) => self.save(path),
+            None => Ok(())
+        }
+    }
+    pub fn workers(&self) -> Option<&Workers> {
+        self.workers.as_ref()
+    }
+    /**Number of chunks waiting to be generated and meshed by workers*/
+    pub fn pending_jobs(&self) -> (usize, usize) {
+        (self.pending_generation.len(), self.pending_meshes.len())
+    }
+    /**Builds faces of the chunk on a worker, if there are any, or right away otherwise*/
+    fn request_chunk_faces(&mut self, chunk_pos: ChunkPos) {
+        match &self.workers {
+            Some(workers) => {
+                let id = self.next_job_id;
+                self.next_job_id += 1;
+                workers.submit(Job::Mesh { id, chunk_pos, blocks: self.blocks.copy_neighbourhood(chunk_pos), meshing_mode: self.meshing_mode });
+                self.pending_meshes.insert(chunk_pos, id);
+            }
+            None => self.compute_chunk_faces(chunk_pos)
+        }
+    }
+    /**True if faces of any chunk touching the block are being built by workers. Such faces can't be updated incrementally*/
+    fn is_mesh_pending_around(&self, x: i32, z: i32) -> bool {
+        !self.pending_meshes.is_empty() && (-1..=1).any(|dx| (-1..=1).any(|dz| self.pending_meshes.contains_key(&block_pos_into_chunk_pos(x + dx, z + dz))))
+    }
+    /**Takes all results of finished jobs without waiting. Generated chunks are loaded (unless the player went too far away
+    in the meantime) and meshes replace faces of their chunks, provided that no block or light they depend on changed since.
+    Outdated meshes are requested again*/
+    fn collect_finished_jobs(&mut self) {
+        let mut loaded = Vec::new();
+        let mut outdated = Vec::new();
+        while let Some(result) = self.workers.as_ref().and_then(|workers| workers.try_recv()) {
+            match result {
+                JobResult::Generated { chunk_pos, chunk } => {
+                    self.pending_generation.remove(&chunk_pos);
+                    if chunk_distance(chunk_pos, self.load_center) <= self.load_radius && !self.blocks.is_chunk_loaded(chunk_pos) {
+                        self.insert_chunk(chunk_pos, chunk);
+                        loaded.push(chunk_pos);
+                    }
+                }
+                JobResult::Meshed { id, chunk_pos, mesh, revisions } => {
+                    if self.pending_meshes.get(&chunk_pos) != Some(&id) {
+                        continue;
+                    }
+                    let up_to_date = revisions.iter().all(|&(pos, revision)| self.blocks.get_chunk(pos).map(|c| c.revision()) == Some(revision));
+                    if up_to_date {
+                        self.pending_meshes.remove(&chunk_pos);
+                        self.faces.get_mut(&chunk_pos).unwrap().set_mesh(mesh);
+                    } else {
+                        outdated.push(chunk_pos);
+                    }
+                }
+            }
+        }
+        for chunk_pos in outdated {
+            self.request_chunk_faces(chunk_pos);
+        }
+        self.light_and_rebuild(loaded, Vec::new());
+    }
+
+    pub fn update_set_block(&mut self, x: i32, y: i32, z: i32, block: Block) {
         self.update_block(x, y, z, move |b| {
             *b = block;
             true
@@ -38,7 +732,7 @@ impl World {
     }
     /**Returns true if previously there was no block at this position and the placement was carried out.
     If there was already a block, then placing a different one is impossible nad function returns false*/
-    pub fn update_place_block(&mut self, x: usize, y: usize, z: usize, block: Block) -> bool {
+    pub fn update_place_block(&mut self, x: i32, y: i32, z: i32, block: Block) -> bool {
         self.update_block(x, y, z, move |b| {
             if b.is_air() {
                 *b = block;
@@ -50,7 +744,7 @@ impl World {
     }
     /**Returns true if previously there was block at this position and the removal was carried out.
     If there was no block, then no removal was necessary and function returns false*/
-    pub fn update_remove_block(&mut self, x: usize, y: usize, z: usize) -> bool {
+    pub fn update_remove_block(&mut self, x: i32, y: i32, z: i32) -> bool {
         self.update_block(x, y, z, move |b| {
             if !b.is_air() {
                 *b = Block::air();
@@ -60,27 +754,81 @@ impl World {
             }
         })
     }
+    /**Changes only the state of the block, keeping its kind. Returns false if the state was already the same*/
+    pub fn update_block_state(&mut self, x: i32, y: i32, z: i32, state: BlockState) -> bool {
+        self.update_block(x, y, z, move |b| {
+            if b.state() != state {
+                *b = b.with_state(state);
+                true
+            } else {
+                false
+            }
+        })
+    }
+    /**Stamps the schematic, so that its origin lands at (x,y,z), updating faces of every changed block.
+    Blocks falling into chunks that are not loaded are skipped. Returns the number of changed blocks.*/
+    pub fn update_place_schematic(&mut self, x: i32, y: i32, z: i32, schematic: &Schematic) -> usize {
+        let mut changed = 0;
+        schematic.for_each_placed(x, y, z, |x, y, z, block| {
+            if self.blocks.is_block_loaded(x, y, z) && self.update_block(x, y, z, move |b| {
+                if *b != block {
+                    *b = block;
+                    true
+                } else {
+                    false
+                }
+            }) {
+                changed += 1;
+            }
+        });
+        changed
+    }
     /**Updates block according to custom policy. Function f should return true if a block was changed and face update is necessary.
-    The result of this function is the same as the output of f.*/
-    pub fn update_block<F: Fn(&mut Block) -> bool>(&mut self, x: usize, y: usize, z: usize, f: F) -> bool {
-        let b = self.blocks_mut().get_block_mut(x, y, z);
+    The result of this function is the same as the output of f. Blocks that are not loaded are left alone and false is returned.*/
+    pub fn update_block<F: Fn(&mut Block) -> bool>(&mut self, x: i32, y: i32, z: i32, f: F) -> bool {
+        if !self.blocks.is_block_loaded(x, y, z) {
+            return false;
+        }
+        let old = *self.blocks.get_block(x, y, z);
+        let changed = if self.meshing_mode == MeshingMode::Greedy || self.is_mesh_pending_around(x, z) {
+            self.update_block_greedy(x, y, z, f)
+        } else {
+            self.update_block_per_face(x, y, z, f)
+        };
+        if changed {
+            self.history.record(x, y, z, old, *self.blocks.get_block(x, y, z));
+            // fluids and falling blocks react to every change in their neighbourhood
+            self.schedule_around(x, y, z);
+        }
+        changed
+    }
+    fn update_block_per_face<F: Fn(&mut Block) -> bool>(&mut self, x: i32, y: i32, z: i32, f: F) -> bool {
+        let mut b = self.blocks_mut().get_block_mut(x, y, z);
         let was_showing_neighboring_faces = b.show_neighboring_faces();
         let was_showing_my_faces = b.show_my_faces();
-        let was_transparent = b.is_transparent();
-        if f(b) {
+        let was_blended = b.is_blended();
+        let was_full_cube = b.is_full_cube();
+        if f(&mut b) {
             let is_showing_neighboring_faces = b.show_neighboring_faces();
             let is_showing_my_faces = b.show_my_faces();
-            let is_transparent = b.is_transparent();
-            let b = b.clone();//just to make borrow-checker happy
+            let is_blended = b.is_blended();
+            let is_full_cube = b.is_full_cube();
+            let b = b.commit();//writes the block back and releases the borrow of blocks
+            let relit = light::update_light_at(&mut self.blocks, x, y, z);
+            if was_showing_neighboring_faces != is_showing_neighboring_faces {
+                self.refresh_section_visibility(x, y, z);
+            }
+            // blocks that are not full cubes have all their faces, so the set of faces changes along with the shape
+            let keeps_faces = was_showing_my_faces && is_showing_my_faces && was_full_cube == is_full_cube;
             if was_showing_my_faces {
-                if is_showing_my_faces {
-                    if was_transparent == is_transparent {
+                if keeps_faces {
+                    if was_blended == is_blended {
                         self.faces.update_block_textures(x, y, z, b);
                     } else {
                         self.faces.change_block_textures(x, y, z, b);
                     }
                 } else {
-                    if was_transparent {
+                    if was_blended {
                         self.faces.remove_block_transparent(x, y, z);
                     } else {
                         self.faces.remove_block_opaque(x, y, z);
@@ -88,93 +836,287 @@ impl World {
                 }
             }
 
-            self.size().clone().for_each_neighbour(x, y, z, |neighbour_x, neighbour_y, neighbour_z, my_face| {
-                let &neighbour = self.blocks().get_block(neighbour_x, neighbour_y, neighbour_z);
+            for_each_neighbour(x, y, z, |neighbour_x, neighbour_y, neighbour_z, my_face| {
+                let neighbour = match self.blocks().try_get_block(neighbour_x, neighbour_y, neighbour_z) {
+                    Some(&neighbour) => neighbour,
+                    None => return
+                };
                 let neighbour_face = my_face.opposite();
 
-                if was_showing_neighboring_faces && !is_showing_neighboring_faces && neighbour.show_my_faces() {
-                    if neighbour.is_transparent() {
+                if was_showing_neighboring_faces && !is_showing_neighboring_faces && neighbour.show_my_faces() && neighbour.is_full_cube() {
+                    if neighbour.is_blended() {
                         self.faces.remove_transparent_block_face(neighbour_x, neighbour_y, neighbour_z, neighbour_face)
                     } else {
                         self.faces.remove_opaque_block_face(neighbour_x, neighbour_y, neighbour_z, neighbour_face)
                     }
                 }
-                if !was_showing_neighboring_faces && is_showing_neighboring_faces && neighbour.show_my_faces() {
-                    self.faces.push_block(neighbour_x, neighbour_y, neighbour_z, neighbour_face, neighbour);
+                if !was_showing_neighboring_faces && is_showing_neighboring_faces && neighbour.show_my_faces() && neighbour.is_full_cube() {
+                    let shading = face_shading(&self.blocks, neighbour_x, neighbour_y, neighbour_z, neighbour_face);
+                    self.faces.push_block(neighbour_x, neighbour_y, neighbour_z, neighbour_face, neighbour, shading);
                 }
-                if !was_showing_my_faces && is_showing_my_faces && neighbour.show_neighboring_faces() {
-                    self.faces.push_block(x, y, z, my_face, b);
+                if !keeps_faces && is_showing_my_faces && (neighbour.show_neighboring_faces() || !is_full_cube) {
+                    let shading = face_shading(&self.blocks, x, y, z, my_face);
+                    self.faces.push_block(x, y, z, my_face, b, shading);
                 }
             });
+            self.refresh_face_shading(relit, |_| true);
+            // Ambient occlusion of faces looking into the 3x3x3 neighbourhood of the changed block depends on it
+            for nx in x - 1..=x + 1 {
+                for ny in y - 1..=y + 1 {
+                    for nz in z - 1..=z + 1 {
+                        if self.blocks.try_get_block(nx, ny, nz).map(|b| b.show_my_faces()).unwrap_or(false) {
+                            for &ort in FaceOrientation::ALL.iter() {
+                                let shading = face_shading(&self.blocks, nx, ny, nz, ort);
+                                self.faces.refresh_face_shading(nx, ny, nz, ort, shading);
+                            }
+                        }
+                    }
+                }
+            }
             true
         } else {
             false
         }
     }
+    /**Merged faces (and faces that are still being built by workers) can't be edited individually,
+    so every chunk that could be affected by the change is rebuilt*/
+    fn update_block_greedy<F: Fn(&mut Block) -> bool>(&mut self, x: i32, y: i32, z: i32, f: F) -> bool {
+        if !f(&mut self.blocks.get_block_mut(x, y, z)) {
+            return false;
+        }
+        let mut to_rebuild = light::update_light_at(&mut self.blocks, x, y, z);
+        for dx in -1..=1 {
+            for dz in -1..=1 {
+                to_rebuild.insert(block_pos_into_chunk_pos(x + dx, z + dz));
+            }
+        }
+        for chunk_pos in to_rebuild {
+            if self.blocks.is_chunk_loaded(chunk_pos) {
+                self.compute_chunk_faces(chunk_pos);
+            }
+        }
+        true
+    }
+    /**Sorts transparent faces of every chunk back to front as seen from the camera. Chunks whose faces didn't change
+    are only sorted again once the camera moves further than TRANSPARENCY_SORT_THRESHOLD, and only their transparent faces
+    are uploaded again right away, so sorting doesn't use up the budget of gl_update_chunks. Faces of chunks that
+    changed are uploaded in the new order by gl_update_chunks. Call before gl_update_chunks*/
+    pub fn update_transparency_order(&mut self, camera_location: &glm::Vec3) {
+        for (&(x, z), chunk) in self.faces.iter_mut() {
+            let (x, z) = chunk_pos_into_block_pos(x, z);
+            if chunk.sort_transparent(&(camera_location - glm::vec3(x as f32, 0., z as f32)), TRANSPARENCY_SORT_THRESHOLD) {
+                chunk.gl_update_transparent_order();
+            }
+        }
+    }
+    /**Uploads faces of at most max_uploads chunks that changed, starting from those nearest to the center.
+    Returns the number of uploaded chunks. Limiting uploads keeps frames short while many chunks are streamed in*/
+    pub fn gl_update_chunks(&mut self, center: &glm::Vec3, max_uploads: usize) -> usize {
+        let center = block_pos_into_chunk_pos(center[0].floor() as i32, center[2].floor() as i32);
+        let mut to_upload: Vec<ChunkPos> = self.faces.iter()
+            .filter(|(_, chunk)| chunk.has_faces_to_update())
+            .map(|(&chunk_pos, _)| chunk_pos)
+            .collect();
+        to_upload.sort_by_key(|&(chunk_x, chunk_z)| {
+            let (dx, dz) = (chunk_x - center.0, chunk_z - center.1);
+            dx * dx + dz * dz
+        });
+        to_upload.truncate(max_uploads);
+        for chunk_pos in &to_upload {
+            let chunk = self.faces.get_mut(chunk_pos).unwrap();
+            chunk.gl_update_opaque();
+            chunk.gl_update_transparent();
+        }
+        to_upload.len()
+    }
     pub fn gl_update_all_chunks(&mut self) {
-        for chunk in self.faces.iter_mut() {
+        for chunk in self.faces.values_mut() {
             chunk.gl_update_opaque();
             chunk.gl_update_transparent();
         }
     }
-    pub fn gl_draw(&self, chunk_location_uniform: UniformVec3fv, shader: &Program) {
-        for (chunk_idx, chunk) in self.faces.iter().enumerate() {
-            assert!(chunk_idx < self.faces.len());
-            let (x, z) = self.size().chunk_idx_into_chunk_pos(chunk_idx);
-            shader.set_uniform_vec3fv(chunk_location_uniform, &[(x * CHUNK_WIDTH) as f32, 0., (z * CHUNK_DEPTH) as f32]);
-            chunk.gl_draw_opaque();
+    /**Draws sections of chunks that intersect the frustum of the camera and can be seen through see-through blocks
+    (either kind of culling can be disabled). Bounding boxes of sections only span the layers covered by faces of the chunk,
+    so they are culled also when looking above or below them. Opaque and cutout faces are drawn first, with fragments
+    below the alpha cutoff discarded. Blended faces follow, starting with the section furthest from the camera*/
+    pub fn gl_draw(&mut self, camera: &Camera, chunk_location_uniform: UniformVec3fv, alpha_cutoff_uniform: Uniform1f, shader: &Program) {
+        let Self { faces, falling_blocks, frustum_culling, cave_culling, .. } = self;
+        let frustum = camera.frustum();
+        let section_in_frustum = |(x, z): ChunkPos, from_y: usize, to_y: usize| {
+            let (x, z) = chunk_pos_into_block_pos(x, z);
+            let min = glm::vec3(x as f32, from_y as f32, z as f32);
+            let max = glm::vec3((x + CHUNK_WIDTH as i32) as f32, to_y as f32, (z + CHUNK_DEPTH as i32) as f32);
+            !*frustum_culling || frustum.intersects_aabb(&min, &max)
+        };
+        let in_frustum = |(chunk_pos, section): SectionPos| match faces[&chunk_pos].y_range() {
+            Some((from_y, to_y)) => {
+                let from_y = from_y.max(section * SECTION_HEIGHT);
+                let to_y = to_y.min((section + 1) * SECTION_HEIGHT);
+                from_y < to_y && section_in_frustum(chunk_pos, from_y, to_y)
+            }
+            None => false,
+        };
+        let has_faces = |&(chunk_pos, section): &SectionPos| faces[&chunk_pos].section_len(section) > 0;
+        let all: Vec<SectionPos> = faces.keys()
+            .flat_map(|&chunk_pos| (0..SECTIONS).map(move |section| (chunk_pos, section)))
+            .filter(has_faces)
+            .collect();
+        let in_view: Vec<SectionPos> = all.iter().cloned().filter(|&pos| in_frustum(pos)).collect();
+        let mut visible = if *cave_culling {
+            // the search also has to go through empty sections, so it tests whole sections against the frustum
+            let reachable = section_visibility::visible_sections(faces, camera.location(),
+                |(chunk_pos, section)| section_in_frustum(chunk_pos, section * SECTION_HEIGHT, (section + 1) * SECTION_HEIGHT));
+            reachable.into_iter().filter(|&pos| has_faces(&pos) && in_frustum(pos)).collect()
+        } else {
+            in_view.clone()
+        };
+        self.cull_stats = CullStats { drawn: visible.len(), culled: all.len() - in_view.len(), occluded: in_view.len() - visible.len() };
+        shader.set_uniform_1f(alpha_cutoff_uniform, CUTOUT_ALPHA);
+        for &((x, z), section) in visible.iter() {
+            let (block_x, block_z) = chunk_pos_into_block_pos(x, z);
+            shader.set_uniform_vec3fv(chunk_location_uniform, &[block_x as f32, 0., block_z as f32]);
+            faces[&(x, z)].gl_draw_opaque_section(section);
         }
-        for (chunk_idx, chunk) in self.faces.iter().enumerate() {
-            assert!(chunk_idx < self.faces.len());
-            let (x, z) = self.size().chunk_idx_into_chunk_pos(chunk_idx);
-            shader.set_uniform_vec3fv(chunk_location_uniform, &[(x * CHUNK_WIDTH) as f32, 0., (z * CHUNK_DEPTH) as f32]);
-            chunk.gl_draw_transparent();
+        falling_blocks.gl_draw(chunk_location_uniform, shader);
+        shader.set_uniform_1f(alpha_cutoff_uniform, 0.);
+        let distance = |&((x, z), section): &SectionPos| {
+            let (x, z) = chunk_pos_into_block_pos(x, z);
+            let centre = glm::vec3(x as f32 + CHUNK_WIDTH as f32 / 2., (section * SECTION_HEIGHT) as f32 + SECTION_HEIGHT as f32 / 2., z as f32 + CHUNK_DEPTH as f32 / 2.);
+            glm::distance2(&centre, camera.location())
+        };
+        visible.retain(|&(chunk_pos, section)| faces[&chunk_pos].section_len_transparent(section) > 0);
+        visible.sort_by(|a, b| distance(b).partial_cmp(&distance(a)).unwrap_or(std::cmp::Ordering::Equal));
+        for &((x, z), section) in visible.iter() {
+            let (block_x, block_z) = chunk_pos_into_block_pos(x, z);
+            shader.set_uniform_vec3fv(chunk_location_uniform, &[block_x as f32, 0., block_z as f32]);
+            faces[&(x, z)].gl_draw_transparent_section(section);
         }
     }
 
-    pub fn compute_faces(&mut self) {
-        for x in 0..self.size().world_width() {
-            for z in 0..self.size().world_depth() {
-                for y in 0..CHUNK_HEIGHT {
-                    let &block = self.blocks().get_block(x, y, z);
-                    if block.show_my_faces() {
-                        self.size().clone().for_each_neighbour(x, y, z, |neighbour_x, neighbour_y, neighbour_z, ort| {
-                            let neighbour = self.blocks().get_block(neighbour_x, neighbour_y, neighbour_z);
-                            if neighbour.show_neighboring_faces() {
-                                self.faces.push_block(x, y, z, ort, block);
-                            }
-                        });
-                    }
+    /**Discards all faces of the chunk and computes them from scratch. Faces between this chunk and
+    its neighbours are only shown if the neighbour is loaded. Any meshing job of the chunk becomes obsolete*/
+    pub fn compute_chunk_faces(&mut self, chunk_pos: ChunkPos) {
+        let mesh = ChunkMesh::build(&self.blocks, chunk_pos, self.meshing_mode);
+        self.faces.get_mut(&chunk_pos).unwrap().set_mesh(mesh);
+        self.pending_meshes.remove(&chunk_pos);
+    }
+    /**Blocks that stopped or started showing faces of their neighbours can open or close paths for cave culling*/
+    fn refresh_section_visibility(&mut self, x: i32, y: i32, z: i32) {
+        let section = y as usize / SECTION_HEIGHT;
+        let visibility = SectionVisibility::compute(self.blocks.get_chunk(block_pos_into_chunk_pos(x, z)).unwrap(), section);
+        self.faces.get_chunk_mut(x, z).set_visibility(section, visibility);
+    }
+
+    /**Recomputes shading of faces in the given chunks that belong to blocks accepted by the filter (which receives absolute block position)*/
+    fn refresh_face_shading(&mut self, chunks: impl IntoIterator<Item=ChunkPos>, filter: impl Fn((i32, i32, i32)) -> bool) {
+        if self.meshing_mode == MeshingMode::Greedy {
+            // Merged faces may no longer share the same shading, hence they need to be built again
+            for chunk_pos in chunks {
+                if self.blocks.is_chunk_loaded(chunk_pos) {
+                    self.compute_chunk_faces(chunk_pos);
                 }
             }
+            return;
         }
-    }
-
-    pub fn ray_cast_place_block(&mut self, start: &[f32], distance_and_direction: &[f32], block: Block) {
-        ray_cast(start, distance_and_direction, |block_x, block_y, block_z, adjacent_x, adjacent_y, adjacent_z| {
-            if self.size().is_point_in_bounds(block_x, block_y, block_z) && !self.blocks().get_block(block_x as usize, block_y as usize, block_z as usize).is_air() {
-                if block_x != adjacent_x || block_y != adjacent_y || block_z != adjacent_z {
-                    let adjacent_y = adjacent_y as usize;
-                    if adjacent_y < CHUNK_HEIGHT {//we don't need to test other coordinates because
-                        // normally it should be impossible for a player to reach them
-                        self.update_place_block(adjacent_x as usize, adjacent_y, adjacent_z as usize, block);
+        let Self { blocks, faces, .. } = self;
+        for chunk_pos in chunks {
+            if let Some(chunk_faces) = faces.get_mut(&chunk_pos) {
+                let (from_x, from_z) = chunk_pos_into_block_pos(chunk_pos.0, chunk_pos.1);
+                let block_pos = |face: &Face| (from_x + face.block_x() as i32, face.block_y() as i32, from_z + face.block_z() as i32);
+                chunk_faces.refresh_shading(|face| {
+                    let (x, y, z) = block_pos(face);
+                    if filter((x, y, z)) {
+                        Some(face_shading(blocks, x, y, z, face.block_orientation()))
+                    } else {
+                        None
                     }
-                }
-                Some(())
-            } else {
-                None
+                });
             }
-        });
+        }
+    }
+    /**Recomputes light and faces of all loaded chunks. Necessary after editing blocks with no_update_* functions.*/
+    pub fn compute_faces(&mut self) {
+        let chunks: Vec<ChunkPos> = self.blocks.chunk_positions().cloned().collect();
+        for &chunk_pos in &chunks {
+            light::compute_chunk_light(&mut self.blocks, chunk_pos);
+        }
+        let start = Instant::now();
+        for chunk_pos in chunks {
+            self.compute_chunk_faces(chunk_pos);
+        }
+        self.last_rebuild_time = start.elapsed();
     }
 
-    pub fn ray_cast_remove_block(&mut self, start: &[f32], distance_and_direction: &[f32]) {
-        ray_cast(start, distance_and_direction, |block_x, block_y, block_z, adjacent_x, adjacent_y, adjacent_z| {
-            if self.size().is_point_in_bounds(block_x, block_y, block_z) &&
-                self.update_remove_block(block_x as usize, block_y as usize, block_z as usize) {
-                Some(())
-            } else {
-                None
-            }
-        });
+    /**First block accepted by the filter along the ray, no further than max_distance from the origin.
+    Blocks that are not loaded are passed through*/
+    pub fn raycast(&self, origin: &glm::Vec3, direction: &glm::Vec3, max_distance: f32, mut filter: impl FnMut(Block) -> bool) -> Option<RaycastHit> {
+        let direction = glm::normalize(direction);
+        let blocks = &self.blocks;
+        ray_cast(origin.as_slice(), (direction * max_distance).as_slice(), |block_x, block_y, block_z, adjacent_x, adjacent_y, adjacent_z| {
+            let block_pos = (block_x as i32, block_y as i32, block_z as i32);
+            let block = match blocks.try_get_block(block_pos.0, block_pos.1, block_pos.2) {
+                Some(&block) if filter(block) => block,
+                _ => return None
+            };
+            let adjacent_pos = (adjacent_x as i32, adjacent_y as i32, adjacent_z as i32);
+            let face = FaceOrientation::from_offset((adjacent_pos.0 - block_pos.0, adjacent_pos.1 - block_pos.1, adjacent_pos.2 - block_pos.2))
+                .unwrap_or_else(|| entry_face(&direction));
+            let min = glm::vec3(block_x, block_y, block_z);
+            let distance = ray_aabb(origin, &direction, &min, &(min + glm::vec3(1., 1., 1.))).unwrap_or(0.);
+            Some(RaycastHit { block_pos, adjacent_pos, face, distance, block })
+        })
+    }
+    /**Whichever is closer: an entity or a block accepted by the filter*/
+    pub fn raycast_with_entities(&self, origin: &glm::Vec3, direction: &glm::Vec3, max_distance: f32, filter: impl FnMut(Block) -> bool) -> Option<RayTarget> {
+        let block_hit = self.raycast(origin, direction, max_distance, filter);
+        let entity_max_distance = block_hit.map(|hit| hit.distance).unwrap_or(max_distance);
+        match self.entities.ray_cast(origin, direction, entity_max_distance) {
+            Some(entity_hit) => Some(RayTarget::Entity(entity_hit)),
+            None => block_hit.map(RayTarget::Block)
+        }
+    }
+
+    /**Blocks the player can aim at. Fluids are looked through just like air*/
+    pub fn is_targetable(&self, block: Block) -> bool {
+        !block.is_air() && !self.fluids.is_fluid(block)
     }
-}
\ No newline at end of file
+
+    /**Oriented blocks are turned according to the face they are placed against and the direction of the ray.
+    Returns the block against which the new one was placed*/
+    pub fn ray_cast_place_block(&mut self, origin: &glm::Vec3, direction: &glm::Vec3, max_distance: f32, block: Block) -> Option<RaycastHit> {
+        let hit = self.raycast(origin, direction, max_distance, |b| self.is_targetable(b))?;
+        let (x, y, z) = hit.adjacent_pos;
+        if hit.block_pos != hit.adjacent_pos && self.blocks.is_block_loaded(x, y, z) {
+            self.update_place_block(x, y, z, block.placed(hit.face, direction.x, direction.z));
+        }
+        Some(hit)
+    }
+
+    /**Places the schematic with its origin at the empty position in front of the block hit by the ray*/
+    pub fn ray_cast_place_schematic(&mut self, origin: &glm::Vec3, direction: &glm::Vec3, max_distance: f32, schematic: &Schematic) -> Option<RaycastHit> {
+        let hit = self.raycast(origin, direction, max_distance, |b| !b.is_air())?;
+        let (x, y, z) = hit.adjacent_pos;
+        if hit.block_pos != hit.adjacent_pos {
+            self.update_place_schematic(x, y, z, schematic);
+        }
+        Some(hit)
+    }
+
+    /**Returns the removed block. Blocks with negative hardness are unbreakable and stay in place*/
+    pub fn ray_cast_remove_block(&mut self, origin: &glm::Vec3, direction: &glm::Vec3, max_distance: f32) -> Option<RaycastHit> {
+        let hit = self.raycast(origin, direction, max_distance, |b| self.is_targetable(b))?;
+        if hit.block.hardness() < 0. {
+            return None;
+        }
+        let (x, y, z) = hit.block_pos;
+        self.update_remove_block(x, y, z);
+        Some(hit)
+    }
+}
+
+impl Drop for World {
+    fn drop(&mut self) {
+        // worker threads are joined before the chunks and GL buffers go away
+        self.workers = None;
+    }
+}
@@ -1,75 +1,114 @@
-use crate::render_gl::data::{u8_u8_u8_u8};
-use crate::render_gl::data::VertexAttribPointers;
-use crate::render_gl::data::VertexAttrib;
-use crate::render_gl::util::init_array;
-use crate::blocks::block_properties::{BLOCKS, STONE};
-use std::fmt::{Display, Formatter};
-use crate::render_gl::buffer::{BufferDynamicDraw, DynamicBuffer};
-use crate::render_gl::instanced_logical_model::InstancedLogicalModel;
-use crate::render_gl::Program;
-use crate::render_gl::shader::UniformVec3fv;
+use std::collections::HashMap;
 use crate::blocks::block::Block;
-use crate::blocks::face_orientation::FaceOrientation;
-use crate::blocks::chunk_faces::ChunkFaces;
-use crate::blocks::world_size::WorldSize;
+use crate::blocks::chunk_blocks::{ChunkBlocks, BlockMut};
+use crate::blocks::schematic::Schematic;
+use crate::blocks::chunk_coords::{ChunkPos, block_pos_into_chunk_pos, chunk_pos_into_block_pos, absolute_block_to_chunk_block_position, is_height_in_bounds, CHUNK_HEIGHT};
 
 
 pub struct WorldBlocks{
-    blocks: Vec<Block>,
-    size:WorldSize
+    chunks: HashMap<ChunkPos, ChunkBlocks>,
 }
 
 
 impl WorldBlocks {
-    pub fn size(&self)->&WorldSize{
-        &self.size
+    pub fn new() -> Self {
+        Self { chunks: HashMap::new() }
     }
-    pub fn as_slice(&self)->&[Block]{
-        &self.blocks
+    pub fn chunks(&self) -> impl Iterator<Item=(&ChunkPos, &ChunkBlocks)> {
+        self.chunks.iter()
     }
-    pub fn get_block(&self, x: usize, y: usize, z: usize) -> &Block {
-        &self.blocks[self.size().block_pos_into_world_idx(x,y,z)]
+    pub fn chunk_positions(&self) -> impl Iterator<Item=&ChunkPos> {
+        self.chunks.keys()
     }
-    pub fn get_block_mut(&mut self, x: usize, y: usize, z: usize) -> &mut Block {
-        let idx = self.size().block_pos_into_world_idx(x,y,z);
-        &mut self.blocks[idx]
+    pub fn total_chunks(&self) -> usize {
+        self.chunks.len()
     }
-
-    pub fn new(size: WorldSize) -> Self {
-        Self { size, blocks:vec![Block::air();size.world_volume()] }
+    pub fn is_chunk_loaded(&self, chunk_pos: ChunkPos) -> bool {
+        self.chunks.contains_key(&chunk_pos)
+    }
+    pub fn is_block_loaded(&self, x: i32, y: i32, z: i32) -> bool {
+        is_height_in_bounds(y) && self.is_chunk_loaded(block_pos_into_chunk_pos(x, z))
+    }
+    pub fn get_chunk(&self, chunk_pos: ChunkPos) -> Option<&ChunkBlocks> {
+        self.chunks.get(&chunk_pos)
+    }
+    pub fn get_chunk_mut(&mut self, chunk_pos: ChunkPos) -> Option<&mut ChunkBlocks> {
+        self.chunks.get_mut(&chunk_pos)
+    }
+    /**Copies of the chunk and its loaded neighbours, which is everything needed to build faces of the chunk*/
+    pub(crate) fn copy_neighbourhood(&self, chunk_pos: ChunkPos) -> WorldBlocks {
+        let mut chunks = HashMap::new();
+        for chunk_x in chunk_pos.0 - 1..=chunk_pos.0 + 1 {
+            for chunk_z in chunk_pos.1 - 1..=chunk_pos.1 + 1 {
+                if let Some(chunk) = self.get_chunk((chunk_x, chunk_z)) {
+                    chunks.insert((chunk_x, chunk_z), chunk.clone());
+                }
+            }
+        }
+        WorldBlocks { chunks }
+    }
+    pub(crate) fn insert_chunk(&mut self, chunk_pos: ChunkPos, chunk: ChunkBlocks) {
+        let prev = self.chunks.insert(chunk_pos, chunk);
+        assert!(prev.is_none(), "Chunk {:?} was already loaded", chunk_pos);
+    }
+    pub(crate) fn remove_chunk(&mut self, chunk_pos: ChunkPos) -> Option<ChunkBlocks> {
+        self.chunks.remove(&chunk_pos)
+    }
+    /**Returns None if the block lies outside of world height or in a chunk that is not loaded*/
+    pub fn try_get_block(&self, x: i32, y: i32, z: i32) -> Option<&Block> {
+        if !is_height_in_bounds(y) {
+            return None;
+        }
+        let chunk = self.get_chunk(block_pos_into_chunk_pos(x, z))?;
+        let (x, y, z) = absolute_block_to_chunk_block_position(x, y, z);
+        Some(chunk.get_block(x as usize, y as usize, z as usize))
+    }
+    pub fn get_block(&self, x: i32, y: i32, z: i32) -> &Block {
+        self.try_get_block(x, y, z).unwrap_or_else(|| panic!("Block {},{},{} is not loaded", x, y, z))
+    }
+    /**The block is written back once the returned guard is dropped*/
+    pub fn get_block_mut(&mut self, x: i32, y: i32, z: i32) -> BlockMut<'_> {
+        let chunk_pos = block_pos_into_chunk_pos(x, z);
+        let chunk = self.get_chunk_mut(chunk_pos).unwrap_or_else(|| panic!("Chunk {:?} is not loaded", chunk_pos));
+        let (x, y, z) = absolute_block_to_chunk_block_position(x, y, z);
+        chunk.get_block_mut(x as usize, y as usize, z as usize)
     }
 
-    pub fn no_update_remove_block(&mut self, x: usize, y: usize, z: usize) {
+    pub fn no_update_remove_block(&mut self, x: i32, y: i32, z: i32) {
         self.no_update_set_block(x, y, z, Block::air())
     }
-    pub fn no_update_set_block(&mut self, x: usize, y: usize, z: usize, block: Block) {
-        *self.get_block_mut(x, y, z) = block
+    pub fn no_update_set_block(&mut self, x: i32, y: i32, z: i32, block: Block) {
+        let chunk_pos = block_pos_into_chunk_pos(x, z);
+        let chunk = self.get_chunk_mut(chunk_pos).unwrap_or_else(|| panic!("Chunk {:?} is not loaded", chunk_pos));
+        let (x, y, z) = absolute_block_to_chunk_block_position(x, y, z);
+        chunk.set_block(x as usize, y as usize, z as usize, block)
     }
-    pub fn no_update_fill(&mut self, from_x: usize, from_y: usize, from_z: usize, width: usize, height: usize, depth: usize, block: Block) {
-        for x in from_x..(from_x + width) {
-            for y in from_y..(from_y + height) {
-                for z in from_z..(from_z + depth) {
+    pub fn no_update_fill(&mut self, from_x: i32, from_y: i32, from_z: i32, width: usize, height: usize, depth: usize, block: Block) {
+        for x in from_x..(from_x + width as i32) {
+            for y in from_y..(from_y + height as i32) {
+                for z in from_z..(from_z + depth as i32) {
                     self.no_update_set_block(x, y, z, block);
                 }
             }
         }
     }
-    pub fn no_update_outline(&mut self, from_x: usize, from_y: usize, from_z: usize, width: usize, height: usize, depth: usize, block: Block) {
+    pub fn no_update_outline(&mut self, from_x: i32, from_y: i32, from_z: i32, width: usize, height: usize, depth: usize, block: Block) {
+        let (width_i, height_i, depth_i) = (width as i32, height as i32, depth as i32);
         self.no_update_fill(from_x, from_y, from_z,width, 1, depth, block);
         if height > 1 {
-            self.no_update_fill(from_x, from_y + height - 1, from_z , width, 1, depth, block);
+            self.no_update_fill(from_x, from_y + height_i - 1, from_z , width, 1, depth, block);
             if height > 2 {
-                for y in from_y+1..(from_y + height-1) {
+                for y in from_y+1..(from_y + height_i-1) {
                     if width>0 {
-                        for z in from_z..(from_z + depth) {
+                        for z in from_z..(from_z + depth_i) {
                             self.no_update_set_block(from_x, y, z, block);
-                            self.no_update_set_block(from_x + width - 1, y, z, block);
+                            self.no_update_set_block(from_x + width_i - 1, y, z, block);
                         }
                     }
                     if depth>2 {
-                        for x in from_x..(from_x + width) {
+                        for x in from_x..(from_x + width_i) {
                             self.no_update_set_block(x, y, from_z, block);
-                            self.no_update_set_block(x, y, from_z+depth-1, block);
+                            self.no_update_set_block(x, y, from_z+depth_i-1, block);
                         }
                     }
                 }
@@ -77,38 +116,50 @@ impl WorldBlocks {
         }
 
     }
+    /**Fills the level in all currently loaded chunks*/
     pub fn no_update_fill_level(&mut self, from_y: usize, height: usize, block: Block) {
-        self.no_update_fill(0, from_y, 0, self.size().world_width(), height, self.size().world_depth(), block)
-    }
-    pub fn no_update_replace(&mut self, from_x: usize, from_y: usize, from_z: usize, width: usize, height: usize, depth: usize, old_block: Block, new_block: Block) {
-        for x in from_x..(from_x + width) {
-            for y in from_y..(from_y + height) {
-                for z in from_z..(from_z + depth) {
-                    let b = self.get_block_mut(x, y, z);
-                    if b == &old_block {
-                        *b = new_block
+        for chunk in self.chunks.values_mut() {
+            chunk.fill_level(from_y, height, block)
+        }
+    }
+    pub fn no_update_replace(&mut self, from_x: i32, from_y: i32, from_z: i32, width: usize, height: usize, depth: usize, old_block: Block, new_block: Block) {
+        for x in from_x..(from_x + width as i32) {
+            for y in from_y..(from_y + height as i32) {
+                for z in from_z..(from_z + depth as i32) {
+                    if self.get_block(x, y, z) == &old_block {
+                        self.no_update_set_block(x, y, z, new_block)
                     }
                 }
             }
         }
     }
-    pub fn no_update_heightmap(&mut self, filler_block: Block, height_at: impl Fn(usize, usize) -> usize) {
-        for x in 0..self.size().world_width() {
-            for z in 0..self.size().world_depth() {
-                for y in 0..height_at(x, z) {
-                    self.no_update_set_block(x, y, z, filler_block)
-                }
+    /**Stamps the schematic, so that its origin lands at (x,y,z). Blocks falling into chunks that are not loaded are skipped.*/
+    pub fn no_update_place_schematic(&mut self, x: i32, y: i32, z: i32, schematic: &Schematic) {
+        schematic.for_each_placed(x, y, z, |x, y, z, block| {
+            if self.is_block_loaded(x, y, z) {
+                self.no_update_set_block(x, y, z, block)
             }
+        })
+    }
+    /**Fills columns of all currently loaded chunks. The closure receives absolute x and z coordinates.*/
+    pub fn no_update_heightmap(&mut self, filler_block: Block, height_at: impl Fn(i32, i32) -> usize) {
+        for (&(chunk_x, chunk_z), chunk) in self.chunks.iter_mut() {
+            let (from_x, from_z) = chunk_pos_into_block_pos(chunk_x, chunk_z);
+            chunk.fill_heightmap(filler_block, |x, z| height_at(from_x + x as i32, from_z + z as i32))
         }
     }
-    pub fn contains_solid_block(&self, from_x: usize, from_y: usize, from_z: usize, width: usize, height: usize, depth: usize) ->bool{
-        self.contains_solid_block_in(from_x,from_y,from_z,from_x+width,from_y+height,from_z+depth)
+    pub fn contains_solid_block(&self, from_x: i32, from_y: i32, from_z: i32, width: usize, height: usize, depth: usize) ->bool{
+        self.contains_solid_block_in(from_x,from_y,from_z,from_x+width as i32,from_y+height as i32,from_z+depth as i32)
     }
-    pub fn contains_solid_block_in(&self, from_x: usize, from_y: usize, from_z: usize, to_x: usize, to_y: usize, to_z: usize) ->bool{
+    /**Blocks below the world and in chunks that are not loaded are considered solid. Blocks above the world are air.*/
+    pub fn contains_solid_block_in(&self, from_x: i32, from_y: i32, from_z: i32, to_x: i32, to_y: i32, to_z: i32) ->bool{
         for x in from_x..to_x {
             for y in from_y..to_y {
+                if y >= CHUNK_HEIGHT as i32 {
+                    break;
+                }
                 for z in from_z..to_z {
-                    if self.get_block(x,y,z).is_solid(){
+                    if y < 0 || self.try_get_block(x,y,z).map(|b|b.is_solid()).unwrap_or(true){
                         return true;
                     }
                 }
@@ -116,28 +167,6 @@ impl WorldBlocks {
         }
         false
     }
-    pub fn hitbox_collision(&self,hitbox_from:&glm::Vec3,hitbox_to:&glm::Vec3)->[bool;6]{
-        let (start_x,start_y,start_z) = (hitbox_from[0] as usize,hitbox_from[1] as usize,hitbox_from[2] as usize);
-        let (end_x,end_y,end_z) = (hitbox_to[0].ceil() as usize, hitbox_to[1].ceil() as usize, hitbox_to[2].ceil() as usize);
-        let cant_go_minus_x = if start_x>0{self.contains_solid_block_in(start_x-1,start_y,start_z,start_x,end_y,end_z)}else{true};
-        let cant_go_plus_x = if end_x<self.size().world_width(){self.contains_solid_block_in(end_x,start_y,start_z,end_x+1,end_y,end_z)}else{true};
-        let cant_go_minus_y = if start_y>0{self.contains_solid_block_in(start_x,start_y-1,start_z,end_x,start_y,end_z)}else{true};
-        let cant_go_plus_y = if end_y<self.size().height(){self.contains_solid_block_in(start_x,end_y,start_z,end_x,end_y+1,end_z)}else{true};
-        let cant_go_minus_z = if start_z>0{self.contains_solid_block_in(start_x,start_y,start_z-1,end_x,end_y,start_z)}else{true};
-        let cant_go_plus_z = if end_z<self.size().world_depth(){self.contains_solid_block_in(start_x,start_y,end_z,end_x,end_y,end_z+1)}else{true};
-        [cant_go_minus_x,cant_go_plus_x,cant_go_minus_y,cant_go_plus_y,cant_go_minus_z,cant_go_plus_z]
-    }
-
-    pub fn zero_out_velocity_vector_on_hitbox_collision(&self,velocity:&mut glm::Vec3,hitbox_from:&glm::Vec3,hitbox_to:&glm::Vec3){
-        let cant_go_in_direction = self.hitbox_collision(hitbox_from,hitbox_to);
-        for axis in 0..3{
-            if cant_go_in_direction[axis*2] && hitbox_from[axis]+velocity[axis] <= hitbox_from[axis].floor(){
-                velocity[axis] = 0.
-            }else if cant_go_in_direction[axis*2+1] && hitbox_to[axis].ceil() <= hitbox_to[axis]+velocity[axis]  {
-                velocity[axis] = 0.
-            }
-        }
-    }
 
 
 
@@ -1,53 +1,53 @@
 use crate::blocks::chunk_faces::ChunkFaces;
-use crate::blocks::world_size::WorldSize;
+use crate::blocks::chunk_coords::{ChunkPos, block_pos_into_chunk_pos};
 use crate::blocks::face_orientation::FaceOrientation;
 use crate::blocks::block::Block;
 use std::ops::{Deref, DerefMut};
+use std::collections::HashMap;
 
 pub struct WorldFaces {
-    chunks: Vec<ChunkFaces>,
-    size:WorldSize
+    chunks: HashMap<ChunkPos, ChunkFaces>,
 }
 
 impl WorldFaces{
-    pub fn new(size:WorldSize,gl: &gl::Gl)->Self{
-        Self{ chunks: std::iter::repeat_with(||ChunkFaces::new(gl)).take(size.total_chunks()).collect(), size }
+    pub fn new()->Self{
+        Self{ chunks: HashMap::new() }
     }
-    pub fn size(&self)->&WorldSize{
-        &self.size
-    }
-    pub(crate) fn remove_block_transparent(&mut self, x: usize, y: usize, z: usize) {
+    pub(crate) fn remove_block_transparent(&mut self, x: i32, y: i32, z: i32) {
         self.get_chunk_mut(x, z).remove_block_transparent(x, y, z)
     }
-    pub(crate) fn remove_block_opaque(&mut self, x: usize, y: usize, z: usize) {
+    pub(crate) fn remove_block_opaque(&mut self, x: i32, y: i32, z: i32) {
         self.get_chunk_mut(x, z).remove_block_opaque(x, y, z)
     }
-    pub(crate) fn push_block(&mut self, x: usize, y: usize, z: usize, ort: FaceOrientation, block: Block) {
-        self.get_chunk_mut(x, z).push_block(x, y, z, ort, block)
+    pub(crate) fn push_block(&mut self, x: i32, y: i32, z: i32, ort: FaceOrientation, block: Block, shading: (u8, u8, u8)) {
+        self.get_chunk_mut(x, z).push_block(x, y, z, ort, block, shading)
     }
-    pub(crate) fn remove_transparent_block_face(&mut self, x: usize, y: usize, z: usize, ort: FaceOrientation) {
+    pub(crate) fn remove_transparent_block_face(&mut self, x: i32, y: i32, z: i32, ort: FaceOrientation) {
         self.get_chunk_mut(x, z).remove_transparent_block_face(x, y, z, ort)
     }
-    pub(crate) fn remove_opaque_block_face(&mut self, x: usize, y: usize, z: usize, ort: FaceOrientation) {
+    pub(crate) fn remove_opaque_block_face(&mut self, x: i32, y: i32, z: i32, ort: FaceOrientation) {
         self.get_chunk_mut(x, z).remove_opaque_block_face(x, y, z, ort)
     }
-    pub(crate) fn update_block_textures(&mut self, x: usize, y: usize, z: usize, new_block: Block) {
+    pub(crate) fn refresh_face_shading(&mut self, x: i32, y: i32, z: i32, ort: FaceOrientation, shading: (u8, u8, u8)) {
+        self.get_chunk_mut(x, z).refresh_face_shading(x, y, z, ort, shading)
+    }
+    pub(crate) fn update_block_textures(&mut self, x: i32, y: i32, z: i32, new_block: Block) {
         self.get_chunk_mut(x, z).update_block_textures(x, y, z, new_block)
     }
-    pub(crate) fn change_block_textures(&mut self, x: usize, y: usize, z: usize, new_block: Block) {
+    pub(crate) fn change_block_textures(&mut self, x: i32, y: i32, z: i32, new_block: Block) {
         self.get_chunk_mut(x, z).change_block_textures(x, y, z, new_block)
     }
-    pub fn get_chunk_mut(&mut self, x: usize, z: usize) -> &mut ChunkFaces {
-        let u = self.size().block_pos_into_chunk_idx(x, z);
-        &mut self.chunks[u]
+    pub fn get_chunk_mut(&mut self, x: i32, z: i32) -> &mut ChunkFaces {
+        let chunk_pos = block_pos_into_chunk_pos(x, z);
+        self.chunks.get_mut(&chunk_pos).unwrap_or_else(|| panic!("Faces of chunk {:?} are not loaded", chunk_pos))
     }
-    pub fn get_chunk(&self, x: usize, z: usize) -> &ChunkFaces {
-        &self.chunks[self.size().block_pos_into_chunk_idx(x, z)]
+    pub fn get_chunk(&self, x: i32, z: i32) -> &ChunkFaces {
+        &self.chunks[&block_pos_into_chunk_pos(x, z)]
     }
 }
 
 impl Deref for WorldFaces{
-    type Target = Vec<ChunkFaces>;
+    type Target = HashMap<ChunkPos, ChunkFaces>;
 
     fn deref(&self) -> &Self::Target {
         &self.chunks
@@ -59,4 +59,4 @@ impl DerefMut for WorldFaces{
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.chunks
     }
-}
\ No newline at end of file
+}
@@ -13,13 +13,36 @@ use crate::render_gl::array_model::{ArrayModel, Primitive};
 use crate::render_gl::instanced_logical_model::InstancedLogicalModel;
 use crate::render_gl::buffer::{DynamicBuffer, AnyBuffer, ShaderStorageArrayBuffer};
 use crate::render_gl::texture::Filter::Nearest;
-use crate::blocks::block_properties::{STONE, GRASS, GLASS, CRAFTING, SLAB, ICE, LEAVES, TNT, BLOCKS, BEDROCK, DIRT, PLANK};
+use crate::blocks::block_properties::PLANK;
 use crate::render_gl::uniform_buffer::UniformBuffer;
-use crate::blocks::{Entities, Entity, ZombieVariant};
-use crate::blocks::WorldSize;
+use crate::blocks::{Entity, ZombieVariant};
+use crate::blocks::{ChunkGenerator, MeshingMode, Region, Schematic, Axis, RayTarget};
+use crate::worldgen::TerrainGenerator;
+use crate::worldgen::trees::random_tree;
+use crate::worldgen::noise::Rng;
 use crate::compute_cl::context::Context;
 use crate::render_gl::font::AsciiFont;
 use rand::random;
+use crate::blocks::persistence;
+use crate::physics::body::PhysicsBody;
+use crate::render_gl::camera::{Camera, FpsController, MouseLook};
+use crate::blocks::block_registry;
+use sdl2::keyboard::Keycode;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const SAVE_DIR: &str = "saves/world";
+const LOAD_RADIUS: i32 = 3;
+const SEED: u64 = 7548;
+const WORKER_THREADS: usize = 2;
+/**At most this many chunks are generated in the background at once*/
+const CHUNKS_IN_FLIGHT: usize = 4;
+/**At most this many chunks have their faces uploaded to GPU in a single frame*/
+const UPLOADS_PER_FRAME: usize = 2;
+
+fn new_generator() -> Box<dyn ChunkGenerator> {
+    Box::new(TerrainGenerator::new(SEED))
+}
 
 pub fn run(
     gl: gl::Gl,
@@ -35,6 +58,7 @@ pub fn run(
         gl.Enable(gl::PROGRAM_POINT_SIZE);
         gl.BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
     }
+    block_registry::init_from_res(&res)?;
     // let cl_context = Context::new(&gl_context)?;
     // let cl_physics = cl_context.compile_from_res(&res, "cl/position_based_dynamics.cl")?;
 
@@ -68,25 +92,33 @@ pub fn run(
     let texture_uniform = warn_ok(shader_program.get_uniform_texture("myTextureSampler").map_err(err_msg)).unwrap();
     let matrices_uniform = warn_ok(shader_program.get_uniform_std140::<Matrices,2>("Matrices").map_err(err_msg)).unwrap();
     let chunk_location_uniform = warn_ok(shader_program.get_uniform_vec3fv("chunk_location").map_err(err_msg)).unwrap();
+    let alpha_cutoff_uniform = warn_ok(shader_program.get_uniform_1f("alpha_cutoff").map_err(err_msg)).unwrap();
     shader_program.set_uniform_buffer(matrices_uniform,&matrices);
     let orb_matrices_uniform = warn_ok(orb_program.get_uniform_std140("Matrices").map_err(err_msg)).unwrap();
     orb_program.set_uniform_buffer(orb_matrices_uniform,&matrices);
     let mobs_texture_uniform = warn_ok(mobs_program.get_uniform_texture("myTextureSampler").map_err(err_msg)).unwrap();
     let mobs_matrices_uniform = warn_ok(mobs_program.get_uniform_std140::<Matrices,2>("Matrices").map_err(err_msg)).unwrap();
     mobs_program.set_uniform_buffer(mobs_matrices_uniform,&matrices);
-    let mut entities = Entities::new();
-    entities.push(Entity::Zombie(ZombieVariant::Zombie), &glm::vec3(4.,0.,0.), &glm::quat_angle_axis(0f32, &glm::vec3(0., 1., 0.)));
-    entities.push(Entity::Zombie(ZombieVariant::Steve), &glm::vec3(5.,0.,0.),&glm::quat_angle_axis(2f32, &glm::vec3(0., 1., 0.)));
-
-    let mut world = World::new(2,2, &gl);
-    world.blocks_mut().no_update_fill_level(0,1,BEDROCK);
-    world.blocks_mut().no_update_fill_level(1,1,DIRT);
-    world.blocks_mut().no_update_fill_level(2,1,GRASS);
-    world.blocks_mut().no_update_outline(5,2,5,5,5,5,PLANK);
-    world.compute_faces();
+    let mut world = if Path::new(SAVE_DIR).join(persistence::HEADER_FILE).is_file() {
+        World::load(SAVE_DIR, LOAD_RADIUS, new_generator(), &gl)?
+    } else {
+        let ground = TerrainGenerator::new(SEED).height_at(2, 2) + 1;
+        let mut world = World::new(LOAD_RADIUS, new_generator(), &gl);
+        world.player_mut().location = glm::vec3(2f32, ground as f32 + 2f32, 2f32);
+        world.update_loaded_chunks(&world.player().location.clone(), usize::MAX)?;
+        world.blocks_mut().no_update_outline(5,ground,5,5,5,5,PLANK);
+        world.entities_mut().push(Entity::Zombie(ZombieVariant::Zombie), &glm::vec3(4.,ground as f32,0.), &glm::quat_angle_axis(0f32, &glm::vec3(0., 1., 0.)));
+        world.entities_mut().push(Entity::Zombie(ZombieVariant::Steve), &glm::vec3(5.,ground as f32,0.),&glm::quat_angle_axis(2f32, &glm::vec3(0., 1., 0.)));
+        world.compute_faces();
+        world.set_save_path(Some(PathBuf::from(SAVE_DIR)));
+        world.save(SAVE_DIR)?;
+        world
+    };
+    world.update_loaded_chunks(&world.player().location.clone(), usize::MAX)?;
     world.gl_update_all_chunks();
+    world.start_workers(WORKER_THREADS);
 
-    let mut model_mobs = InstancedLogicalModel::new(DynamicBuffer::new(entities.bone_slice(),&gl),&gl);
+    let mut model_mobs = InstancedLogicalModel::new(DynamicBuffer::new(world.entities().bone_slice(),&gl),&gl);
     let mut orbs = Vec::with_capacity(64);
     for _ in 0..orbs.capacity(){
         fn rand(max:f32)->f32{
@@ -98,20 +130,18 @@ pub fn run(
     // let cl_orbs = cl_context.buffer_from_gl(model_mobs.ibo(),cl_sys::CL_MEM_READ_WRITE)?;
     // let orbs_kernel = cl_physics.kernel_builder("test")?.add_mem(&cl_orbs)?.done();
     let model_matrix = glm::identity::<f32, 4>();
-    let mut rotation = glm::quat_identity();
-    let mut location = glm::vec3(2f32, 5f32, 2f32);
+    let mut location = world.player().location.clone();
+    let mut body = PhysicsBody::player(&location);
     let mut block_in_hand = Block::new(2u32);
-    let movement_speed = 0.005f32;
+    let mut selection: (Option<(i32, i32, i32)>, Option<(i32, i32, i32)>) = (None, None);
+    let mut clipboard: Option<Schematic> = None;
+    let movement_speed = 5f32;
     let player_reach = 3f32;
-    let rotation_speed = 1f32;
     let mut fps_counter = render_gl::fps::FpsCounter::new(timer,60);
-    let fov = 60f32 / 360f32 * std::f32::consts::PI * 2f32;
-    let mut projection_matrix = glm::perspective(
-        (viewport.w as f32) / (viewport.h as f32),
-        fov,
-        0.1f32,
-        200f32,
-    );
+    let mut camera = Camera::perspective(60f32.to_radians(), (viewport.w as f32) / (viewport.h as f32), 0.1f32, 200f32)
+        .with_location(location.clone())
+        .with_rotation(world.player().rotation.clone());
+    let mut controller = FpsController::new(&camera, movement_speed);
     let event_pump = sdl.event_pump().map_err(err_msg)?;
     let mut input = render_gl::input::Input::new(event_pump);
     'main: loop {
@@ -119,6 +149,9 @@ pub fn run(
         input.poll();
 
         if input.quit() {
+            world.player_mut().location = location.clone();
+            world.player_mut().rotation = camera.rotation().clone();
+            world.shutdown()?;
             break;
         }
         if input.escape() {
@@ -126,70 +159,172 @@ pub fn run(
             sdl.mouse()
                 .set_relative_mouse_mode(!sdl.mouse().relative_mouse_mode());
         }
-        if input.has_mouse_move() {
-            let normalized_x = (input.mouse_move_xrel() as f32) / (viewport.w as f32)
-                * fps_counter.delta_f32()
-                * rotation_speed;
-            let normalized_y = (input.mouse_move_yrel() as f32) / (viewport.h as f32)
-                * fps_counter.delta_f32()
-                * rotation_speed;
-            rotation = glm::quat_angle_axis(normalized_y, &glm::vec3(1f32, 0f32, 0f32))
-                * rotation
-                * glm::quat_angle_axis(normalized_x, &glm::vec3(0f32, 1f32, 0f32));
-        }
         if input.has_resize() {
             viewport.update_size(input.resize_w(), input.resize_h());
             viewport.set_used(&gl);
-            projection_matrix = glm::perspective(
-                (viewport.w as f32) / (viewport.h as f32),
-                fov,
-                0.1f32,
-                20f32,
-            );
+            camera.resize(input.resize_w(), input.resize_h());
+        }
+        if input.has_key_press(Keycode::F5) {
+            world.player_mut().location = location.clone();
+            world.player_mut().rotation = camera.rotation().clone();
+            warn_ok(world.save(SAVE_DIR).map_err(failure::Error::from));
+        }
+        if input.has_key_press(Keycode::F9) {
+            if let Some(loaded) = warn_ok(World::load(SAVE_DIR, LOAD_RADIUS, new_generator(), &gl).map_err(failure::Error::from)) {
+                world = loaded;
+                location = world.player().location.clone();
+                // chunks around the player are loaded right away, like on start up, and the rest is streamed by workers
+                warn_ok(world.update_loaded_chunks(&location, usize::MAX).map_err(failure::Error::from));
+                world.start_workers(WORKER_THREADS);
+                camera.set_rotation(&world.player().rotation);
+                controller.look = MouseLook::from_rotation(camera.rotation());
+                body = PhysicsBody::player(&location);
+            }
         }
         if input.number() > -1{
             block_in_hand = Block::new((input.number()+1) as u32)
         }
 
-        let movement_vector = input.get_direction_unit_vector() * movement_speed * fps_counter.delta_f32();
-        let inverse_rotation = glm::quat_inverse(&rotation);
-        let mut movement_vector = glm::quat_rotate_vec3(&inverse_rotation, &movement_vector);
-        world.blocks().zero_out_velocity_vector_on_hitbox_collision(&mut movement_vector, &(location-glm::vec3(0.4f32,1.5,0.4)),&(location+glm::vec3(0.4f32,0.3,0.4)));
-        location += movement_vector;
-        if input.has_mouse_left_click()||input.has_mouse_right_click() {
-            let ray_trace_vector = glm::vec4(0f32,0.,-player_reach, 0.);
-            let ray_trace_vector = glm::quat_rotate_vec(&inverse_rotation, &ray_trace_vector);
-            if input.has_mouse_left_click() {
-                world.ray_cast_remove_block(location.as_slice(), ray_trace_vector.as_slice());
-            }else{
-                world.ray_cast_place_block(location.as_slice(), ray_trace_vector.as_slice(), block_in_hand);
+        let elapsed = Duration::from_millis(fps_counter.delta() as u64);
+        controller.update_look(&mut camera, &input);
+        if input.has_key_press(Keycode::Tab) {
+            body.set_flying(!body.is_flying());
+        }
+        let walk = if body.is_flying() {
+            camera.to_world(&input.get_direction_unit_vector()) * movement_speed
+        } else {
+            // walking ignores the pitch of the camera, Space jumps
+            if input.get_direction_unit_vector().y > 0. {
+                body.jump();
+            }
+            controller.walk_direction(&input)
+        };
+        body.update(world.blocks(), &walk, elapsed.as_secs_f32());
+        location = body.eye_position();
+        camera.set_location(&location);
+        warn_ok(world.update_loaded_chunks(&location, CHUNKS_IN_FLIGHT).map_err(failure::Error::from));
+        world.update_ticks(elapsed);
+        world.update_falling_blocks(elapsed);
+        world.update_explosions(elapsed);
+        world.update_entity_motion(elapsed);
+        let look_direction = camera.forward();
+        if input.has_mouse_left_click() {
+            // mobs get punched, blocks get removed
+            match world.raycast_with_entities(&location, &look_direction, player_reach, |b| world.is_targetable(b)) {
+                Some(RayTarget::Entity(hit)) => world.entities_mut().apply_impulse(hit.entity_id, &(look_direction * 4. + glm::vec3(0., 2., 0.))),
+                Some(RayTarget::Block(_)) => {
+                    world.edit(|world| world.ray_cast_remove_block(&location, &look_direction, player_reach));
+                }
+                None => {}
             }
-            world.gl_update_all_chunks();
+        } else if input.has_mouse_right_click() {
+            if !world.ray_cast_ignite(&location, &look_direction, player_reach) {
+                world.edit(|world| world.ray_cast_place_block(&location, &look_direction, player_reach, block_in_hand));
+            }
+        }
+        if input.has_key_press(Keycode::M) {
+            let mode = match world.meshing_mode() {
+                MeshingMode::PerFace => MeshingMode::Greedy,
+                MeshingMode::Greedy => MeshingMode::PerFace,
+            };
+            world.set_meshing_mode(mode);
+        }
+        if input.has_key_press(Keycode::U) {
+            let culling = !world.frustum_culling();
+            world.set_frustum_culling(culling);
+        }
+        if input.has_key_press(Keycode::I) {
+            let culling = !world.cave_culling();
+            world.set_cave_culling(culling);
+        }
+        if input.has_key_press(Keycode::T) {
+            let tree = random_tree(&mut Rng::new(random()));
+            world.edit(|world| world.ray_cast_place_schematic(&location, &look_direction, player_reach, &tree));
+        }
+        // B and N mark corners of the selection on the targeted block, F fills it, H builds a hollow box,
+        // G replaces the kind of block at the first corner, K places a sphere, C copies, V pastes,
+        // O turns the clipboard and P mirrors it
+        let edit_keys = [Keycode::B, Keycode::N, Keycode::F, Keycode::H, Keycode::G, Keycode::K, Keycode::C, Keycode::V];
+        if edit_keys.iter().any(|&key| input.has_key_press(key)) {
+            if let Some(hit) = world.raycast(&location, &look_direction, player_reach * 4., |b| !b.is_air()) {
+                let (hit, adjacent) = (hit.block_pos, hit.adjacent_pos);
+                if input.has_key_press(Keycode::B) {
+                    selection.0 = Some(hit);
+                }
+                if input.has_key_press(Keycode::N) {
+                    selection.1 = Some(hit);
+                }
+                if input.has_key_press(Keycode::K) {
+                    world.edit(|world| world.fill_sphere(adjacent.0, adjacent.1, adjacent.2, 3., block_in_hand, false));
+                }
+                if input.has_key_press(Keycode::V) {
+                    if let Some(clipboard) = &clipboard {
+                        world.edit(|world| world.paste(adjacent.0, adjacent.1, adjacent.2, clipboard));
+                    }
+                }
+            }
+            if let (Some(a), Some(b)) = selection {
+                let region = Region::from_corners(a, b);
+                if input.has_key_press(Keycode::F) {
+                    world.edit(|world| world.fill_region(&region, block_in_hand));
+                }
+                if input.has_key_press(Keycode::H) {
+                    world.edit(|world| world.hollow_box(&region, block_in_hand));
+                }
+                if input.has_key_press(Keycode::G) {
+                    if let Some(&old) = world.blocks().try_get_block(a.0, a.1, a.2) {
+                        world.edit(|world| world.replace_in_region(&region, old, block_in_hand));
+                    }
+                }
+                if input.has_key_press(Keycode::C) {
+                    clipboard = Some(world.copy_region(&region).with_air_keeps_existing(true));
+                }
+            }
+        }
+        if input.has_key_press(Keycode::O) {
+            clipboard = clipboard.map(|c| c.rotated_y(1));
+        }
+        if input.has_key_press(Keycode::P) {
+            clipboard = clipboard.map(|c| c.mirrored(Axis::X));
+        }
+        let ctrl = input.is_key_down(Keycode::LCtrl) || input.is_key_down(Keycode::RCtrl);
+        if ctrl && input.has_key_press(Keycode::Z) {
+            world.undo();
+        }
+        if ctrl && input.has_key_press(Keycode::Y) {
+            world.redo();
         }
+        world.update_transparency_order(&location);
+        world.gl_update_chunks(&location, UPLOADS_PER_FRAME);
 
         // draw triangle
         color_buffer.clear(&gl);
 
-        let v = glm::quat_to_mat4(&rotation) * glm::translation(&-location);
+        let v = camera.view_matrix();
 
         let m = model_matrix;
         matrices.mv = &v * m;
-        matrices.mvp = projection_matrix * &matrices.mv;
+        matrices.mvp = camera.projection_matrix() * &matrices.mv;
         matrices.update();
-        entities.update(0,&glm::vec3((fps_counter.ticks() as f32/1000.).sin(),0.,0.), &glm::quat_angle_axis(fps_counter.ticks() as f32/1000., &glm::vec3(0., 1., 0.)));
-        model_mobs.ibo_mut().update(entities.bone_slice());
+        model_mobs.ibo_mut().update(world.entities().bone_slice());
         mobs_program.set_used();
         mobs_program.set_uniform_texture(mobs_texture_uniform, &zombie_texture, 0);
         model_mobs.draw_instanced_triangles(0,/*1 cube=6 quads=12 triangles=36 vertices*/36, model_mobs.ibo().len());
 
         shader_program.set_used();
         shader_program.set_uniform_texture(texture_uniform, &texture, 0);
-        world.gl_draw(chunk_location_uniform,&shader_program);
+        world.gl_draw(&camera, chunk_location_uniform, alpha_cutoff_uniform, &shader_program);
 
         orb_program.set_used();
         model_orbs.draw_vertices(Primitive::Points, 64);
 
         font.draw(block_in_hand.name(), -1., -1., 0.08,0.12);
+        let stats = world.mesh_stats();
+        font.draw(&format!("{:?} {} quads {} vertices {}ms", world.meshing_mode(), stats.instances, stats.vertices, stats.rebuild_time.as_millis()), -1., 0.94, 0.03,0.05);
+        let cull_stats = world.cull_stats();
+        font.draw(&format!("{} sections drawn {} culled {} occluded", cull_stats.drawn, cull_stats.culled, cull_stats.occluded), -1., 0.88, 0.03,0.05);
+        let (generating, meshing) = world.pending_jobs();
+        font.draw(&format!("{} chunks generating {} meshing", generating, meshing), -1., 0.82, 0.03,0.05);
         window.gl_swap_window();
 
     }
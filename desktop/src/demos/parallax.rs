@@ -1,4 +1,5 @@
 use crate::render_gl;
+use crate::render_gl::camera::{Camera, CameraController, FreeFlyController};
 
 use crate::render_gl::data::VertexTexNorTan;
 use crate::render_gl::model::Model;
@@ -122,21 +123,14 @@ pub fn run(
     let susanne_model_matrix = glm::translation(&glm::vec3(2f32, 0f32, 2f32));
 
     let model_matrix = glm::identity::<f32, 4>();
-    let mut rotation = glm::quat_identity();
-    let mut location = glm::vec3(0f32, 2f32, 2f32);
     let mut light_location = glm::vec3(0f32, 2f32, 0f32);
     let mut light_strength = 1f32;
-    let movement_speed = 0.001f32;
+    let movement_speed = 1f32;
     let _normal_length = 1f32;
-    let rotation_speed = 1f32;
     let mut fps_counter = render_gl::fps::FpsCounter::new(timer, 60);
-    let fov = 60f32 / 360f32 * std::f32::consts::PI * 2f32;
-    let mut projection_matrix = glm::perspective(
-        (viewport.w as f32) / (viewport.h as f32),
-        fov,
-        0.1f32,
-        100f32,
-    );
+    let mut camera = Camera::perspective(60f32.to_radians(), (viewport.w as f32) / (viewport.h as f32), 0.1f32, 100f32)
+        .with_location(glm::vec3(0f32, 2f32, 2f32));
+    let mut controller = FreeFlyController::new(&camera, movement_speed);
     let _velocity = glm::vec3(0f32, 0f32, 0f32);
     let event_pump = sdl.event_pump().map_err(err_msg)?;
     let mut input = render_gl::input::Input::new(event_pump);
@@ -152,26 +146,10 @@ pub fn run(
             sdl.mouse()
                 .set_relative_mouse_mode(!sdl.mouse().relative_mouse_mode());
         }
-        if input.has_mouse_move() {
-            let normalized_x = (input.mouse_move_xrel() as f32) / (viewport.w as f32)
-                * fps_counter.delta_f32()
-                * rotation_speed;
-            let normalized_y = (input.mouse_move_yrel() as f32) / (viewport.h as f32)
-                * fps_counter.delta_f32()
-                * rotation_speed;
-            rotation = glm::quat_angle_axis(normalized_y, &glm::vec3(1f32, 0f32, 0f32))
-                * rotation
-                * glm::quat_angle_axis(normalized_x, &glm::vec3(0f32, 1f32, 0f32));
-        }
         if input.has_resize() {
             viewport.update_size(input.resize_w(), input.resize_h());
             viewport.set_used(&gl);
-            projection_matrix = glm::perspective(
-                (viewport.w as f32) / (viewport.h as f32),
-                fov,
-                0.1f32,
-                20f32,
-            );
+            camera.resize(input.resize_w(), input.resize_h());
         }
         if input.is_q() {
             light_strength += 0.01f32;
@@ -180,22 +158,17 @@ pub fn run(
             light_strength -= 0.01f32;
         }
         if input.is_r() {
-            light_location.x = location.x;
-            light_location.y = location.y;
-            light_location.z = location.z;
+            light_location = camera.location().clone();
         }
-        let movement_vector =
-            input.get_direction_unit_vector() * (movement_speed * fps_counter.delta_f32());
-        let movement_vector = glm::quat_rotate_vec3(&glm::quat_inverse(&rotation), &movement_vector);
-        location += movement_vector;
+        controller.update(&mut camera, &input, &fps_counter);
         // draw triangle
         color_buffer.clear(&gl);
         shader_program.set_used();
-        let v = glm::quat_to_mat4(&rotation) * glm::translation(&-location);
+        let v = camera.view_matrix();
 
         let m = susanne_model_matrix;
         let mv = &v * m;
-        let mvp = projection_matrix * &mv;
+        let mvp = camera.projection_matrix() * &mv;
         mvp_uniform.map(|u| shader_program.set_uniform_matrix4fv(u, mvp.as_slice()));
         v_uniform.map(|u| shader_program.set_uniform_matrix4fv(u, v.as_slice()));
         m_uniform.map(|u| shader_program.set_uniform_matrix4fv(u, m.as_slice()));
@@ -212,7 +185,7 @@ pub fn run(
 
         let m = model_matrix;
         let mv = v * m;
-        let mvp = projection_matrix * &mv;
+        let mvp = camera.projection_matrix() * &mv;
         normal_mapping_program.set_used();
         mvp_parallax_uniform
             .map(|u| normal_mapping_program.set_uniform_matrix4fv(u, mvp.as_slice()));
@@ -1,4 +1,5 @@
 use crate::render_gl;
+use crate::render_gl::camera::{Camera, CameraController, FreeFlyController};
 use crate::render_gl::terrain::{iterate, Graph};
 use crate::render_gl::texture::Cubemap;
 
@@ -105,20 +106,13 @@ pub fn run(
 
     let susanne_model_matrix = glm::translation(&glm::vec3(2f32, 0f32, 2f32));
 
-    let mut rotation = glm::quat_identity();
-    let mut location = glm::vec3(0f32, 2f32, 2f32);
     let mut light_location = glm::vec3(0f32, 2f32, 0f32);
     let mut light_strength = 20f32;
-    let mut movement_speed = 0.01f32;
-    let rotation_speed = 1f32;
+    let movement_speed = 10f32;
     let mut fps_counter = render_gl::fps::FpsCounter::new(timer,60);
-    let fov = 60f32 / 360f32 * std::f32::consts::PI * 2f32;
-    let mut projection_matrix = glm::perspective(
-        (viewport.w as f32) / (viewport.h as f32),
-        fov,
-        0.1f32,
-        100f32,
-    );
+    let mut camera = Camera::perspective(60f32.to_radians(), (viewport.w as f32) / (viewport.h as f32), 0.1f32, 100f32)
+        .with_location(glm::vec3(0f32, 2f32, 2f32));
+    let mut controller = FreeFlyController::new(&camera, movement_speed);
     let event_pump = sdl.event_pump().map_err(err_msg)?;
     let mut input = render_gl::input::Input::new(event_pump);
 
@@ -140,26 +134,10 @@ pub fn run(
             sdl.mouse()
                 .set_relative_mouse_mode(!sdl.mouse().relative_mouse_mode());
         }
-        if input.has_mouse_move() {
-            let normalized_x = (input.mouse_move_xrel() as f32) / (viewport.w as f32)
-                * fps_counter.delta_f32()
-                * rotation_speed;
-            let normalized_y = (input.mouse_move_yrel() as f32) / (viewport.h as f32)
-                * fps_counter.delta_f32()
-                * rotation_speed;
-            rotation = glm::quat_angle_axis(normalized_y, &glm::vec3(1f32, 0f32, 0f32))
-                * rotation
-                * glm::quat_angle_axis(normalized_x, &glm::vec3(0f32, 1f32, 0f32));
-        }
         if input.has_resize() {
             viewport.update_size(input.resize_w(), input.resize_h());
             viewport.set_used(&gl);
-            projection_matrix = glm::perspective(
-                (viewport.w as f32) / (viewport.h as f32),
-                fov,
-                0.1f32,
-                20f32,
-            );
+            camera.resize(input.resize_w(), input.resize_h());
         }
         if input.is_q() {
             light_strength += 0.01f32;
@@ -168,9 +146,7 @@ pub fn run(
             light_strength -= 0.01f32;
         }
         if input.is_r() {
-            light_location.x = location.x;
-            light_location.y = location.y;
-            light_location.z = location.z;
+            light_location = camera.location().clone();
         }
         if input.is_1() {
             // &|x, y| 1f32 / (x + y).ln()
@@ -192,16 +168,13 @@ pub fn run(
             model_terrain.update_from_tex_nor(g.to_ver_nor_tex().as_slice())?;
         }
         if input.is_2() {
-            movement_speed += 0.00001f32;
+            controller.movement_speed += 0.01f32;
         }
         if input.is_3() {
-            movement_speed -= 0.00001f32;
+            controller.movement_speed -= 0.01f32;
         }
-        let movement_vector =
-            input.get_direction_unit_vector() * (movement_speed * fps_counter.delta_f32());
-        let movement_vector = glm::quat_rotate_vec3(&glm::quat_inverse(&rotation), &movement_vector);
-        location += movement_vector;
-        let v = glm::quat_to_mat4(&rotation) * glm::translation(&-location);
+        controller.update(&mut camera, &input, &fps_counter);
+        let v = camera.view_matrix();
 
         color_buffer.clear(&gl);
 
@@ -214,7 +187,7 @@ pub fn run(
         sky_box_vp_uniform.map(|u| {
             sky_box_program.set_uniform_matrix4fv(
                 u,
-                (projection_matrix * &glm::mat3_to_mat4(&glm::mat4_to_mat3(&v))).as_slice(),
+                (camera.projection_matrix() * &glm::mat3_to_mat4(&glm::mat4_to_mat3(&v))).as_slice(),
             )
         });
         model_cube.draw(Primitive::Triangles);
@@ -226,7 +199,7 @@ pub fn run(
 
         let m = susanne_model_matrix;
         let mv = v * m;
-        let mvp = projection_matrix * &mv;
+        let mvp = camera.projection_matrix() * &mv;
         mvp_uniform.map(|u| shader_program.set_uniform_matrix4fv(u, mvp.as_slice()));
         v_uniform.map(|u| shader_program.set_uniform_matrix4fv(u, v.as_slice()));
         m_uniform.map(|u| shader_program.set_uniform_matrix4fv(u, m.as_slice()));
@@ -16,6 +16,7 @@ extern crate num_derive;
 extern crate cl_sys;
 
 mod blocks;
+mod worldgen;
 mod demos;
 mod render_gl;
 mod resources;
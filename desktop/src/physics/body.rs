@@ -0,0 +1,332 @@
+use crate::blocks::{WorldBlocks, CHUNK_HEIGHT};
+
+/**Blocks per second squared*/
+pub const GRAVITY: f32 = 28.;
+/**Blocks per second*/
+pub const TERMINAL_VELOCITY: f32 = 60.;
+/**Initial vertical speed of a jump, enough to get on top of a full block*/
+pub const JUMP_SPEED: f32 = 8.6;
+/**Obstacles up to this high (such as slabs) are climbed without jumping*/
+pub const STEP_HEIGHT: f32 = 0.6;
+pub const PLAYER_HALF_WIDTH: f32 = 0.3;
+pub const PLAYER_HEIGHT: f32 = 1.8;
+pub const PLAYER_EYE_HEIGHT: f32 = 1.5;
+/**Boxes closer than this are considered touching, which hides rounding errors of the contact distance*/
+const EPSILON: f32 = 1e-4;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    pub min: glm::Vec3,
+    pub max: glm::Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: glm::Vec3, max: glm::Vec3) -> Self {
+        Self { min, max }
+    }
+    pub fn translated(&self, offset: &glm::Vec3) -> Self {
+        Self { min: self.min + offset, max: self.max + offset }
+    }
+    /**Smallest box containing both this box and this box moved by the offset*/
+    pub fn swept(&self, offset: &glm::Vec3) -> Self {
+        let moved = self.translated(offset);
+        Self { min: glm::min2(&self.min, &moved.min), max: glm::max2(&self.max, &moved.max) }
+    }
+    fn overlaps_on(&self, other: &Aabb, axis: usize) -> bool {
+        self.min[axis] < other.max[axis] - EPSILON && other.min[axis] < self.max[axis] - EPSILON
+    }
+    /**How far this box can move along the axis (up to amount) before touching any of the obstacles.
+    Only obstacles that overlap with this box on the other two axes can be touched*/
+    pub fn sweep(&self, obstacles: &[Aabb], axis: usize, amount: f32) -> f32 {
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let mut allowed = amount;
+        for obstacle in obstacles {
+            if !(self.overlaps_on(obstacle, a) && self.overlaps_on(obstacle, b)) {
+                continue;
+            }
+            if amount > 0. && obstacle.min[axis] >= self.max[axis] - EPSILON {
+                allowed = allowed.min(obstacle.min[axis] - self.max[axis]).max(0.);
+            } else if amount < 0. && obstacle.max[axis] <= self.min[axis] + EPSILON {
+                allowed = allowed.max(obstacle.max[axis] - self.min[axis]).min(0.);
+            }
+        }
+        allowed
+    }
+}
+
+/**Collision boxes of all blocks that intersect the region. Blocks below the world and in chunks
+that are not loaded are solid, so that nothing falls out of the world while chunks are loading*/
+pub fn solid_boxes(blocks: &WorldBlocks, region: &Aabb) -> Vec<Aabb> {
+    let mut boxes = Vec::new();
+    for x in region.min.x.floor() as i32..region.max.x.ceil() as i32 {
+        for y in region.min.y.floor() as i32..region.max.y.ceil() as i32 {
+            if y >= CHUNK_HEIGHT as i32 {
+                break;
+            }
+            for z in region.min.z.floor() as i32..region.max.z.ceil() as i32 {
+                let corner = glm::vec3(x as f32, y as f32, z as f32);
+                let collision_box = if y < 0 {
+                    Some((glm::vec3(0., 0., 0.), glm::vec3(1., 1., 1.)))
+                } else {
+                    match blocks.try_get_block(x, y, z) {
+                        Some(block) => block.collision_box(),
+                        None => Some((glm::vec3(0., 0., 0.), glm::vec3(1., 1., 1.))),
+                    }
+                };
+                if let Some((min, max)) = collision_box {
+                    boxes.push(Aabb::new(corner + min, corner + max));
+                }
+            }
+        }
+    }
+    boxes
+}
+
+/**Upright box moved by gravity and collisions with blocks. Collisions are resolved one axis at a time
+(first Y, then X and Z), each time moving by the exact distance to the nearest obstacle,
+so the box can't tunnel through blocks or get stuck inside of them.*/
+pub struct PhysicsBody {
+    /**Centre of the bottom of the box*/
+    position: glm::Vec3,
+    velocity: glm::Vec3,
+    half_width: f32,
+    height: f32,
+    eye_height: f32,
+    on_ground: bool,
+    hit_ceiling: bool,
+    flying: bool,
+}
+
+impl PhysicsBody {
+    pub fn new(position: glm::Vec3, half_width: f32, height: f32, eye_height: f32) -> Self {
+        Self { position, velocity: glm::vec3(0., 0., 0.), half_width, height, eye_height, on_ground: false, hit_ceiling: false, flying: false }
+    }
+    /**Body of the player, whose eyes are at the given position*/
+    pub fn player(eye_position: &glm::Vec3) -> Self {
+        Self::new(eye_position - glm::vec3(0., PLAYER_EYE_HEIGHT, 0.), PLAYER_HALF_WIDTH, PLAYER_HEIGHT, PLAYER_EYE_HEIGHT)
+    }
+    pub fn position(&self) -> &glm::Vec3 {
+        &self.position
+    }
+    pub fn set_position(&mut self, position: &glm::Vec3) {
+        self.position = position.clone()
+    }
+    pub fn eye_position(&self) -> glm::Vec3 {
+        self.position + glm::vec3(0., self.eye_height, 0.)
+    }
+    pub fn set_eye_position(&mut self, eye_position: &glm::Vec3) {
+        self.position = eye_position - glm::vec3(0., self.eye_height, 0.)
+    }
+    pub fn velocity(&self) -> &glm::Vec3 {
+        &self.velocity
+    }
+    pub fn set_velocity(&mut self, velocity: &glm::Vec3) {
+        self.velocity = velocity.clone()
+    }
+    pub fn aabb(&self) -> Aabb {
+        let half = glm::vec3(self.half_width, 0., self.half_width);
+        Aabb::new(self.position - half, self.position + half + glm::vec3(0., self.height, 0.))
+    }
+    /**True if the body stood on something at the end of the last update*/
+    pub fn is_on_ground(&self) -> bool {
+        self.on_ground
+    }
+    /**True if the body bumped its head during the last update*/
+    pub fn hit_ceiling(&self) -> bool {
+        self.hit_ceiling
+    }
+    pub fn is_flying(&self) -> bool {
+        self.flying
+    }
+    /**Flying bodies ignore gravity and move vertically on their own*/
+    pub fn set_flying(&mut self, flying: bool) {
+        self.flying = flying;
+        self.velocity.y = 0.;
+    }
+    /**Returns false if the body can't jump, because it is in the air*/
+    pub fn jump(&mut self) -> bool {
+        if self.on_ground && !self.flying {
+            self.velocity.y = JUMP_SPEED;
+            self.on_ground = false;
+            true
+        } else {
+            false
+        }
+    }
+    /**Simulates delta seconds of movement. Horizontal velocity is taken from walk (in blocks per second).
+    The vertical component of walk is only used when flying, otherwise vertical velocity comes from gravity and jumps*/
+    pub fn update(&mut self, blocks: &WorldBlocks, walk: &glm::Vec3, delta: f32) {
+        self.velocity.x = walk.x;
+        self.velocity.z = walk.z;
+        if self.flying {
+            self.velocity.y = walk.y;
+        }
+        self.simulate(blocks, delta)
+    }
+    /**Simulates delta seconds of movement with the current velocity, for bodies that are pushed around
+    instead of walking (such as knocked back mobs). Velocity along the axes that were blocked drops to zero*/
+    pub fn simulate(&mut self, blocks: &WorldBlocks, delta: f32) {
+        if !self.flying {
+            self.velocity.y = (self.velocity.y - GRAVITY * delta).max(-TERMINAL_VELOCITY);
+        }
+        let movement = self.velocity * delta;
+        let start = self.aabb();
+        let obstacles = solid_boxes(blocks, &start.swept(&movement).swept(&glm::vec3(0., STEP_HEIGHT, 0.)));
+        let was_on_ground = self.on_ground;
+
+        let mut moved = Self::slide(&start, &obstacles, &movement, &[1, 0, 2]);
+        let blocked_horizontally = moved.x != movement.x || moved.z != movement.z;
+        if blocked_horizontally && was_on_ground && !self.flying && movement.y <= 0. {
+            // try to climb the obstacle: lift the box, move it sideways and put it back down
+            let lift = start.sweep(&obstacles, 1, STEP_HEIGHT);
+            let lifted = start.translated(&glm::vec3(0., lift, 0.));
+            let mut stepped = Self::slide(&lifted, &obstacles, &glm::vec3(movement.x, 0., movement.z), &[0, 2]);
+            let stepped_box = lifted.translated(&stepped);
+            stepped.y = lift + stepped_box.sweep(&obstacles, 1, -lift + movement.y.min(0.));
+            let horizontal = |v: &glm::Vec3| v.x * v.x + v.z * v.z;
+            if horizontal(&stepped) > horizontal(&moved) + EPSILON {
+                moved = stepped;
+            }
+        }
+        self.on_ground = movement.y < 0. && moved.y > movement.y;
+        self.hit_ceiling = movement.y > 0. && moved.y < movement.y;
+        if moved.x != movement.x {
+            self.velocity.x = 0.;
+        }
+        if moved.z != movement.z {
+            self.velocity.z = 0.;
+        }
+        if self.on_ground || self.hit_ceiling {
+            self.velocity.y = 0.;
+        }
+        self.position += moved;
+    }
+    /**Moves the box along the given axes one by one and returns the total offset*/
+    fn slide(aabb: &Aabb, obstacles: &[Aabb], movement: &glm::Vec3, axes: &[usize]) -> glm::Vec3 {
+        let mut moved = glm::vec3(0., 0., 0.);
+        for &axis in axes {
+            let current = aabb.translated(&moved);
+            moved[axis] = current.sweep(obstacles, axis, movement[axis]);
+        }
+        moved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::ChunkBlocks;
+    use crate::blocks::block_properties::{STONE, SLAB};
+    use crate::blocks::block_registry::init_for_tests;
+
+    const DELTA: f32 = 0.05;
+
+    /**Single chunk at the origin filled with stone up to (excluding) y=10*/
+    fn ground() -> WorldBlocks {
+        init_for_tests();
+        let mut blocks = WorldBlocks::new();
+        let mut chunk = ChunkBlocks::new();
+        chunk.fill_level(0, 10, STONE);
+        blocks.insert_chunk((0, 0), chunk);
+        blocks
+    }
+
+    fn cube(x: f32, y: f32, z: f32) -> Aabb {
+        Aabb::new(glm::vec3(x, y, z), glm::vec3(x + 1., y + 1., z + 1.))
+    }
+
+    /**Body standing on the ground at (x, 10, z)*/
+    fn standing(blocks: &WorldBlocks, x: f32, z: f32) -> PhysicsBody {
+        let mut body = PhysicsBody::new(glm::vec3(x, 10.2, z), PLAYER_HALF_WIDTH, PLAYER_HEIGHT, PLAYER_EYE_HEIGHT);
+        for _ in 0..10 {
+            body.update(blocks, &glm::vec3(0., 0., 0.), DELTA);
+        }
+        assert!(body.is_on_ground());
+        body
+    }
+
+    #[test]
+    fn sweep_stops_at_the_nearest_obstacle() {
+        let body = Aabb::new(glm::vec3(0., 0., 0.), glm::vec3(1., 2., 1.));
+        let obstacles = [cube(3., 0., 0.), cube(2.5, 1., 0.), cube(-4., 0., 0.)];
+        assert_eq!(body.sweep(&obstacles, 0, 5.), 1.5);
+        assert_eq!(body.sweep(&obstacles, 0, 1.), 1.);
+        assert_eq!(body.sweep(&obstacles, 0, -10.), -3.);
+        // obstacles that only touch the box on another axis don't block it
+        assert_eq!(body.sweep(&[cube(3., 2., 0.), cube(3., 0., 1.)], 0, 5.), 5.);
+        // touching obstacles block all movement towards them, but not away from them
+        assert_eq!(body.sweep(&[cube(1., 0., 0.)], 0, 0.5), 0.);
+        assert_eq!(body.sweep(&[cube(1., 0., 0.)], 0, -0.5), -0.5);
+        assert_eq!(body.sweep(&[cube(0., -1., 0.)], 1, -0.5), 0.);
+    }
+
+    #[test]
+    fn falling_body_lands_on_the_ground() {
+        let blocks = ground();
+        let mut body = PhysicsBody::new(glm::vec3(5.5, 14., 5.5), PLAYER_HALF_WIDTH, PLAYER_HEIGHT, PLAYER_EYE_HEIGHT);
+        body.update(&blocks, &glm::vec3(0., 0., 0.), DELTA);
+        assert!(!body.is_on_ground());
+        for _ in 0..40 {
+            body.update(&blocks, &glm::vec3(0., 0., 0.), DELTA);
+        }
+        assert!(body.is_on_ground());
+        assert!((body.position().y - 10.).abs() < 1e-3, "{:?}", body.position());
+        assert_eq!(body.velocity().y, 0.);
+        assert!(body.jump());
+        assert!(!body.jump());
+    }
+
+    #[test]
+    fn jumping_under_a_ceiling_bumps_the_head() {
+        let mut blocks = ground();
+        blocks.no_update_set_block(5, 12, 5, STONE);
+        let mut body = standing(&blocks, 5.5, 5.5);
+        assert!(body.jump());
+        body.update(&blocks, &glm::vec3(0., 0., 0.), DELTA);
+        assert!(body.hit_ceiling());
+        assert_eq!(body.velocity().y, 0.);
+        assert!((body.aabb().max.y - 12.).abs() < 1e-3, "{:?}", body.aabb());
+    }
+
+    #[test]
+    fn low_obstacles_are_climbed_but_full_blocks_are_not() {
+        let mut blocks = ground();
+        blocks.no_update_set_block(5, 10, 5, SLAB);
+        let mut body = standing(&blocks, 3.5, 5.5);
+        let mut highest = 0f32;
+        for _ in 0..20 {
+            body.update(&blocks, &glm::vec3(4., 0., 0.), DELTA);
+            highest = highest.max(body.position().y);
+        }
+        assert!(body.position().x > 6.5, "{:?}", body.position());
+        assert!((highest - 10.5).abs() < 1e-3, "{}", highest);
+
+        blocks.no_update_set_block(5, 10, 5, STONE);
+        let mut body = standing(&blocks, 3.5, 5.5);
+        for _ in 0..20 {
+            body.update(&blocks, &glm::vec3(4., 0., 0.), DELTA);
+        }
+        assert!((body.position().x - (5. - PLAYER_HALF_WIDTH)).abs() < 1e-3, "{:?}", body.position());
+        assert!((body.position().y - 10.).abs() < 1e-3);
+        assert_eq!(body.velocity().x, 0.);
+    }
+
+    #[test]
+    fn pushed_body_keeps_its_velocity_until_it_hits_a_wall() {
+        let mut blocks = ground();
+        for y in 10..13 {
+            blocks.no_update_set_block(8, y, 5, STONE);
+        }
+        let mut body = standing(&blocks, 5.5, 5.5);
+        body.set_velocity(&glm::vec3(4., 3., 0.));
+        body.simulate(&blocks, DELTA);
+        assert!(body.position().x > 5.5 && body.position().y > 10.);
+        assert_eq!(body.velocity().x, 4.);
+        for _ in 0..20 {
+            body.simulate(&blocks, DELTA);
+        }
+        assert!((body.position().x - (8. - PLAYER_HALF_WIDTH)).abs() < 1e-3, "{:?}", body.position());
+        assert_eq!(body.velocity().x, 0.);
+        assert!(body.is_on_ground());
+    }
+}
@@ -1 +1,2 @@
-pub mod particles;
\ No newline at end of file
+pub mod particles;
+pub mod body;
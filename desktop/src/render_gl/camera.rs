@@ -0,0 +1,346 @@
+use crate::render_gl::input::Input;
+use crate::render_gl::fps::FpsCounter;
+
+/**Radians per pixel of mouse movement*/
+pub const DEFAULT_SENSITIVITY: f32 = 0.004;
+/**Looking straight up or down would make yaw ambiguous, so pitch stops a little before*/
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+#[derive(Copy, Clone, Debug)]
+pub enum Projection {
+    /**Vertical field of view in radians*/
+    Perspective { fov: f32, near: f32, far: f32 },
+    /**Height of the visible area in world units*/
+    Orthographic { height: f32, near: f32, far: f32 },
+}
+
+/**Position and orientation of the viewer together with its projection.
+Rotation turns world-space directions into view-space ones, so the camera looks along its inverse applied to -Z*/
+pub struct Camera {
+    location: glm::Vec3,
+    rotation: glm::Quat,
+    projection: Projection,
+    aspect: f32,
+    projection_matrix: glm::Mat4,
+}
+
+impl Camera {
+    pub fn new(projection: Projection, aspect: f32) -> Self {
+        let mut me = Self { location: glm::vec3(0., 0., 0.), rotation: glm::quat_identity(), projection, aspect, projection_matrix: glm::identity() };
+        me.update_projection_matrix();
+        me
+    }
+    pub fn perspective(fov: f32, aspect: f32, near: f32, far: f32) -> Self {
+        Self::new(Projection::Perspective { fov, near, far }, aspect)
+    }
+    pub fn orthographic(height: f32, aspect: f32, near: f32, far: f32) -> Self {
+        Self::new(Projection::Orthographic { height, near, far }, aspect)
+    }
+    pub fn with_location(mut self, location: glm::Vec3) -> Self {
+        self.location = location;
+        self
+    }
+    pub fn with_rotation(mut self, rotation: glm::Quat) -> Self {
+        self.rotation = rotation;
+        self
+    }
+    fn update_projection_matrix(&mut self) {
+        self.projection_matrix = match self.projection {
+            Projection::Perspective { fov, near, far } => glm::perspective(self.aspect, fov, near, far),
+            Projection::Orthographic { height, near, far } => {
+                let (half_w, half_h) = (height * self.aspect / 2., height / 2.);
+                glm::ortho(-half_w, half_w, -half_h, half_h, near, far)
+            }
+        }
+    }
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+        self.update_projection_matrix()
+    }
+    pub fn aspect(&self) -> f32 {
+        self.aspect
+    }
+    pub fn set_aspect(&mut self, aspect: f32) {
+        self.aspect = aspect;
+        self.update_projection_matrix()
+    }
+    /**Only the aspect ratio changes, the rest of the projection (including the far plane) stays as it was*/
+    pub fn resize(&mut self, w: i32, h: i32) {
+        self.set_aspect(w as f32 / h.max(1) as f32)
+    }
+    pub fn location(&self) -> &glm::Vec3 {
+        &self.location
+    }
+    pub fn set_location(&mut self, location: &glm::Vec3) {
+        self.location = location.clone()
+    }
+    pub fn rotation(&self) -> &glm::Quat {
+        &self.rotation
+    }
+    pub fn set_rotation(&mut self, rotation: &glm::Quat) {
+        self.rotation = rotation.clone()
+    }
+    /**Turns a view-space direction (such as the one returned by Input::get_direction_unit_vector) into world-space*/
+    pub fn to_world(&self, view_direction: &glm::Vec3) -> glm::Vec3 {
+        glm::quat_rotate_vec3(&glm::quat_inverse(&self.rotation), view_direction)
+    }
+    pub fn forward(&self) -> glm::Vec3 {
+        self.to_world(&glm::vec3(0., 0., -1.))
+    }
+    pub fn right(&self) -> glm::Vec3 {
+        self.to_world(&glm::vec3(1., 0., 0.))
+    }
+    pub fn up(&self) -> glm::Vec3 {
+        self.to_world(&glm::vec3(0., 1., 0.))
+    }
+    pub fn view_matrix(&self) -> glm::Mat4 {
+        glm::quat_to_mat4(&self.rotation) * glm::translation(&-self.location)
+    }
+    pub fn projection_matrix(&self) -> &glm::Mat4 {
+        &self.projection_matrix
+    }
+    pub fn view_projection_matrix(&self) -> glm::Mat4 {
+        self.projection_matrix * self.view_matrix()
+    }
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(&self.view_projection_matrix())
+    }
+}
+
+/**Six planes bounding the volume visible through a camera. Normals point inwards*/
+#[derive(Copy, Clone, Debug)]
+pub struct Frustum {
+    /**(normal, distance), such that points p inside satisfy dot(normal, p) + distance >= 0*/
+    planes: [glm::Vec4; 6],
+}
+
+impl Frustum {
+    /**Extracts planes from a view-projection matrix (Gribb-Hartmann method)*/
+    pub fn from_matrix(m: &glm::Mat4) -> Self {
+        let row = |i: usize| glm::vec4(m[(i, 0)], m[(i, 1)], m[(i, 2)], m[(i, 3)]);
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+        let mut planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r3 + r2, r3 - r2];
+        for plane in planes.iter_mut() {
+            let length = glm::length(&plane.xyz());
+            *plane /= length;
+        }
+        Self { planes }
+    }
+    pub fn planes(&self) -> &[glm::Vec4; 6] {
+        &self.planes
+    }
+    pub fn contains_point(&self, point: &glm::Vec3) -> bool {
+        self.planes.iter().all(|plane| glm::dot(&plane.xyz(), point) + plane.w >= 0.)
+    }
+    /**False only if the box lies entirely outside of the frustum. Boxes near corners of the frustum
+    can be reported as visible even if they are not, which is fine for culling*/
+    pub fn intersects_aabb(&self, min: &glm::Vec3, max: &glm::Vec3) -> bool {
+        self.planes.iter().all(|plane| {
+            // corner of the box furthest along the normal
+            let corner = glm::vec3(
+                if plane.x >= 0. { max.x } else { min.x },
+                if plane.y >= 0. { max.y } else { min.y },
+                if plane.z >= 0. { max.z } else { min.z },
+            );
+            glm::dot(&plane.xyz(), &corner) + plane.w >= 0.
+        })
+    }
+}
+
+/**Moves and turns the camera in response to player input*/
+pub trait CameraController {
+    fn update(&mut self, camera: &mut Camera, input: &Input, fps_counter: &FpsCounter);
+}
+
+/**Yaw and pitch driven by the mouse. Speed of turning depends only on how far the mouse moved,
+not on the frame rate*/
+#[derive(Copy, Clone, Debug)]
+pub struct MouseLook {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub sensitivity: f32,
+}
+
+impl MouseLook {
+    pub fn new() -> Self {
+        Self { yaw: 0., pitch: 0., sensitivity: DEFAULT_SENSITIVITY }
+    }
+    /**Yaw and pitch that give the same viewing direction as the rotation. Roll is lost*/
+    pub fn from_rotation(rotation: &glm::Quat) -> Self {
+        let forward = glm::quat_rotate_vec3(&glm::quat_inverse(rotation), &glm::vec3(0., 0., -1.));
+        Self {
+            yaw: forward.x.atan2(-forward.z),
+            pitch: (-forward.y).max(-1.).min(1.).asin().max(-MAX_PITCH).min(MAX_PITCH),
+            sensitivity: DEFAULT_SENSITIVITY,
+        }
+    }
+    pub fn update(&mut self, input: &Input) {
+        if input.has_mouse_move() {
+            self.yaw += input.mouse_move_xrel() as f32 * self.sensitivity;
+            self.pitch = (self.pitch + input.mouse_move_yrel() as f32 * self.sensitivity).max(-MAX_PITCH).min(MAX_PITCH);
+        }
+    }
+    pub fn rotation(&self) -> glm::Quat {
+        glm::quat_angle_axis(self.pitch, &glm::vec3(1., 0., 0.)) * glm::quat_angle_axis(self.yaw, &glm::vec3(0., 1., 0.))
+    }
+}
+
+/**Flies in the direction the camera is looking, like a spectator*/
+pub struct FreeFlyController {
+    pub look: MouseLook,
+    /**Units per second*/
+    pub movement_speed: f32,
+}
+
+impl FreeFlyController {
+    pub fn new(camera: &Camera, movement_speed: f32) -> Self {
+        Self { look: MouseLook::from_rotation(camera.rotation()), movement_speed }
+    }
+}
+
+impl CameraController for FreeFlyController {
+    fn update(&mut self, camera: &mut Camera, input: &Input, fps_counter: &FpsCounter) {
+        self.look.update(input);
+        camera.set_rotation(&self.look.rotation());
+        let movement = camera.to_world(&input.get_direction_unit_vector()) * (self.movement_speed * fps_counter.delta_f32() / 1000.);
+        camera.set_location(&(camera.location() + movement));
+    }
+}
+
+/**Walks on the horizontal plane regardless of pitch, while up and down keys move along the world Y axis.
+Games with physics can use walk_direction and move the camera themselves*/
+pub struct FpsController {
+    pub look: MouseLook,
+    /**Units per second*/
+    pub movement_speed: f32,
+}
+
+impl FpsController {
+    pub fn new(camera: &Camera, movement_speed: f32) -> Self {
+        Self { look: MouseLook::from_rotation(camera.rotation()), movement_speed }
+    }
+    /**World-space direction (scaled by movement speed) in which the player wants to walk.
+    The Y component comes from the up and down keys*/
+    pub fn walk_direction(&self, input: &Input) -> glm::Vec3 {
+        let direction = input.get_direction_unit_vector();
+        let (sin, cos) = self.look.yaw.sin_cos();
+        let forward = glm::vec3(sin, 0., -cos);
+        let right = glm::vec3(cos, 0., sin);
+        let horizontal = right * direction.x - forward * direction.z;
+        let horizontal = if horizontal == glm::vec3(0., 0., 0.) { horizontal } else { glm::normalize(&horizontal) };
+        (horizontal + glm::vec3(0., direction.y, 0.)) * self.movement_speed
+    }
+    /**Only turns the camera*/
+    pub fn update_look(&mut self, camera: &mut Camera, input: &Input) {
+        self.look.update(input);
+        camera.set_rotation(&self.look.rotation());
+    }
+}
+
+impl CameraController for FpsController {
+    fn update(&mut self, camera: &mut Camera, input: &Input, fps_counter: &FpsCounter) {
+        self.update_look(camera, input);
+        let movement = self.walk_direction(input) * (fps_counter.delta_f32() / 1000.);
+        camera.set_location(&(camera.location() + movement));
+    }
+}
+
+/**Circles around the target, always looking at it. Forward and backward keys zoom in and out*/
+pub struct OrbitController {
+    pub look: MouseLook,
+    pub target: glm::Vec3,
+    pub distance: f32,
+    pub min_distance: f32,
+    /**Units per second*/
+    pub zoom_speed: f32,
+}
+
+impl OrbitController {
+    pub fn new(target: glm::Vec3, distance: f32, zoom_speed: f32) -> Self {
+        Self { look: MouseLook::new(), target, distance, min_distance: 0.5, zoom_speed }
+    }
+}
+
+impl CameraController for OrbitController {
+    fn update(&mut self, camera: &mut Camera, input: &Input, fps_counter: &FpsCounter) {
+        self.look.update(input);
+        let zoom = input.get_direction_unit_vector().z * self.zoom_speed * fps_counter.delta_f32() / 1000.;
+        self.distance = (self.distance + zoom).max(self.min_distance);
+        camera.set_rotation(&self.look.rotation());
+        let location = self.target - camera.forward() * self.distance;
+        camera.set_location(&location);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box_at(centre: glm::Vec3) -> (glm::Vec3, glm::Vec3) {
+        (centre - glm::vec3(0.5, 0.5, 0.5), centre + glm::vec3(0.5, 0.5, 0.5))
+    }
+
+    fn is_visible(camera: &Camera, centre: glm::Vec3) -> bool {
+        let (min, max) = unit_box_at(centre);
+        camera.frustum().intersects_aabb(&min, &max)
+    }
+
+    #[test]
+    fn perspective_frustum_keeps_boxes_in_front_and_culls_the_rest() {
+        let camera = Camera::perspective(1.2, 1.5, 0.1, 100.).with_location(glm::vec3(3., 4., 5.));
+        assert!(is_visible(&camera, glm::vec3(3., 4., -5.)));
+        assert!(camera.frustum().contains_point(&glm::vec3(3., 4., -5.)));
+        // behind the camera
+        assert!(!is_visible(&camera, glm::vec3(3., 4., 15.)));
+        assert!(!camera.frustum().contains_point(&glm::vec3(3., 4., 15.)));
+        // beyond the far plane and far off to the side
+        assert!(!is_visible(&camera, glm::vec3(3., 4., -200.)));
+        assert!(!is_visible(&camera, glm::vec3(60., 4., -5.)));
+        // a box the camera stands in is always visible
+        assert!(is_visible(&camera, glm::vec3(3., 4., 5.)));
+    }
+
+    #[test]
+    fn frustum_turns_with_the_camera() {
+        let look = MouseLook { yaw: std::f32::consts::FRAC_PI_2, pitch: 0., sensitivity: DEFAULT_SENSITIVITY };
+        let camera = Camera::perspective(1.2, 1., 0.1, 100.).with_rotation(look.rotation());
+        let forward = camera.forward();
+        assert!(glm::distance(&forward, &glm::vec3(1., 0., 0.)) < 1e-5, "{:?}", forward);
+        assert!(is_visible(&camera, forward * 10.));
+        assert!(!is_visible(&camera, forward * -10.));
+        assert!(!is_visible(&camera, glm::vec3(0., 0., -10.)));
+    }
+
+    #[test]
+    fn orthographic_frustum_has_fixed_width() {
+        let camera = Camera::orthographic(10., 2., 0.1, 50.);
+        assert!(is_visible(&camera, glm::vec3(9., 0., -20.)));
+        assert!(!is_visible(&camera, glm::vec3(11., 0., -20.)));
+        assert!(!is_visible(&camera, glm::vec3(0., 6., -20.)));
+    }
+
+    #[test]
+    fn yaw_and_pitch_survive_a_round_trip_through_rotation() {
+        for &yaw in &[0., 0.5, -1.2, 2.9, -3.] {
+            for &pitch in &[0., 0.7, -0.7, 1.5, -1.5] {
+                let look = MouseLook { yaw, pitch, sensitivity: DEFAULT_SENSITIVITY };
+                let back = MouseLook::from_rotation(&look.rotation());
+                assert!((back.yaw - yaw).abs() < 1e-4, "yaw {} became {}", yaw, back.yaw);
+                assert!((back.pitch - pitch).abs() < 1e-4, "pitch {} became {}", pitch, back.pitch);
+            }
+        }
+    }
+
+    #[test]
+    fn pitch_from_rotation_is_clamped() {
+        let straight_down = glm::quat_angle_axis(std::f32::consts::FRAC_PI_2, &glm::vec3(1., 0., 0.));
+        let look = MouseLook::from_rotation(&straight_down);
+        assert_eq!(look.pitch, MAX_PITCH);
+        // positive pitch looks down
+        let camera = Camera::perspective(1., 1., 0.1, 10.).with_rotation(look.rotation());
+        assert!(camera.forward().y < -0.99);
+    }
+}
@@ -18,7 +18,11 @@ Location 13 is meant to hold some u32 integer meta-data of each instance.
 Location 14 is meant to hold quat with instance rotation.
 */
 pub trait VertexAttribPointers {
-    fn vertex_attrib_pointers(gl: &::gl::Gl);
+    fn vertex_attrib_pointers(gl: &::gl::Gl) {
+        Self::vertex_attrib_pointers_from(gl, 0)
+    }
+    /**Attributes start at the element with index first instead of the beginning of the buffer*/
+    fn vertex_attrib_pointers_from(gl: &::gl::Gl, first: usize);
 }
 
 pub trait VertexAttrib {
@@ -158,7 +162,7 @@ impl From<(f32, f32, f32, f32)> for f32_f32_f32_f32 {
 
 impl From<&[f32; 4]> for f32_f32_f32_f32 {
     fn from(other: &[f32; 4]) -> Self {
-        f32_f32_f32_f32::new(other[0], other[1], other[2], other[4])
+        f32_f32_f32_f32::new(other[0], other[1], other[2], other[3])
     }
 }
 
@@ -330,9 +334,9 @@ impl VertexAttrib for f32 {
 
 
 impl VertexAttribPointers for u8 {
-    fn vertex_attrib_pointers(gl: &Gl) {
+    fn vertex_attrib_pointers_from(gl: &Gl, first: usize) {
         unsafe{
-            u8::vertex_attrib_pointer(gl,1, 11,0);
+            u8::vertex_attrib_pointer(gl,1, 11,first);
             gl.VertexAttribDivisor(11, 1);
         }
     }
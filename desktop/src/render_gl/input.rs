@@ -1,6 +1,8 @@
 use nalgebra_glm as glm;
 use sdl2;
 use sdl2::mouse::MouseButton;
+use sdl2::keyboard::Keycode;
+use std::collections::HashSet;
 
 pub struct Input {
     event_pump: sdl2::EventPump,
@@ -38,6 +40,8 @@ pub struct Input {
     no8: bool,
     no9: bool,
     number: i32,
+    pressed_keys: HashSet<Keycode>,
+    held_keys: HashSet<Keycode>,
 }
 
 impl Input {
@@ -77,7 +81,9 @@ impl Input {
             no7: false,
             no8: false,
             no9: false,
-            number: 0
+            number: 0,
+            pressed_keys: HashSet::new(),
+            held_keys: HashSet::new(),
         }
     }
     pub fn poll(&mut self) {
@@ -86,6 +92,7 @@ impl Input {
         self.has_mouse_left_click = false;
         self.has_mouse_right_click = false;
         self.number = -1;
+        self.pressed_keys.clear();
         for event in self.event_pump.poll_iter() {
             match event {
                 sdl2::event::Event::Quit { .. } => self.quit = true,
@@ -97,8 +104,12 @@ impl Input {
                     self.resize_h = h;
                     self.has_resize = true;
                 }
-                sdl2::event::Event::KeyDown { keycode, .. } => {
+                sdl2::event::Event::KeyDown { keycode, repeat, .. } => {
                     if let Some(k) = keycode {
+                        if !repeat {
+                            self.pressed_keys.insert(k);
+                        }
+                        self.held_keys.insert(k);
                         match k {
                             sdl2::keyboard::Keycode::Num0 => {
                                 self.no0 = true;
@@ -176,6 +187,7 @@ impl Input {
                 }
                 sdl2::event::Event::KeyUp { keycode, .. } => {
                     if let Some(k) = keycode {
+                        self.held_keys.remove(&k);
                         match k {
                             sdl2::keyboard::Keycode::Num0 => {
                                 self.no0 = false;
@@ -387,4 +399,11 @@ impl Input {
     pub fn number(&self) -> i32 {
         self.number
     }
+    /**True only in the frame in which the key went down. Auto-repeated key events are ignored.*/
+    pub fn has_key_press(&self, key: Keycode) -> bool {
+        self.pressed_keys.contains(&key)
+    }
+    pub fn is_key_down(&self, key: Keycode) -> bool {
+        self.held_keys.contains(&key)
+    }
 }
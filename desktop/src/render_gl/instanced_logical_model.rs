@@ -9,6 +9,7 @@ use crate::render_gl::model::Model;
 use crate::render_gl::buffer::Buffer;
 use std::ops::Deref;
 use crate::render_gl::logical_model::LogicalModel;
+use crate::scene::supports_base_instance;
 
 pub struct InstancedLogicalModel<I: VertexAttribPointers, U:BufferUsage> {
     ibo: Buffer<BufferTypeArray,I,U>, //instance buffer
@@ -58,6 +59,42 @@ impl<I: VertexAttribPointers, U:BufferUsage> InstancedLogicalModel<I,U> {
             self.unbind();
         }
     }
+    /**Draws only instance_count instances starting at first_instance. Base instance needs OpenGL 4.2 or ARB_base_instance,
+    so contexts without it (such as those on macOS) temporarily point instance attributes at first_instance instead*/
+    fn draw_instanced_range(&self,
+                            primitive: gl::types::GLenum,
+                            first_vertex: gl::types::GLint,
+                            vertex_count: gl::types::GLsizei,
+                            first_instance: usize,
+                            instance_count: usize) {
+        let gl = self.model.gl();
+        unsafe {
+            self.bind();
+            if supports_base_instance() {
+                gl.DrawArraysInstancedBaseInstance(
+                    primitive,
+                    first_vertex,
+                    vertex_count,
+                    instance_count as gl::types::GLsizei,
+                    first_instance as gl::types::GLuint,
+                );
+                drain_gl_errors(gl);
+            } else {
+                self.ibo.bind();
+                I::vertex_attrib_pointers_from(gl, first_instance);
+                gl.DrawArraysInstanced(
+                    primitive,
+                    first_vertex,
+                    vertex_count,
+                    instance_count as gl::types::GLsizei,
+                );
+                drain_gl_errors(gl);
+                I::vertex_attrib_pointers(gl);
+                self.ibo.unbind();
+            }
+            self.unbind();
+        }
+    }
     pub fn draw_instanced_triangles(&self,
                                     first_vertex: gl::types::GLint,
                                     vertex_count: gl::types::GLsizei,
@@ -65,6 +102,14 @@ impl<I: VertexAttribPointers, U:BufferUsage> InstancedLogicalModel<I,U> {
         self.draw_instanced(gl::TRIANGLES, first_vertex, vertex_count,instance_count);
     }
 
+    pub fn draw_instanced_triangles_range(&self,
+                                          first_vertex: gl::types::GLint,
+                                          vertex_count: gl::types::GLsizei,
+                                          first_instance: usize,
+                                          instance_count: usize) {
+        self.draw_instanced_range(gl::TRIANGLES, first_vertex, vertex_count, first_instance, instance_count);
+    }
+
     pub fn draw_instanced_lines(&self,
                                 first_vertex: gl::types::GLint,
                                 vertex_count: gl::types::GLsizei,
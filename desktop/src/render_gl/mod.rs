@@ -21,3 +21,4 @@ pub mod instanced_array_model;
 pub mod instanced_logical_model;
 pub mod uniform_buffer;
 pub mod font;
+pub mod camera;
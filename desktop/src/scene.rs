@@ -2,6 +2,7 @@ use crate::resources::Resources;
 use failure::err_msg;
 use std::ffi::CStr;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use crate::render_gl::gl_error::drain_gl_errors;
 
@@ -11,6 +12,30 @@ pub fn supported_since(major: u8, minor: u8) -> bool {
     major < GL_VER_MAJOR || (major == GL_VER_MAJOR && minor <= GL_VER_MINOR)
 }
 
+static BASE_INSTANCE: AtomicBool = AtomicBool::new(false);
+/**True if instanced draws can start from a base instance (OpenGL 4.2 or ARB_base_instance). Only 4.1 is requested,
+because that is the newest version macOS offers, but most other drivers create a newer context anyway*/
+pub fn supports_base_instance() -> bool {
+    BASE_INSTANCE.load(Ordering::Relaxed)
+}
+/**Checks the version of the created context and its extensions. Must be called once the context is current*/
+fn detect_base_instance(gl: &gl::Gl) -> bool {
+    unsafe {
+        let (mut major, mut minor) = (0, 0);
+        gl.GetIntegerv(gl::MAJOR_VERSION, &mut major);
+        gl.GetIntegerv(gl::MINOR_VERSION, &mut minor);
+        if (major, minor) >= (4, 2) {
+            return true;
+        }
+        let mut extensions = 0;
+        gl.GetIntegerv(gl::NUM_EXTENSIONS, &mut extensions);
+        (0..extensions as gl::types::GLuint).any(|i| {
+            let name = gl.GetStringi(gl::EXTENSIONS, i);
+            !name.is_null() && CStr::from_ptr(name as *const std::os::raw::c_char).to_bytes() == b"GL_ARB_base_instance"
+        })
+    }
+}
+
 extern "system" fn message_callback(
     _source: gl::types::GLenum,
     type_of_error: gl::types::GLenum,
@@ -88,6 +113,7 @@ pub fn run() -> Result<(), failure::Error> {
         video_subsystem.gl_get_proc_address(s) as *const std::os::raw::c_void
     });
 
+    BASE_INSTANCE.store(detect_base_instance(&gl), Ordering::Relaxed);
     drain_gl_errors(&gl);
     unsafe {
         gl.DepthFunc(gl::LESS);
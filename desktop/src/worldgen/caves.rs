@@ -0,0 +1,163 @@
+use crate::blocks::block_properties::{AIR, BEDROCK, LAVA, WATER};
+use crate::blocks::{ChunkBlocks, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH};
+use crate::worldgen::features::{for_each_block_in_ellipsoid, for_each_origin_chunk, random_point_in_chunk};
+use crate::worldgen::noise::{hash, Noise};
+
+/**Carved cave blocks below this height are filled with lava instead of air*/
+pub const LAVA_LEVEL: i32 = 10;
+const NOISE_CAVE_MAX_Y: i32 = 120;
+/**Squared distance from the intersection of two noise zero-surfaces, under which blocks are carved out.
+ Larger values make wider tunnels.*/
+const NOISE_CAVE_THRESHOLD: f32 = 0.004;
+const WORM_SALT: u64 = 0x5752_4D53;
+const WORM_CHANCE: f32 = 0.12;
+const WORM_MAX_LENGTH: i32 = 64;
+/**How far (in chunks) a worm can get away from the chunk it started in*/
+const WORM_CHUNK_RADIUS: i32 = (WORM_MAX_LENGTH + CHUNK_WIDTH as i32 - 1) / CHUNK_WIDTH as i32;
+const LAKE_SALT: u64 = 0x4C41_4B45;
+const LAKE_CHANCE: f32 = 0.06;
+
+/**Carves tunnels formed by intersecting 3D noise surfaces (spaghetti caves), long winding worm caves
+ and lava lakes near the bottom of the world.*/
+pub struct Caves {
+    seed: u64,
+    noise_a: Noise,
+    noise_b: Noise,
+}
+
+/**Tells if the block at the given world coordinates, just outside of the chunk being carved, is water.
+ Neighbouring chunks may not exist yet, so the generator answers from its heightmap and sea level*/
+pub type WaterOutside<'a> = &'a dyn Fn(i32, i32, i32) -> bool;
+
+/**Position of the carved chunk and what lies around it*/
+#[derive(Copy, Clone)]
+struct Surroundings<'a> {
+    chunk_x: i32,
+    chunk_z: i32,
+    water_outside: WaterOutside<'a>,
+}
+
+fn set_carved(chunk: &mut ChunkBlocks, around: Surroundings, x: usize, y: usize, z: usize) {
+    if can_carve(chunk, around, x, y, z) {
+        chunk.set_block(x, y, z, if (y as i32) < LAVA_LEVEL { LAVA } else { AIR })
+    }
+}
+
+/**Bedrock is never carved and neither is anything touching water (including water of neighbouring chunks),
+ so that caves never flood or drain the sea.*/
+fn can_carve(chunk: &ChunkBlocks, around: Surroundings, x: usize, y: usize, z: usize) -> bool {
+    let block = *chunk.get_block(x, y, z);
+    if block.is_air() || block.is_same_kind(BEDROCK) || block.is_same_kind(WATER) {
+        return false;
+    }
+    let is_water = |x: i32, y: usize, z: i32| {
+        if 0 <= x && x < CHUNK_WIDTH as i32 && 0 <= z && z < CHUNK_DEPTH as i32 {
+            chunk.get_block(x as usize, y, z as usize).is_same_kind(WATER)
+        } else {
+            (around.water_outside)(around.chunk_x * CHUNK_WIDTH as i32 + x, y as i32, around.chunk_z * CHUNK_DEPTH as i32 + z)
+        }
+    };
+    let (x, z) = (x as i32, z as i32);
+    !((y + 1 < CHUNK_HEIGHT && is_water(x, y + 1, z))
+        || is_water(x - 1, y, z)
+        || is_water(x + 1, y, z)
+        || is_water(x, y, z - 1)
+        || is_water(x, y, z + 1))
+}
+
+impl Caves {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            noise_a: Noise::new(hash(seed, 10, 0, 0)),
+            noise_b: Noise::new(hash(seed, 11, 0, 0)),
+        }
+    }
+    pub fn carve(&self, chunk_x: i32, chunk_z: i32, chunk: &mut ChunkBlocks, water_outside: WaterOutside) {
+        let around = Surroundings { chunk_x, chunk_z, water_outside };
+        self.carve_noise_caves(around, chunk);
+        self.carve_worms(around, chunk);
+        self.place_lava_lakes(around, chunk);
+    }
+    fn carve_noise_caves(&self, around: Surroundings, chunk: &mut ChunkBlocks) {
+        let Surroundings { chunk_x, chunk_z, .. } = around;
+        for y in 1..NOISE_CAVE_MAX_Y as usize {
+            for z in 0..CHUNK_DEPTH {
+                for x in 0..CHUNK_WIDTH {
+                    if chunk.get_block(x, y, z).is_air() {
+                        continue;
+                    }
+                    let bx = (chunk_x * CHUNK_WIDTH as i32 + x as i32) as f32 / 48.;
+                    let by = y as f32 / 24.;
+                    let bz = (chunk_z * CHUNK_DEPTH as i32 + z as i32) as f32 / 48.;
+                    let a = self.noise_a.sample3(bx, by, bz);
+                    let b = self.noise_b.sample3(bx, by, bz);
+                    if a * a + b * b < NOISE_CAVE_THRESHOLD {
+                        set_carved(chunk, around, x, y, z)
+                    }
+                }
+            }
+        }
+    }
+    fn carve_worms(&self, around: Surroundings, chunk: &mut ChunkBlocks) {
+        let Surroundings { chunk_x, chunk_z, .. } = around;
+        for_each_origin_chunk(self.seed, WORM_SALT, chunk_x, chunk_z, WORM_CHUNK_RADIUS, |origin_x, origin_z, rng| {
+            if !rng.chance(WORM_CHANCE) {
+                return;
+            }
+            let mut position = random_point_in_chunk(rng, origin_x, origin_z, 8, 72);
+            let mut yaw = rng.next_f32() * std::f32::consts::PI * 2.;
+            let mut pitch = (rng.next_f32() - 0.5) * 0.5;
+            let length = rng.range(WORM_MAX_LENGTH / 2, WORM_MAX_LENGTH);
+            let thickness = 1.5 + rng.next_f32() * 1.5;
+            for step in 0..length {
+                // Worms are thinner at both ends
+                let radius = 1. + thickness * (std::f32::consts::PI * step as f32 / length as f32).sin();
+                let radii = glm::vec3(radius, radius * 0.8, radius);
+                for_each_block_in_ellipsoid(chunk_x, chunk_z, &position, &radii, |x, y, z| set_carved(chunk, around, x, y, z));
+                position += glm::vec3(yaw.cos() * pitch.cos(), pitch.sin(), yaw.sin() * pitch.cos());
+                yaw += (rng.next_f32() - 0.5) * 0.5;
+                pitch = (pitch * 0.7 + (rng.next_f32() - 0.5) * 0.4).max(-0.8).min(0.8);
+            }
+        })
+    }
+    fn place_lava_lakes(&self, around: Surroundings, chunk: &mut ChunkBlocks) {
+        let Surroundings { chunk_x, chunk_z, .. } = around;
+        for_each_origin_chunk(self.seed, LAKE_SALT, chunk_x, chunk_z, 1, |origin_x, origin_z, rng| {
+            if !rng.chance(LAKE_CHANCE) {
+                return;
+            }
+            let center = random_point_in_chunk(rng, origin_x, origin_z, 4, LAVA_LEVEL + 6);
+            let radii = glm::vec3(3. + rng.next_f32() * 4., 2. + rng.next_f32() * 2., 3. + rng.next_f32() * 4.);
+            let surface = center.y as usize;
+            for_each_block_in_ellipsoid(chunk_x, chunk_z, &center, &radii, |x, y, z| {
+                if chunk.get_block(x, y, z).is_air() || can_carve(chunk, around, x, y, z) {
+                    chunk.set_block(x, y, z, if y <= surface { LAVA } else { AIR })
+                }
+            });
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::block_properties::STONE;
+    use crate::blocks::block_registry::init_for_tests;
+
+    #[test]
+    fn water_of_neighbouring_chunks_is_not_exposed() {
+        init_for_tests();
+        let mut chunk = ChunkBlocks::new();
+        chunk.fill_level(0, 20, STONE);
+        // sea fills the chunk to the west of chunk (1, 0)
+        let sea = |x: i32, _y: i32, _z: i32| x < CHUNK_WIDTH as i32;
+        let around = Surroundings { chunk_x: 1, chunk_z: 0, water_outside: &sea };
+        assert!(!can_carve(&chunk, around, 0, 10, 5));
+        assert!(can_carve(&chunk, around, 1, 10, 5));
+        assert!(can_carve(&chunk, around, CHUNK_WIDTH - 1, 10, 5));
+        set_carved(&mut chunk, around, 0, 10, 5);
+        assert!(chunk.get_block(0, 10, 5).is_same_kind(STONE));
+    }
+}
@@ -0,0 +1,44 @@
+use crate::blocks::{CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH};
+use crate::worldgen::noise::Rng;
+
+/**Features such as caves or ore veins may start in one chunk and spill over into its neighbours.
+ In order to generate chunks independently of each other, every chunk replays all the features originating
+ in chunks within the given radius (each with its own deterministic random number generator)
+ and keeps only the blocks that fall inside of it.*/
+pub fn for_each_origin_chunk(seed: u64, salt: u64, chunk_x: i32, chunk_z: i32, radius: i32, mut f: impl FnMut(i32, i32, &mut Rng)) {
+    for origin_x in chunk_x - radius..=chunk_x + radius {
+        for origin_z in chunk_z - radius..=chunk_z + radius {
+            let mut rng = Rng::for_chunk(seed, salt, origin_x, origin_z);
+            f(origin_x, origin_z, &mut rng)
+        }
+    }
+}
+
+/**Calls f with chunk-local coordinates of every block of the chunk, whose center lies inside of the ellipsoid.*/
+pub fn for_each_block_in_ellipsoid(chunk_x: i32, chunk_z: i32, center: &glm::Vec3, radii: &glm::Vec3, mut f: impl FnMut(usize, usize, usize)) {
+    let origin_x = chunk_x * CHUNK_WIDTH as i32;
+    let origin_z = chunk_z * CHUNK_DEPTH as i32;
+    let min = |c: f32, r: f32, origin: i32| ((c - r).floor() as i32 - origin).max(0);
+    let max = |c: f32, r: f32, origin: i32, size: usize| ((c + r).ceil() as i32 - origin).min(size as i32 - 1);
+    for y in min(center.y, radii.y, 0)..=max(center.y, radii.y, 0, CHUNK_HEIGHT) {
+        let dy = (y as f32 + 0.5 - center.y) / radii.y;
+        for z in min(center.z, radii.z, origin_z)..=max(center.z, radii.z, origin_z, CHUNK_DEPTH) {
+            let dz = ((origin_z + z) as f32 + 0.5 - center.z) / radii.z;
+            for x in min(center.x, radii.x, origin_x)..=max(center.x, radii.x, origin_x, CHUNK_WIDTH) {
+                let dx = ((origin_x + x) as f32 + 0.5 - center.x) / radii.x;
+                if dx * dx + dy * dy + dz * dz < 1. {
+                    f(x as usize, y as usize, z as usize)
+                }
+            }
+        }
+    }
+}
+
+/**Random point inside of the chunk with height in range [from_y,to_y)*/
+pub fn random_point_in_chunk(rng: &mut Rng, chunk_x: i32, chunk_z: i32, from_y: i32, to_y: i32) -> glm::Vec3 {
+    glm::vec3(
+        (chunk_x * CHUNK_WIDTH as i32) as f32 + rng.next_f32() * CHUNK_WIDTH as f32,
+        rng.range(from_y, to_y) as f32,
+        (chunk_z * CHUNK_DEPTH as i32) as f32 + rng.next_f32() * CHUNK_DEPTH as f32,
+    )
+}
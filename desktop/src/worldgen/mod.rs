@@ -0,0 +1,8 @@
+pub mod noise;
+mod features;
+mod terrain;
+mod caves;
+mod ores;
+pub mod trees;
+
+pub use terrain::TerrainGenerator;
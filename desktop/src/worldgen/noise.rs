@@ -0,0 +1,162 @@
+/**Mixes the seed with integer coordinates into a well distributed 64-bit hash (based on SplitMix64 finalizer).
+ Everything produced by world generation is derived from such hashes, so the same seed always yields the same world,
+ no matter in which order chunks get generated.*/
+pub fn hash(seed: u64, x: i32, y: i32, z: i32) -> u64 {
+    let mut h = seed
+        ^ (x as u32 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (y as u32 as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ (z as u32 as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+    h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    h ^ (h >> 31)
+}
+
+/**Small deterministic random number generator (SplitMix64). Unlike rand::rngs::StdRng, its output is guaranteed
+ to never change between versions of dependencies.*/
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+    pub fn for_chunk(seed: u64, salt: u64, chunk_x: i32, chunk_z: i32) -> Self {
+        Self::new(hash(seed ^ salt, chunk_x, 0, chunk_z))
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+    /**Uniformly distributed in [0,1)*/
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+    /**Uniformly distributed in [from,to). Panics if the range is empty.*/
+    pub fn range(&mut self, from: i32, to: i32) -> i32 {
+        assert!(from < to);
+        from + (self.next_u64() % (to - from) as u64) as i32
+    }
+    pub fn chance(&mut self, probability: f32) -> bool {
+        self.next_f32() < probability
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/**Seeded gradient (Perlin) noise. Samples are roughly in range [-1,1] and equal 0 at integer coordinates.*/
+#[derive(Clone, Copy)]
+pub struct Noise {
+    seed: u64,
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+    fn gradient2(&self, ix: i32, iz: i32, dx: f32, dz: f32) -> f32 {
+        match hash(self.seed, ix, 0, iz) & 7 {
+            0 => dx + dz,
+            1 => dx - dz,
+            2 => -dx + dz,
+            3 => -dx - dz,
+            4 => dx,
+            5 => -dx,
+            6 => dz,
+            _ => -dz,
+        }
+    }
+    fn gradient3(&self, ix: i32, iy: i32, iz: i32, dx: f32, dy: f32, dz: f32) -> f32 {
+        match hash(self.seed, ix, iy, iz) % 12 {
+            0 => dx + dy,
+            1 => -dx + dy,
+            2 => dx - dy,
+            3 => -dx - dy,
+            4 => dx + dz,
+            5 => -dx + dz,
+            6 => dx - dz,
+            7 => -dx - dz,
+            8 => dy + dz,
+            9 => -dy + dz,
+            10 => dy - dz,
+            _ => -dy - dz,
+        }
+    }
+    pub fn sample2(&self, x: f32, z: f32) -> f32 {
+        let (fx, fz) = (x.floor(), z.floor());
+        let (ix, iz) = (fx as i32, fz as i32);
+        let (dx, dz) = (x - fx, z - fz);
+        let (u, v) = (fade(dx), fade(dz));
+        let n00 = self.gradient2(ix, iz, dx, dz);
+        let n10 = self.gradient2(ix + 1, iz, dx - 1., dz);
+        let n01 = self.gradient2(ix, iz + 1, dx, dz - 1.);
+        let n11 = self.gradient2(ix + 1, iz + 1, dx - 1., dz - 1.);
+        lerp(lerp(n00, n10, u), lerp(n01, n11, u), v) * 0.7
+    }
+    pub fn sample3(&self, x: f32, y: f32, z: f32) -> f32 {
+        let (fx, fy, fz) = (x.floor(), y.floor(), z.floor());
+        let (ix, iy, iz) = (fx as i32, fy as i32, fz as i32);
+        let (dx, dy, dz) = (x - fx, y - fy, z - fz);
+        let (u, v, w) = (fade(dx), fade(dy), fade(dz));
+        let n000 = self.gradient3(ix, iy, iz, dx, dy, dz);
+        let n100 = self.gradient3(ix + 1, iy, iz, dx - 1., dy, dz);
+        let n010 = self.gradient3(ix, iy + 1, iz, dx, dy - 1., dz);
+        let n110 = self.gradient3(ix + 1, iy + 1, iz, dx - 1., dy - 1., dz);
+        let n001 = self.gradient3(ix, iy, iz + 1, dx, dy, dz - 1.);
+        let n101 = self.gradient3(ix + 1, iy, iz + 1, dx - 1., dy, dz - 1.);
+        let n011 = self.gradient3(ix, iy + 1, iz + 1, dx, dy - 1., dz - 1.);
+        let n111 = self.gradient3(ix + 1, iy + 1, iz + 1, dx - 1., dy - 1., dz - 1.);
+        let bottom = lerp(lerp(n000, n100, u), lerp(n010, n110, u), v);
+        let top = lerp(lerp(n001, n101, u), lerp(n011, n111, u), v);
+        lerp(bottom, top, w) * 0.9
+    }
+}
+
+/**Sum of several octaves of noise, each with doubled frequency and halved amplitude (with the default
+ lacunarity and persistence). The result is normalized back to roughly [-1,1].*/
+#[derive(Clone)]
+pub struct FractalNoise {
+    octaves: Vec<Noise>,
+    frequency: f32,
+    lacunarity: f32,
+    persistence: f32,
+}
+
+impl FractalNoise {
+    pub fn new(seed: u64, octaves: usize, frequency: f32) -> Self {
+        Self::with_params(seed, octaves, frequency, 2., 0.5)
+    }
+    pub fn with_params(seed: u64, octaves: usize, frequency: f32, lacunarity: f32, persistence: f32) -> Self {
+        assert!(octaves > 0);
+        let octaves = (0..octaves).map(|o| Noise::new(hash(seed, o as i32, 0, 0))).collect();
+        Self { octaves, frequency, lacunarity, persistence }
+    }
+    fn sum(&self, f: impl Fn(&Noise, f32) -> f32) -> f32 {
+        let mut frequency = self.frequency;
+        let mut amplitude = 1.;
+        let mut total = 0.;
+        let mut max = 0.;
+        for octave in &self.octaves {
+            total += f(octave, frequency) * amplitude;
+            max += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.persistence;
+        }
+        total / max
+    }
+    pub fn sample2(&self, x: f32, z: f32) -> f32 {
+        self.sum(|n, f| n.sample2(x * f, z * f))
+    }
+    pub fn sample3(&self, x: f32, y: f32, z: f32) -> f32 {
+        self.sum(|n, f| n.sample3(x * f, y * f, z * f))
+    }
+}
@@ -0,0 +1,65 @@
+use crate::blocks::block_properties::{COAL_ORE, DIAMOND_ORE, DIRT, GOLD_ORE, GRAVEL, IRON_ORE, REDSTONE_ORE, STONE};
+use crate::blocks::{Block, ChunkBlocks};
+use crate::worldgen::features::{for_each_block_in_ellipsoid, for_each_origin_chunk, random_point_in_chunk};
+use crate::worldgen::noise::hash;
+
+/**Veins of one kind of block scattered through the stone*/
+pub struct OreKind {
+    pub block: Block,
+    /**Veins are centered in range [min_y,max_y)*/
+    pub min_y: i32,
+    pub max_y: i32,
+    /**Average number of veins originating in a single chunk*/
+    pub veins_per_chunk: f32,
+    /**Number of blobs making up a single vein*/
+    pub size: i32,
+    /**Blocks that can be replaced by this vein*/
+    pub replaces: &'static [Block],
+}
+
+const STONE_ONLY: &[Block] = &[STONE];
+const STONE_AND_DIRT: &[Block] = &[STONE, DIRT];
+
+pub const DEFAULT_ORES: [OreKind; 6] = [
+    OreKind { block: GRAVEL, min_y: 5, max_y: 100, veins_per_chunk: 4., size: 8, replaces: STONE_AND_DIRT },
+    OreKind { block: COAL_ORE, min_y: 5, max_y: 128, veins_per_chunk: 12., size: 6, replaces: STONE_ONLY },
+    OreKind { block: IRON_ORE, min_y: 5, max_y: 64, veins_per_chunk: 8., size: 4, replaces: STONE_ONLY },
+    OreKind { block: GOLD_ORE, min_y: 5, max_y: 32, veins_per_chunk: 2., size: 4, replaces: STONE_ONLY },
+    OreKind { block: REDSTONE_ORE, min_y: 5, max_y: 16, veins_per_chunk: 4., size: 4, replaces: STONE_ONLY },
+    OreKind { block: DIAMOND_ORE, min_y: 5, max_y: 16, veins_per_chunk: 1., size: 3, replaces: STONE_ONLY },
+];
+
+/**Scatters veins of ores (and pockets of gravel) with per-kind depth ranges and frequencies*/
+pub struct Ores {
+    seed: u64,
+    kinds: Vec<OreKind>,
+}
+
+impl Ores {
+    pub fn new(seed: u64, kinds: Vec<OreKind>) -> Self {
+        Self { seed, kinds }
+    }
+    pub fn populate(&self, chunk_x: i32, chunk_z: i32, chunk: &mut ChunkBlocks) {
+        for (salt, kind) in self.kinds.iter().enumerate() {
+            let salt = hash(self.seed, salt as i32, kind.block.idx() as i32, 0x4F52);
+            // Veins are small enough to never reach further than the immediate neighbours
+            for_each_origin_chunk(self.seed, salt, chunk_x, chunk_z, 1, |origin_x, origin_z, rng| {
+                let whole = kind.veins_per_chunk as i32;
+                let veins = whole + rng.chance(kind.veins_per_chunk - whole as f32) as i32;
+                for _ in 0..veins {
+                    let mut position = random_point_in_chunk(rng, origin_x, origin_z, kind.min_y, kind.max_y);
+                    for _ in 0..kind.size {
+                        let radius = 0.8 + rng.next_f32();
+                        let radii = glm::vec3(radius, radius, radius);
+                        for_each_block_in_ellipsoid(chunk_x, chunk_z, &position, &radii, |x, y, z| {
+                            if kind.replaces.contains(chunk.get_block(x, y, z)) {
+                                chunk.set_block(x, y, z, kind.block)
+                            }
+                        });
+                        position += glm::vec3(rng.next_f32() - 0.5, rng.next_f32() - 0.5, rng.next_f32() - 0.5) * 2.;
+                    }
+                }
+            })
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use crate::blocks::block_properties::{BEDROCK, DIRT, GRASS, SAND, STONE, WATER};
+use crate::blocks::{ChunkBlocks, ChunkGenerator, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH};
+use crate::worldgen::caves::Caves;
+use crate::worldgen::noise::{hash, FractalNoise};
+use crate::worldgen::ores::{Ores, DEFAULT_ORES};
+use crate::worldgen::features::{for_each_origin_chunk, random_point_in_chunk};
+use crate::worldgen::trees::random_tree;
+
+pub const SEA_LEVEL: i32 = 62;
+/**Columns whose surface lies within this distance around the sea level are covered with sand*/
+const BEACH_HALF_HEIGHT: i32 = 2;
+const BEDROCK_MAX_HEIGHT: i32 = 4;
+const TREE_SALT: u64 = 0x5452_4545;
+const MAX_TREES_PER_CHUNK: i32 = 4;
+
+/**Seed-driven terrain: a layered fractal heightmap of stone covered with dirt and grass,
+ water filling everything below sea level, sandy beaches and a bedrock floor.
+ The terrain is then carved by caves and populated with ores.*/
+pub struct TerrainGenerator {
+    seed: u64,
+    continents: FractalNoise,
+    hills: FractalNoise,
+    roughness: FractalNoise,
+    soil: FractalNoise,
+    caves: Caves,
+    ores: Ores,
+    forests: FractalNoise,
+}
+
+impl TerrainGenerator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            continents: FractalNoise::new(hash(seed, 1, 0, 0), 3, 1. / 512.),
+            hills: FractalNoise::new(hash(seed, 2, 0, 0), 5, 1. / 128.),
+            roughness: FractalNoise::new(hash(seed, 3, 0, 0), 2, 1. / 256.),
+            soil: FractalNoise::new(hash(seed, 4, 0, 0), 2, 1. / 32.),
+            caves: Caves::new(hash(seed, 5, 0, 0)),
+            ores: Ores::new(hash(seed, 6, 0, 0), DEFAULT_ORES.into()),
+            forests: FractalNoise::new(hash(seed, 7, 0, 0), 2, 1. / 256.),
+        }
+    }
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    /**Height of the topmost solid block of the column (before any carving done by later passes)*/
+    pub fn height_at(&self, x: i32, z: i32) -> i32 {
+        let (fx, fz) = (x as f32, z as f32);
+        let continent = self.continents.sample2(fx, fz);
+        // Hills get more pronounced in rough areas and further inland
+        let roughness = (self.roughness.sample2(fx, fz) * 0.5 + 0.5).max(0.) + continent.max(0.);
+        let hills = self.hills.sample2(fx, fz);
+        let height = SEA_LEVEL as f32 + 2. + continent * 24. + hills * (8. + 40. * roughness);
+        (height as i32).max(BEDROCK_MAX_HEIGHT + 1).min(CHUNK_HEIGHT as i32 - 1)
+    }
+    /**Grass grows on columns above the beaches*/
+    pub fn is_grass_at(&self, x: i32, z: i32) -> bool {
+        self.height_at(x, z) > SEA_LEVEL + BEACH_HALF_HEIGHT
+    }
+    fn soil_depth(&self, x: i32, z: i32) -> i32 {
+        3 + (self.soil.sample2(x as f32, z as f32) * 2.).round() as i32
+    }
+    fn is_bedrock(&self, x: i32, y: i32, z: i32) -> bool {
+        // Bottom layer is always bedrock, the layers above become less and less likely to be bedrock
+        y == 0 || (y < BEDROCK_MAX_HEIGHT && (hash(self.seed, x, y, z) % BEDROCK_MAX_HEIGHT as u64) as i32 >= y)
+    }
+    /**Trees can overhang into neighbouring chunks, so every chunk replays trees planted in its neighbours.
+    Whether a tree is planted depends only on the seed and heightmap, never on blocks of other chunks.*/
+    fn plant_trees(&self, chunk_x: i32, chunk_z: i32, chunk: &mut ChunkBlocks) {
+        for_each_origin_chunk(self.seed, TREE_SALT, chunk_x, chunk_z, 1, |origin_x, origin_z, rng| {
+            let center = random_point_in_chunk(rng, origin_x, origin_z, 0, 1);
+            let density = self.forests.sample2(center.x, center.z);
+            let trees = ((density + 0.2) * MAX_TREES_PER_CHUNK as f32 * 2.).max(0.) as i32;
+            for _ in 0..trees.min(MAX_TREES_PER_CHUNK) {
+                let position = random_point_in_chunk(rng, origin_x, origin_z, 0, 1);
+                let (x, z) = (position.x.floor() as i32, position.z.floor() as i32);
+                let tree = random_tree(rng);
+                if self.is_grass_at(x, z) {
+                    tree.stamp_into_chunk(x, self.height_at(x, z) + 1, z, chunk_x, chunk_z, chunk);
+                }
+            }
+        })
+    }
+    fn generate_terrain(&self, chunk_x: i32, chunk_z: i32, chunk: &mut ChunkBlocks) {
+        for local_z in 0..CHUNK_DEPTH {
+            for local_x in 0..CHUNK_WIDTH {
+                let x = chunk_x * CHUNK_WIDTH as i32 + local_x as i32;
+                let z = chunk_z * CHUNK_DEPTH as i32 + local_z as i32;
+                let height = self.height_at(x, z);
+                let soil_depth = self.soil_depth(x, z);
+                let is_beach = height <= SEA_LEVEL + BEACH_HALF_HEIGHT;
+                for y in 0..=height.max(SEA_LEVEL) {
+                    let block = if self.is_bedrock(x, y, z) {
+                        BEDROCK
+                    } else if y > height {
+                        WATER
+                    } else if y <= height - soil_depth {
+                        STONE
+                    } else if is_beach {
+                        SAND
+                    } else if y == height {
+                        GRASS
+                    } else {
+                        DIRT
+                    };
+                    chunk.set_block(local_x, y as usize, local_z, block);
+                }
+            }
+        }
+    }
+}
+
+impl ChunkGenerator for TerrainGenerator {
+    fn generate(&self, chunk_x: i32, chunk_z: i32, chunk: &mut ChunkBlocks) {
+        self.generate_terrain(chunk_x, chunk_z, chunk);
+        // water of neighbouring chunks is known without generating them, because carving never touches it
+        self.caves.carve(chunk_x, chunk_z, chunk, &|x, y, z| self.height_at(x, z) < y && y <= SEA_LEVEL);
+        self.ores.populate(chunk_x, chunk_z, chunk);
+        self.plant_trees(chunk_x, chunk_z, chunk);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::block_registry::init_for_tests;
+
+    fn generate(generator: &TerrainGenerator, chunk_x: i32, chunk_z: i32) -> ChunkBlocks {
+        let mut chunk = ChunkBlocks::new();
+        generator.generate(chunk_x, chunk_z, &mut chunk);
+        chunk
+    }
+
+    /**Highest block of the column that belongs to the terrain itself rather than to water or trees*/
+    fn surface(chunk: &ChunkBlocks, x: usize, z: usize) -> i32 {
+        (0..CHUNK_HEIGHT).rev()
+            .find(|&y| [STONE, DIRT, GRASS, SAND, BEDROCK].contains(chunk.get_block(x, y, z)))
+            .unwrap() as i32
+    }
+
+    fn same_blocks(a: &ChunkBlocks, b: &ChunkBlocks) -> bool {
+        (0..CHUNK_WIDTH).all(|x| (0..CHUNK_DEPTH).all(|z| (0..CHUNK_HEIGHT).all(|y| a.get_block(x, y, z) == b.get_block(x, y, z))))
+    }
+
+    #[test]
+    fn same_seed_generates_same_chunks() {
+        init_for_tests();
+        let generator = TerrainGenerator::new(1234);
+        let first = generate(&generator, 3, -2);
+        assert!(same_blocks(&first, &generate(&generator, 3, -2)));
+        assert!(same_blocks(&first, &generate(&TerrainGenerator::new(1234), 3, -2)));
+        assert!(!same_blocks(&first, &generate(&TerrainGenerator::new(4321), 3, -2)));
+    }
+
+    #[test]
+    fn neighbouring_chunks_meet_at_the_border() {
+        init_for_tests();
+        let generator = TerrainGenerator::new(1234);
+        let (west, east) = (generate(&generator, 3, -2), generate(&generator, 4, -2));
+        let border_x = 4 * CHUNK_WIDTH as i32;
+        for z in 0..CHUNK_DEPTH {
+            let world_z = -2 * CHUNK_DEPTH as i32 + z as i32;
+            let (west_height, east_height) = (generator.height_at(border_x - 1, world_z), generator.height_at(border_x, world_z));
+            assert_eq!(surface(&west, CHUNK_WIDTH - 1, z), west_height);
+            assert_eq!(surface(&east, 0, z), east_height);
+            assert!((west_height - east_height).abs() <= 3, "cliff between {} and {}", west_height, east_height);
+        }
+    }
+}
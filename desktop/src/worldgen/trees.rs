@@ -0,0 +1,63 @@
+use crate::blocks::block_properties::{LEAVES, WOOD};
+use crate::blocks::Schematic;
+use crate::worldgen::noise::Rng;
+
+/**Trunk of wood topped with a rounded blob of leaves. The origin is at the bottom of the trunk.*/
+pub fn oak_tree(rng: &mut Rng) -> Schematic {
+    let trunk_height = rng.range(4, 7);
+    let radius = 2;
+    let size = (radius * 2 + 1) as usize;
+    let mut tree = Schematic::new(size, trunk_height as usize + 2, size)
+        .with_origin(radius, 0, radius)
+        .with_air_keeps_existing(true);
+    for y in trunk_height - 2..trunk_height + 2 {
+        // The crown narrows at the top
+        let layer_radius = if y >= trunk_height { radius - 1 } else { radius };
+        for z in -layer_radius..=layer_radius {
+            for x in -layer_radius..=layer_radius {
+                let is_corner = x.abs() == layer_radius && z.abs() == layer_radius;
+                if !is_corner || rng.chance(0.5) {
+                    tree.try_set_block(radius + x, y, radius + z, LEAVES);
+                }
+            }
+        }
+    }
+    for y in 0..trunk_height {
+        tree.set_block(radius as usize, y as usize, radius as usize, WOOD);
+    }
+    tree
+}
+
+/**Tall trunk surrounded by conical layers of leaves. The origin is at the bottom of the trunk.*/
+pub fn spruce_tree(rng: &mut Rng) -> Schematic {
+    let trunk_height = rng.range(6, 10);
+    let radius = 3;
+    let size = (radius * 2 + 1) as usize;
+    let mut tree = Schematic::new(size, trunk_height as usize + 2, size)
+        .with_origin(radius, 0, radius)
+        .with_air_keeps_existing(true);
+    for y in 2..trunk_height + 2 {
+        // The cone widens by one block every two layers down from the top
+        let layer_radius = ((trunk_height + 2 - y) / 2).min(radius);
+        for z in -layer_radius..=layer_radius {
+            for x in -layer_radius..=layer_radius {
+                if x.abs() + z.abs() <= layer_radius + 1 {
+                    tree.try_set_block(radius + x, y, radius + z, LEAVES);
+                }
+            }
+        }
+    }
+    for y in 0..trunk_height {
+        tree.set_block(radius as usize, y as usize, radius as usize, WOOD);
+    }
+    tree
+}
+
+/**Randomly picks one of the available kinds of trees*/
+pub fn random_tree(rng: &mut Rng) -> Schematic {
+    if rng.chance(0.3) {
+        spruce_tree(rng)
+    } else {
+        oak_tree(rng)
+    }
+}